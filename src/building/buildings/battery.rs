@@ -0,0 +1,15 @@
+use lombok::Getter;
+
+use crate::common::{position::Position, EntityId};
+
+#[derive(Getter, Debug, Clone)]
+pub struct Battery {
+    id: EntityId,
+    position: Position,
+}
+
+impl Battery {
+    pub fn new(id: EntityId, position: Position) -> Self {
+        Self { id, position }
+    }
+}
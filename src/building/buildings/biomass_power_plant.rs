@@ -1,15 +1,23 @@
 use lombok::Getter;
 
-use crate::common::{position::Position, EntityId};
+use crate::common::{
+    position::{Orientation, Position},
+    EntityId,
+};
 
 #[derive(Getter, Debug, Clone)]
 pub struct BiomassPowerPlant {
     id: EntityId,
     position: Position,
+    orientation: Orientation,
 }
 
 impl BiomassPowerPlant {
-    pub fn new(id: EntityId, position: Position) -> Self {
-        Self { id, position }
+    pub fn new(id: EntityId, position: Position, orientation: Orientation) -> Self {
+        Self {
+            id,
+            position,
+            orientation,
+        }
     }
 }
@@ -1,20 +1,30 @@
 use lombok::Getter;
 
-use crate::common::{position::Position, EntityId};
+use crate::common::{
+    position::{Orientation, Position},
+    EntityId,
+};
 
 #[derive(Getter, Debug, Clone)]
 pub struct House {
     id: EntityId,
     position: Position,
+    orientation: Orientation,
     current_residents: u32,
     max_residents: u32,
 }
 
 impl House {
-    pub fn new(id: EntityId, position: Position, max_residents: u32) -> Self {
+    pub fn new(
+        id: EntityId,
+        position: Position,
+        orientation: Orientation,
+        max_residents: u32,
+    ) -> Self {
         Self {
             id,
             position,
+            orientation,
             current_residents: 0,
             max_residents,
         }
@@ -25,9 +35,20 @@ impl House {
         debug_assert!(self.max_residents >= self.current_residents);
     }
 
-    #[allow(dead_code)]
+    /// Return the number of residents added
+    pub fn upgrade_density(&mut self, max_residents: u32) -> u32 {
+        let added = max_residents.saturating_sub(self.max_residents);
+        self.max_residents += added;
+        added
+    }
+
     pub fn inhabitant_left(&mut self) {
         debug_assert!(self.current_residents >= 1);
         self.current_residents -= 1;
     }
+
+    /// Everybody left, the upgraded density stays
+    pub fn vacate(&mut self) {
+        self.current_residents = 0;
+    }
 }
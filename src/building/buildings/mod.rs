@@ -1,12 +1,16 @@
+mod battery;
 mod biomass_power_plant;
 mod garden;
 mod house;
 mod office;
+mod school;
 pub mod snapshot;
 mod street;
 
+pub use battery::*;
 pub use biomass_power_plant::*;
 pub use garden::*;
 pub use house::*;
 pub use office::*;
+pub use school::*;
 pub use street::*;
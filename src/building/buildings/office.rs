@@ -1,20 +1,30 @@
 use lombok::Getter;
 
-use crate::common::{position::Position, EntityId};
+use crate::common::{
+    position::{Orientation, Position},
+    EntityId,
+};
 
 #[derive(Getter, Debug, Clone)]
 pub struct Office {
     id: EntityId,
     position: Position,
+    orientation: Orientation,
     current_workers: u32,
     max_workers: u32,
 }
 
 impl Office {
-    pub fn new(id: EntityId, position: Position, max_workers: u32) -> Self {
+    pub fn new(
+        id: EntityId,
+        position: Position,
+        orientation: Orientation,
+        max_workers: u32,
+    ) -> Self {
         Self {
             id,
             position,
+            orientation,
             current_workers: 0,
             max_workers,
         }
@@ -25,9 +35,12 @@ impl Office {
         debug_assert!(self.max_workers >= self.current_workers);
     }
 
-    #[allow(dead_code)]
     pub fn worker_left(&mut self) {
         debug_assert!(self.current_workers >= 1);
         self.current_workers -= 1;
     }
+
+    pub fn vacate(&mut self) {
+        self.current_workers = 0;
+    }
 }
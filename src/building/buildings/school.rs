@@ -0,0 +1,36 @@
+use lombok::Getter;
+
+use crate::common::{position::Position, EntityId};
+
+#[derive(Getter, Debug, Clone)]
+pub struct School {
+    id: EntityId,
+    position: Position,
+    current_students: u32,
+    max_students: u32,
+}
+
+impl School {
+    pub fn new(id: EntityId, position: Position, max_students: u32) -> Self {
+        Self {
+            id,
+            position,
+            current_students: 0,
+            max_students,
+        }
+    }
+
+    pub fn students_arrived(&mut self, count: u32) {
+        self.current_students += count;
+        debug_assert!(self.max_students >= self.current_students);
+    }
+
+    pub fn student_left(&mut self) {
+        debug_assert!(self.current_students >= 1);
+        self.current_students -= 1;
+    }
+
+    pub fn vacate(&mut self) {
+        self.current_students = 0;
+    }
+}
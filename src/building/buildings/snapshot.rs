@@ -12,6 +12,8 @@ pub enum BuildingSnapshot {
     Street(StreetSnapshot),
     Garden(GardenSnapshot),
     BiomassPowerPlant(BiomassPowerPlantSnapshot),
+    Battery(BatterySnapshot),
+    School(SchoolSnapshot),
 }
 
 #[allow(dead_code)]
@@ -37,6 +39,8 @@ impl BuildingSnapshot {
             BuildingSnapshot::Garden(b) => b.get_position(),
             BuildingSnapshot::Street(b) => b.get_position(),
             BuildingSnapshot::BiomassPowerPlant(b) => b.get_position(),
+            BuildingSnapshot::Battery(b) => b.get_position(),
+            BuildingSnapshot::School(b) => b.get_position(),
         }
     }
 
@@ -47,6 +51,8 @@ impl BuildingSnapshot {
             BuildingSnapshot::Garden(b) => b.get_id(),
             BuildingSnapshot::Street(b) => b.get_id(),
             BuildingSnapshot::BiomassPowerPlant(b) => b.get_id(),
+            BuildingSnapshot::Battery(b) => b.get_id(),
+            BuildingSnapshot::School(b) => b.get_id(),
         }
     }
 }
@@ -80,6 +86,16 @@ impl From<&Building> for BuildingSnapshot {
                     position: *b.get_position(),
                 })
             }
+            Building::Battery(b) => BuildingSnapshot::Battery(BatterySnapshot {
+                id: *b.get_id(),
+                position: *b.get_position(),
+            }),
+            Building::School(s) => BuildingSnapshot::School(SchoolSnapshot {
+                id: *s.get_id(),
+                position: *s.get_position(),
+                current_students: *s.get_current_students(),
+                max_students: *s.get_max_students(),
+            }),
         }
     }
 }
@@ -113,3 +129,16 @@ pub struct BiomassPowerPlantSnapshot {
     pub id: EntityId,
     pub position: Position,
 }
+#[derive(Getter, Debug)]
+pub struct BatterySnapshot {
+    pub id: EntityId,
+    pub position: Position,
+}
+#[allow(dead_code)]
+#[derive(Getter, Debug)]
+pub struct SchoolSnapshot {
+    pub id: EntityId,
+    pub position: Position,
+    pub current_students: u32,
+    pub max_students: u32,
+}
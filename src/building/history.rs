@@ -0,0 +1,98 @@
+use std::collections::VecDeque;
+
+use crate::common::position::Position;
+
+use super::manager::{Building, BuildingUnderConstruction};
+
+/// What a tile hosts
+#[derive(Debug, Clone)]
+pub enum TileState {
+    Empty,
+    UnderConstruction(BuildingUnderConstruction),
+    /// Without inhabitants: they don't come back with the building
+    Completed(Building),
+}
+
+/// The tiles changed by a player action, with what they hosted before.
+/// Undoing it puts back those states, keeping the replaced ones for the redo
+#[derive(Debug)]
+pub struct PlayerAction {
+    pub tiles: Vec<(Position, TileState)>,
+    /// Paid when done, refunded when undone
+    pub cost: u64,
+}
+
+/// The undo and redo stacks: a new action forgets what was undone
+pub struct History<T> {
+    done: VecDeque<T>,
+    undone: Vec<T>,
+    max_size: usize,
+}
+
+impl<T> History<T> {
+    pub fn new(max_size: usize) -> Self {
+        Self {
+            done: Default::default(),
+            undone: Default::default(),
+            max_size,
+        }
+    }
+
+    /// The oldest actions are forgotten over `max_size`
+    pub fn record(&mut self, action: T) {
+        self.undone.clear();
+        self.push_done(action);
+    }
+
+    /// The last action, to undo
+    pub fn undo(&mut self) -> Option<T> {
+        self.done.pop_back()
+    }
+
+    /// The last undone action, to redo
+    pub fn redo(&mut self) -> Option<T> {
+        self.undone.pop()
+    }
+
+    /// The action can be undone: redone, or refused to undo
+    pub fn push_done(&mut self, action: T) {
+        self.done.push_back(action);
+        if self.done.len() > self.max_size {
+            self.done.pop_front();
+        }
+    }
+
+    /// The action can be redone: undone, or refused to redo
+    pub fn push_undone(&mut self, action: T) {
+        self.undone.push(action);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_undo_and_redo() {
+        let mut history = History::new(2);
+        history.record(1);
+        history.record(2);
+        history.record(3);
+
+        // Only the last 2 actions are kept
+        assert_eq!(history.undo(), Some(3));
+        history.push_undone(3);
+        assert_eq!(history.undo(), Some(2));
+        history.push_undone(2);
+        assert_eq!(history.undo(), None);
+
+        assert_eq!(history.redo(), Some(2));
+        history.push_done(2);
+
+        // A new action forgets what was undone
+        history.record(4);
+        assert_eq!(history.redo(), None);
+        assert_eq!(history.undo(), Some(4));
+        assert_eq!(history.undo(), Some(2));
+    }
+}
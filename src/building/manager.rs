@@ -1,15 +1,24 @@
-use std::sync::Arc;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::Arc,
+};
 
-use bevy::utils::{HashMap, HashSet};
+use crate::common::{
+    configuration::Configuration,
+    position::{Footprint, Orientation, Position},
+    EntityId,
+};
 
-use crate::common::{configuration::Configuration, position::Position, EntityId};
-
-use super::buildings::{BiomassPowerPlant, Garden, House, Office, Street};
+use super::buildings::{
+    snapshot::{BuildingSnapshot, HouseSnapshot},
+    Battery, BiomassPowerPlant, Garden, House, Office, School, Street,
+};
 
 pub struct BuildingManager {
     configuration: Arc<Configuration>,
-    position_already_used: HashSet<Position>,
-    buildings: HashMap<EntityId, Building>,
+    /// Every tile of the footprints, with the position of its building
+    position_already_used: BTreeMap<Position, Position>,
+    buildings: BTreeMap<EntityId, Building>,
 }
 impl BuildingManager {
     pub(super) fn new(configuration: Arc<Configuration>) -> Self {
@@ -24,9 +33,7 @@ impl BuildingManager {
         &mut self,
         building: Building,
     ) -> Result<BuildingUnderConstruction, &'static str> {
-        if !self.position_already_used.insert(*building.get_position()) {
-            return Err("Position already used");
-        }
+        self.reserve(&building)?;
 
         let step_to_reach = building.get_step_to_reach(&self.configuration);
 
@@ -39,16 +46,196 @@ impl BuildingManager {
         })
     }
 
+    /// Everything that would prevent the building from being built or from working
+    pub(super) fn check_placement(
+        &self,
+        building: &Building,
+        context: &PlacementContext,
+    ) -> Vec<PlacementProblem> {
+        let mut problems = vec![];
+        let tiles: Vec<_> = self
+            .get_footprint(building)
+            .tiles(building.get_position())
+            .collect();
+        if tiles.iter().any(|tile| self.is_position_used(tile)) {
+            problems.push(PlacementProblem::PositionAlreadyUsed);
+        }
+        if !tiles.iter().all(|tile| self.is_inside_map(tile)) {
+            problems.push(PlacementProblem::OutOfMap);
+        }
+        if !context.palatable {
+            problems.push(PlacementProblem::InsufficientPalatability);
+        }
+        if context.funds < building.get_cost(&self.configuration) {
+            problems.push(PlacementProblem::InsufficientFunds);
+        }
+        if building.needs_street_access() && !context.street_access {
+            problems.push(PlacementProblem::NoStreetAccess);
+        }
+        problems
+    }
+
+    /// The free tiles from `from` to `to`: straight if aligned,
+    /// otherwise along the x axis first and then along the y axis
+    pub(super) fn street_path(&self, from: Position, to: Position) -> Vec<Position> {
+        let step = |from: i64, to: i64| if from <= to { 1 } else { -1 };
+
+        let mut path = vec![from];
+        let mut current = from;
+        while current.x != to.x {
+            current.x += step(current.x, to.x);
+            path.push(current);
+        }
+        while current.y != to.y {
+            current.y += step(current.y, to.y);
+            path.push(current);
+        }
+
+        path.retain(|position| !self.is_position_used(position));
+        path
+    }
+
+    /// The free tiles of the rectangle with `from` and `to` as opposite corners
+    pub(super) fn area(&self, from: Position, to: Position) -> Vec<Position> {
+        let (min_x, max_x) = (from.x.min(to.x), from.x.max(to.x));
+        let (min_y, max_y) = (from.y.min(to.y), from.y.max(to.y));
+
+        (min_y..=max_y)
+            .flat_map(|y| (min_x..=max_x).map(move |x| Position { x, y }))
+            .filter(|position| !self.is_position_used(position))
+            .collect()
+    }
+
+    /// The construction is cancelled: the position is free again
+    pub(super) fn cancel_building_creation(
+        &mut self,
+        building_under_construction: &BuildingUnderConstruction,
+    ) {
+        self.release(&building_under_construction.building);
+    }
+
+    /// The construction starts again from where it was cancelled
+    pub(super) fn restore_building_creation(
+        &mut self,
+        building_under_construction: BuildingUnderConstruction,
+    ) -> Result<BuildingUnderConstruction, &'static str> {
+        self.reserve(&building_under_construction.building)?;
+        Ok(building_under_construction)
+    }
+
+    /// Remove the completed building, returned as it was
+    pub(super) fn demolish(&mut self, id: &EntityId) -> Option<Building> {
+        let building = self.buildings.remove(id)?;
+        self.release(&building);
+        Some(building)
+    }
+
+    /// Place a demolished building again, as it was completed
+    pub(super) fn restore_building(&mut self, building: Building) -> Result<(), &'static str> {
+        self.reserve(&building)?;
+        self.buildings.insert(building.get_id(), building);
+        Ok(())
+    }
+
+    /// The same building without inhabitants, workers and students, as large as it was
+    pub(super) fn vacated(&self, building: &Building) -> Building {
+        let mut building = building.clone();
+        match &mut building {
+            Building::House(house) => house.vacate(),
+            Building::Office(office) => office.vacate(),
+            Building::School(school) => school.vacate(),
+            Building::Garden(_)
+            | Building::Street(_)
+            | Building::BiomassPowerPlant(_)
+            | Building::Battery(_) => {}
+        }
+        building
+    }
+
+    /// Take every tile of the footprint, if all of them are free
+    fn reserve(&mut self, building: &Building) -> Result<(), &'static str> {
+        let position = *building.get_position();
+        let tiles: Vec<_> = self.get_footprint(building).tiles(&position).collect();
+        if !tiles.iter().all(|tile| self.is_inside_map(tile)) {
+            return Err("Out of the map");
+        }
+        if tiles.iter().any(|tile| self.is_position_used(tile)) {
+            return Err("Position already used");
+        }
+        self.position_already_used
+            .extend(tiles.into_iter().map(|tile| (tile, position)));
+        Ok(())
+    }
+
+    fn release(&mut self, building: &Building) {
+        for tile in self.get_footprint(building).tiles(building.get_position()) {
+            self.position_already_used.remove(&tile);
+        }
+    }
+
+    /// The footprint is in the map and every tile is free
+    /// or hosts one of the buildings at `replaced`
+    pub(super) fn fits(&self, building: &Building, replaced: &[Position]) -> bool {
+        self.get_footprint(building)
+            .tiles(building.get_position())
+            .all(|tile| {
+                self.is_inside_map(&tile)
+                    && self
+                        .get_building_position(&tile)
+                        .is_none_or(|position| replaced.contains(position))
+            })
+    }
+
+    fn is_inside_map(&self, position: &Position) -> bool {
+        let game = &self.configuration.game;
+        (0..game.width_table as i64).contains(&position.x)
+            && (0..game.depth_table as i64).contains(&position.y)
+    }
+
+    pub(super) fn is_position_used(&self, position: &Position) -> bool {
+        self.position_already_used.contains_key(position)
+    }
+
+    /// The position of the building covering the tile
+    pub(super) fn get_building_position(&self, position: &Position) -> Option<&Position> {
+        self.position_already_used.get(position)
+    }
+
+    /// As placed: turned with the building
+    pub(super) fn get_footprint(&self, building: &Building) -> Footprint {
+        building
+            .get_footprint(&self.configuration)
+            .rotated(building.get_orientation().unwrap_or_default())
+    }
+
+    /// The tiles where a street gives access to the building:
+    /// in front of it, or all around it when it has no facing
+    pub(super) fn get_entrances(&self, building: &Building) -> BTreeSet<Position> {
+        let footprint = self.get_footprint(building);
+        match building.get_orientation() {
+            Some(orientation) => footprint.front(building.get_position(), orientation),
+            None => footprint.neighbors(building.get_position()),
+        }
+    }
+
+    pub(super) fn get_cost(&self, building: &Building) -> u64 {
+        building.get_cost(&self.configuration)
+    }
+
+    /// Progress for `ticks` steps, return true when the building is completed
     pub(super) fn make_progress(
         &mut self,
         building_under_construction: &mut BuildingUnderConstruction,
+        ticks: u32,
     ) -> bool {
         debug_assert!(
             building_under_construction.progress_status.current_step
                 < building_under_construction.progress_status.step_to_reach
         );
 
-        building_under_construction.progress_status.make_progress();
+        building_under_construction
+            .progress_status
+            .make_progress(ticks);
 
         building_under_construction.progress_status.current_step
             >= building_under_construction.progress_status.step_to_reach
@@ -58,42 +245,115 @@ impl BuildingManager {
         &mut self,
         building_under_construction: &mut BuildingUnderConstruction,
     ) {
-        let entry = self
-            .buildings
-            .entry(building_under_construction.building.get_id());
-        entry.insert(building_under_construction.building.clone());
+        self.buildings.insert(
+            building_under_construction.building.get_id(),
+            building_under_construction.building.clone(),
+        );
     }
 
     pub(super) fn inhabitants_arrived_at_home(&mut self, house_id: EntityId, count: u32) {
-        let house = self
-            .buildings
-            .get_mut(&house_id)
-            .expect("house should exists");
+        // The house could be demolished meanwhile
+        let house = match self.buildings.get_mut(&house_id) {
+            None => return,
+            Some(house) => house,
+        };
         let house = house.to_house_mut();
 
         house.inhabitants_arrived(count);
     }
 
     pub(super) fn workers_found_job(&mut self, office_id: EntityId, count: u32) {
-        let office = self
-            .buildings
-            .get_mut(&office_id)
-            .expect("office should exists");
+        // The office could be demolished meanwhile
+        let office = match self.buildings.get_mut(&office_id) {
+            None => return,
+            Some(office) => office,
+        };
         let office = office.to_office_mut();
 
         office.workers_arrived(count);
     }
 
-    pub(super) fn house(&self, id: EntityId, position: Position) -> House {
+    pub(super) fn inhabitant_left_home(&mut self, house_id: EntityId) {
+        // The house could be demolished meanwhile
+        let house = match self.buildings.get_mut(&house_id) {
+            None => return,
+            Some(house) => house,
+        };
+        let house = house.to_house_mut();
+
+        house.inhabitant_left();
+    }
+
+    pub(super) fn worker_left_job(&mut self, office_id: EntityId) {
+        // The office could be demolished meanwhile
+        let office = match self.buildings.get_mut(&office_id) {
+            None => return,
+            Some(office) => office,
+        };
+        let office = office.to_office_mut();
+
+        office.worker_left();
+    }
+
+    pub(super) fn students_arrived_at_school(&mut self, school_id: EntityId, count: u32) {
+        // The school could be demolished meanwhile
+        let school = match self.buildings.get_mut(&school_id) {
+            None => return,
+            Some(school) => school,
+        };
+        let school = school.to_school_mut();
+
+        school.students_arrived(count);
+    }
+
+    pub(super) fn student_left_school(&mut self, school_id: EntityId) {
+        // The school could be demolished meanwhile
+        let school = match self.buildings.get_mut(&school_id) {
+            None => return,
+            Some(school) => school,
+        };
+        let school = school.to_school_mut();
+
+        school.student_left();
+    }
+
+    /// Return the number of residents added
+    pub(super) fn upgrade_house_density(&mut self, house_id: EntityId, max_residents: u32) -> u32 {
+        let house = self
+            .buildings
+            .get_mut(&house_id)
+            .expect("house should exists");
+        let house = house.to_house_mut();
+
+        house.upgrade_density(max_residents)
+    }
+
+    pub(super) fn house(
+        &self,
+        id: EntityId,
+        position: Position,
+        orientation: Orientation,
+    ) -> House {
         House::new(
             id,
             position,
+            orientation,
             self.configuration.buildings.house.max_residents,
         )
     }
 
-    pub(super) fn office(&self, id: EntityId, position: Position) -> Office {
-        Office::new(id, position, self.configuration.buildings.office.max_worker)
+    pub(super) fn office(
+        &self,
+        id: EntityId,
+        position: Position,
+        orientation: Orientation,
+    ) -> Office {
+        Office::new(
+            id,
+            position,
+            orientation,
+            self.configuration.buildings.office.max_worker,
+        )
     }
 
     pub(super) fn garden(&self, id: EntityId, position: Position) -> Garden {
@@ -108,14 +368,54 @@ impl BuildingManager {
         &self,
         id: EntityId,
         position: Position,
+        orientation: Orientation,
     ) -> BiomassPowerPlant {
-        BiomassPowerPlant::new(id, position)
+        BiomassPowerPlant::new(id, position, orientation)
+    }
+
+    pub(super) fn battery(&self, id: EntityId, position: Position) -> Battery {
+        Battery::new(id, position)
+    }
+
+    pub(super) fn school(&self, id: EntityId, position: Position) -> School {
+        School::new(
+            id,
+            position,
+            self.configuration.buildings.school.max_students,
+        )
     }
 
-    #[cfg(test)]
     pub fn get_building(&self, id: &EntityId) -> Option<&Building> {
         self.buildings.get(id)
     }
+
+    pub fn get_houses(&self) -> impl Iterator<Item = HouseSnapshot> + '_ {
+        self.buildings
+            .values()
+            .filter(|b| matches!(b, Building::House(_)))
+            .map(|b| BuildingSnapshot::from(b).into_house())
+    }
+}
+
+/// What the other managers know about the placement of a building
+pub struct PlacementContext {
+    /// The building palatability is not negative
+    pub palatable: bool,
+    pub funds: u64,
+    /// A street next to the building
+    pub street_access: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlacementProblem {
+    PositionAlreadyUsed,
+    /// The construction doesn't progress
+    InsufficientPalatability,
+    InsufficientFunds,
+    /// Nobody can reach the building
+    NoStreetAccess,
+    /// Some tiles of the footprint are out of the map
+    OutOfMap,
 }
 
 #[derive(Debug, Clone)]
@@ -125,6 +425,8 @@ pub enum Building {
     Garden(Garden),
     Street(Street),
     BiomassPowerPlant(BiomassPowerPlant),
+    Battery(Battery),
+    School(School),
 }
 
 impl Building {
@@ -140,6 +442,12 @@ impl Building {
             _ => unreachable!("cannot call to_mut_office for not offices"),
         }
     }
+    fn to_school_mut(&mut self) -> &mut School {
+        match self {
+            Building::School(s) => s,
+            _ => unreachable!("cannot call to_mut_school for not schools"),
+        }
+    }
 
     fn get_step_to_reach(&self, configuration: &Arc<Configuration>) -> u8 {
         match self {
@@ -154,9 +462,55 @@ impl Building {
                     .common
                     .time_for_building
             }
+            Building::Battery(_) => configuration.buildings.battery.common.time_for_building,
+            Building::School(_) => configuration.buildings.school.common.time_for_building,
+        }
+    }
+
+    fn get_footprint(&self, configuration: &Arc<Configuration>) -> Footprint {
+        let buildings = &configuration.buildings;
+        match self {
+            Building::House(_) => buildings.house.common.footprint,
+            Building::Office(_) => buildings.office.common.footprint,
+            Building::Garden(_) => buildings.garden.common.footprint,
+            Building::Street(_) => buildings.street.common.footprint,
+            Building::BiomassPowerPlant(_) => buildings.biomass_power_plant.common.footprint,
+            Building::Battery(_) => buildings.battery.common.footprint,
+            Building::School(_) => buildings.school.common.footprint,
+        }
+    }
+
+    fn get_cost(&self, configuration: &Arc<Configuration>) -> u64 {
+        let buildings = &configuration.buildings;
+        match self {
+            Building::House(_) => buildings.house.common.cost,
+            Building::Office(_) => buildings.office.common.cost,
+            Building::Garden(_) => buildings.garden.common.cost,
+            Building::Street(_) => buildings.street.common.cost,
+            Building::BiomassPowerPlant(_) => buildings.biomass_power_plant.common.cost,
+            Building::Battery(_) => buildings.battery.common.cost,
+            Building::School(_) => buildings.school.common.cost,
         }
     }
 
+    /// Somebody lives, works or studies in it
+    pub fn is_inhabited(&self) -> bool {
+        match self {
+            Building::House(h) => *h.get_current_residents() > 0,
+            Building::Office(o) => *o.get_current_workers() > 0,
+            Building::School(s) => *s.get_current_students() > 0,
+            Building::Garden(_)
+            | Building::Street(_)
+            | Building::BiomassPowerPlant(_)
+            | Building::Battery(_) => false,
+        }
+    }
+
+    /// The streets and the gardens are fine on their own
+    fn needs_street_access(&self) -> bool {
+        !matches!(self, Building::Street(_) | Building::Garden(_))
+    }
+
     pub fn get_id(&self) -> EntityId {
         match self {
             Building::House(b) => *b.get_id(),
@@ -164,6 +518,21 @@ impl Building {
             Building::Garden(b) => *b.get_id(),
             Building::Street(b) => *b.get_id(),
             Building::BiomassPowerPlant(b) => *b.get_id(),
+            Building::Battery(b) => *b.get_id(),
+            Building::School(b) => *b.get_id(),
+        }
+    }
+
+    /// `None` for the buildings without a facing
+    pub fn get_orientation(&self) -> Option<Orientation> {
+        match self {
+            Building::House(b) => Some(*b.get_orientation()),
+            Building::Office(b) => Some(*b.get_orientation()),
+            Building::BiomassPowerPlant(b) => Some(*b.get_orientation()),
+            Building::Garden(_)
+            | Building::Street(_)
+            | Building::Battery(_)
+            | Building::School(_) => None,
         }
     }
 
@@ -174,11 +543,13 @@ impl Building {
             Building::Garden(b) => b.get_position(),
             Building::Street(b) => b.get_position(),
             Building::BiomassPowerPlant(b) => b.get_position(),
+            Building::Battery(b) => b.get_position(),
+            Building::School(b) => b.get_position(),
         }
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct BuildingUnderConstruction {
     building: Building,
     progress_status: ProgressStatus,
@@ -198,17 +569,258 @@ impl BuildingUnderConstruction {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 struct ProgressStatus {
     current_step: u8,
     step_to_reach: u8,
 }
 
 impl ProgressStatus {
-    pub fn make_progress(&mut self) {
+    /// Never over `step_to_reach`, also with many ticks in a frame
+    pub fn make_progress(&mut self, ticks: u32) {
         // Make progress never be called if the building is already ready to be built.
         // But we probably don't care about this in production release: it works fine anyway
         debug_assert!(self.step_to_reach > self.current_step);
-        self.current_step += 1;
+        let ticks = u8::try_from(ticks).unwrap_or(u8::MAX);
+        self.current_step = self
+            .current_step
+            .saturating_add(ticks)
+            .min(self.step_to_reach);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::common::{
+        configuration::CONFIGURATION,
+        position::{Footprint, Orientation, Position},
+    };
+
+    use super::{Building, BuildingManager, PlacementContext, PlacementProblem, ProgressStatus};
+
+    #[test]
+    fn test_check_placement() {
+        let mut configuration = CONFIGURATION;
+        configuration.buildings.house.common.cost = 100;
+        let mut manager = BuildingManager::new(Arc::new(configuration));
+        let position = Position { x: 1, y: 1 };
+
+        let fine = PlacementContext {
+            palatable: true,
+            funds: 100,
+            street_access: true,
+        };
+        let house = Building::House(manager.house(0, position, Orientation::North));
+        assert!(manager.check_placement(&house, &fine).is_empty());
+
+        let bad = PlacementContext {
+            palatable: false,
+            funds: 99,
+            street_access: false,
+        };
+        assert_eq!(
+            manager.check_placement(&house, &bad),
+            vec![
+                PlacementProblem::InsufficientPalatability,
+                PlacementProblem::InsufficientFunds,
+                PlacementProblem::NoStreetAccess,
+            ]
+        );
+        // The streets are free and need no other street
+        let street = Building::Street(manager.street(1, Position { x: 2, y: 1 }));
+        assert_eq!(
+            manager.check_placement(&street, &bad),
+            vec![PlacementProblem::InsufficientPalatability]
+        );
+
+        manager.start_building_creation(house.clone()).unwrap();
+        assert_eq!(
+            manager.check_placement(&house, &fine),
+            vec![PlacementProblem::PositionAlreadyUsed]
+        );
+    }
+
+    #[test]
+    fn test_footprint() {
+        let mut manager = BuildingManager::new(Arc::new(CONFIGURATION));
+        let position = |x, y| Position { x, y };
+        let fine = PlacementContext {
+            palatable: true,
+            funds: 0,
+            street_access: true,
+        };
+
+        // 2x2 tiles
+        let power_plant = Building::BiomassPowerPlant(manager.biomass_power_plant(
+            0,
+            position(1, 1),
+            Orientation::North,
+        ));
+        let under_construction = manager.start_building_creation(power_plant).unwrap();
+        assert!(manager.is_position_used(&position(2, 2)));
+        assert_eq!(
+            manager.get_building_position(&position(2, 1)),
+            Some(&position(1, 1))
+        );
+
+        let house = Building::House(manager.house(1, position(2, 2), Orientation::North));
+        assert_eq!(
+            manager.check_placement(&house, &fine),
+            vec![PlacementProblem::PositionAlreadyUsed]
+        );
+        assert!(manager.start_building_creation(house.clone()).is_err());
+        assert!(manager.fits(&house, &[position(1, 1)]));
+
+        let power_plant = Building::BiomassPowerPlant(manager.biomass_power_plant(
+            2,
+            position(31, 0),
+            Orientation::North,
+        ));
+        assert_eq!(
+            manager.check_placement(&power_plant, &fine),
+            vec![PlacementProblem::OutOfMap]
+        );
+
+        manager.cancel_building_creation(&under_construction);
+        assert!(!manager.is_position_used(&position(2, 2)));
+        assert!(manager.start_building_creation(house).is_ok());
+    }
+
+    #[test]
+    fn test_orientation() {
+        let mut configuration = CONFIGURATION;
+        configuration.buildings.office.common.footprint = Footprint { width: 3, depth: 2 };
+        let mut manager = BuildingManager::new(Arc::new(configuration));
+        let position = |x, y| Position { x, y };
+
+        // Turned sideways: 2 tiles along x, 3 along y
+        let office = Building::Office(manager.office(0, position(0, 0), Orientation::East));
+        manager.start_building_creation(office.clone()).unwrap();
+        assert!(manager.is_position_used(&position(1, 2)));
+        assert!(!manager.is_position_used(&position(2, 0)));
+        assert_eq!(
+            manager.get_entrances(&office),
+            [position(2, 0), position(2, 1), position(2, 2)].into()
+        );
+
+        let house = Building::House(manager.house(1, position(5, 5), Orientation::South));
+        assert_eq!(manager.get_entrances(&house), [position(5, 6)].into());
+        // Without a facing, every side is an entrance
+        let school = Building::School(manager.school(2, position(5, 5)));
+        assert_eq!(manager.get_entrances(&school).len(), 4);
+    }
+
+    #[test]
+    fn test_street_path() {
+        let mut manager = BuildingManager::new(Arc::new(CONFIGURATION));
+        let position = |x, y| Position { x, y };
+
+        assert_eq!(
+            manager.street_path(position(2, 1), position(2, 3)),
+            vec![position(2, 1), position(2, 2), position(2, 3)]
+        );
+        assert_eq!(
+            manager.street_path(position(1, 1), position(1, 1)),
+            vec![position(1, 1)]
+        );
+        assert_eq!(
+            manager.street_path(position(2, 2), position(0, 3)),
+            vec![
+                position(2, 2),
+                position(1, 2),
+                position(0, 2),
+                position(0, 3)
+            ]
+        );
+
+        // The occupied tiles are skipped
+        let house = Building::House(manager.house(0, position(1, 2), Orientation::North));
+        manager.start_building_creation(house).unwrap();
+        assert_eq!(
+            manager.street_path(position(2, 2), position(0, 3)),
+            vec![position(2, 2), position(0, 2), position(0, 3)]
+        );
+    }
+
+    #[test]
+    fn test_area() {
+        let mut manager = BuildingManager::new(Arc::new(CONFIGURATION));
+        let position = |x, y| Position { x, y };
+
+        assert_eq!(
+            manager.area(position(1, 1), position(1, 1)),
+            vec![position(1, 1)]
+        );
+        assert_eq!(
+            manager.area(position(2, 2), position(1, 1)),
+            vec![
+                position(1, 1),
+                position(2, 1),
+                position(1, 2),
+                position(2, 2)
+            ]
+        );
+
+        let house = Building::House(manager.house(0, position(2, 1), Orientation::North));
+        manager.start_building_creation(house).unwrap();
+        assert_eq!(manager.area(position(1, 1), position(3, 3)).len(), 8);
+    }
+
+    #[test]
+    fn test_demolish_and_restore() {
+        let mut manager = BuildingManager::new(Arc::new(CONFIGURATION));
+        let position = Position { x: 1, y: 1 };
+
+        let house = Building::House(manager.house(0, position, Orientation::North));
+        let mut under_construction = manager.start_building_creation(house).unwrap();
+        manager.make_progress(&mut under_construction, 1);
+
+        // Cancelled and started again from the same step
+        manager.cancel_building_creation(&under_construction);
+        assert!(!manager.is_position_used(&position));
+        let mut under_construction = manager
+            .restore_building_creation(under_construction)
+            .unwrap();
+        assert_eq!(under_construction.get_status().0, 1);
+        assert!(manager
+            .restore_building_creation(under_construction.clone())
+            .is_err());
+
+        while !manager.make_progress(&mut under_construction, 1) {}
+        manager.finalize_building_creation(&mut under_construction);
+        manager.inhabitants_arrived_at_home(0, 2);
+        manager.upgrade_house_density(0, 12);
+
+        let house = manager.demolish(&0).unwrap();
+        assert!(house.is_inhabited());
+        assert!(!manager.is_position_used(&position));
+        assert!(manager.demolish(&0).is_none());
+        // The residents evicted later are ignored
+        manager.inhabitant_left_home(0);
+
+        // Empty but still upgraded
+        let mut house = manager.vacated(&house);
+        assert!(!house.is_inhabited());
+        assert_eq!(house.to_house_mut().get_max_residents(), &12);
+        manager.restore_building(house).unwrap();
+        assert!(manager.get_building(&0).is_some());
+        assert!(manager.is_position_used(&position));
+    }
+
+    #[test]
+    fn test_make_progress_for_many_ticks() {
+        let mut progress_status = ProgressStatus {
+            current_step: 0,
+            step_to_reach: 10,
+        };
+
+        progress_status.make_progress(1);
+        assert_eq!(progress_status.current_step, 1);
+        progress_status.make_progress(4);
+        assert_eq!(progress_status.current_step, 5);
+        progress_status.make_progress(1000);
+        assert_eq!(progress_status.current_step, 10);
     }
 }
@@ -1,14 +1,18 @@
 mod buildings;
+mod history;
 mod manager;
 mod plugin;
 
 pub use buildings::snapshot::*;
 
 pub use plugin::events;
-pub use plugin::{BuildingManagerResource, BuildingPlugin};
+pub use plugin::{
+    check_placement, dragged_placement, placement_transform, BiomassPowerPlantComponent,
+    BuildingManagerResource, BuildingPlugin, EditMode, HouseComponent, OfficeComponent,
+    PlacementOrientationResource, PlaneComponent,
+};
 
 #[cfg(test)]
 pub use plugin::{
-    BiomassPowerPlantComponent, BuildingUnderConstructionComponent, GardenComponent,
-    HouseComponent, OfficeComponent, PlaneComponent, StreetComponent,
+    BatteryComponent, BuildingUnderConstructionComponent, GardenComponent, StreetComponent,
 };
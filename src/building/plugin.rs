@@ -1,26 +1,38 @@
+use std::collections::{BTreeMap, BTreeSet};
+
 use bevy::{
-    input::{keyboard::KeyboardInput, ButtonState},
+    ecs::system::{EntityCommands, SystemParam},
     prelude::*,
 };
-use bevy_mod_picking::{DefaultPickingPlugins, PickableBundle, PickingEvent};
+use bevy_mod_picking::{DefaultPickingPlugins, PickableBundle};
 
 use crate::{
-    building::{manager::Building, BuildingSnapshot},
+    building::{
+        history::{History, PlayerAction, TileState},
+        manager::{Building, BuildingUnderConstruction, PlacementContext, PlacementProblem},
+        BuildingSnapshot,
+    },
+    command::{events::GameCommandEvent, GameCommand},
     common::{
-        position::Position,
-        position_utils::{convert_bevy_coords_into_position, convert_position_into_bevy_coords},
+        position::{Orientation, Position},
+        position_utils::{
+            convert_bevy_coords_into_position, convert_footprint_into_bevy_transform,
+            convert_position_into_bevy_coords,
+        },
         EntityId,
     },
-    inhabitant::events::{HomeAssignedToInhabitantEvent, JobAssignedToInhabitantEvent},
+    inhabitant::events::{
+        HomeAssignedToInhabitantEvent, InhabitantBornEvent, InhabitantGraduatedEvent,
+        InhabitantLeftEvent, InhabitantRetiredEvent, JobAssignedToInhabitantEvent,
+        SchoolAssignedToInhabitantEvent,
+    },
+    navigation::NavigatorResource,
     palatability::PalatabilityManagerResource,
     resources::ConfigurationResource,
     GameTick, PbrBundles,
 };
 
-#[cfg(test)]
 pub use components::*;
-#[cfg(not(test))]
-use components::*;
 
 use events::*;
 pub use resources::*;
@@ -33,108 +45,631 @@ impl Plugin for BuildingPlugin {
     fn build(&self, app: &mut App) {
         let configuration: &ConfigurationResource = app.world.resource();
         let manager = BuildingManagerResource(BuildingManager::new((*configuration).clone()));
+        let history =
+            PlayerActionHistoryResource(History::new(configuration.game.undo_history_size));
 
         app.insert_resource(EditMode::None)
+            .init_resource::<PlacementOrientationResource>()
+            .init_resource::<PlanesResource>()
             .insert_resource(manager)
+            .insert_resource(history)
             .add_event::<BuildingCreatedEvent>()
+            .add_event::<BuildingDemolishedEvent>()
+            .add_event::<HouseDensityUpgradedEvent>()
             .add_plugins(DefaultPickingPlugins)
             .add_startup_system(setup)
-            .add_system(start_building_creation)
             .add_system(switch_edit_mode)
+            .add_system(rotate_placement)
             .add_system(make_progress_for_building_under_construction)
+            .add_system(
+                apply_building_commands
+                    .after(switch_edit_mode)
+                    .after(rotate_placement)
+                    .after(make_progress_for_building_under_construction),
+            )
             .add_system(habit_house)
-            .add_system(work_on_office);
+            .add_system(work_on_office)
+            .add_system(attend_school)
+            .add_system(leave_school.after(attend_school))
+            .add_system(welcome_newborns)
+            .add_system(retire_workers.after(work_on_office))
+            .add_system(
+                leave_buildings
+                    .after(habit_house)
+                    .after(welcome_newborns)
+                    .after(work_on_office)
+                    .after(attend_school),
+            )
+            .add_system(upgrade_house_density);
     }
 }
 
-/// Allow the user to switch edit mode base on the keyboard key
-fn switch_edit_mode(
-    mut keyboard_input_events: EventReader<KeyboardInput>,
-    mut edit_mode: ResMut<EditMode>,
-) {
-    if let Some(e) = keyboard_input_events
+/// Switch edit mode on command
+fn switch_edit_mode(mut commands: EventReader<GameCommandEvent>, mut edit_mode: ResMut<EditMode>) {
+    if let Some(e) = commands
         .iter()
-        .filter_map(|e| match (e.state, e.key_code) {
-            (ButtonState::Released, Some(KeyCode::S)) => Some(EditMode::Street),
-            (ButtonState::Released, Some(KeyCode::G)) => Some(EditMode::Garden),
-            (ButtonState::Released, Some(KeyCode::H)) => Some(EditMode::House),
-            (ButtonState::Released, Some(KeyCode::O)) => Some(EditMode::Office),
-            (ButtonState::Released, Some(KeyCode::B)) => Some(EditMode::BiomassPowerPlant),
-            (ButtonState::Released, Some(KeyCode::Escape)) => Some(EditMode::None),
+        .filter_map(|e| match e.command {
+            GameCommand::SetEditMode(edit_mode) => Some(edit_mode),
             _ => None,
         })
-        .next()
+        .next_back()
     {
         info!("Switch EditMode to {:?}", e);
         *edit_mode = e;
     }
 }
 
-/// Spawn entity with `BuildingInConstructionComponent`
-fn start_building_creation(
-    mut events: EventReader<PickingEvent>,
-    planes: Query<&PlaneComponent>,
+/// Turn the next placed buildings on command
+fn rotate_placement(
+    mut commands: EventReader<GameCommandEvent>,
+    mut orientation: ResMut<PlacementOrientationResource>,
+) {
+    if let Some(o) = commands
+        .iter()
+        .filter_map(|e| match e.command {
+            GameCommand::SetOrientation(orientation) => Some(orientation),
+            _ => None,
+        })
+        .next_back()
+    {
+        info!("Switch placement orientation to {:?}", o);
+        **orientation = o;
+    }
+}
+
+/// Place, demolish, undo and redo in the order of the commands.
+/// The dragged streets and areas are a batch, paid at once: the tiles that don't fit
+/// are left out, the others are placed only if the funds cover all of them.
+/// Every placement and demolition is recorded to be undone
+fn apply_building_commands(
+    mut game_commands: EventReader<GameCommandEvent>,
     edit_mode: Res<EditMode>,
-    mut building_manager: ResMut<BuildingManagerResource>,
-    mut commands: Commands,
-    bundles: Res<PbrBundles>,
+    orientation: Res<PlacementOrientationResource>,
+    mut palatability: ResMut<PalatabilityManagerResource>,
+    mut history: ResMut<PlayerActionHistoryResource>,
+    mut tiles: Tiles,
 ) {
-    if *edit_mode == EditMode::None {
-        return;
+    // The commands are applied at the end of the stage:
+    // the tiles changed in this frame are tracked apart
+    tiles.changed_tiles.clear();
+
+    for GameCommandEvent { command } in game_commands.iter() {
+        let batch = match *command {
+            GameCommand::PlaceBuilding(position) => (*edit_mode, vec![position]),
+            GameCommand::PlaceStreets { from, to } => (
+                EditMode::Street,
+                tiles.building_manager.street_path(from, to),
+            ),
+            GameCommand::PlaceArea { from, to } => {
+                (*edit_mode, tiles.building_manager.area(from, to))
+            }
+            GameCommand::Demolish(position) => {
+                if let Some(action) = tiles.demolish(position) {
+                    history.record(action);
+                }
+                continue;
+            }
+            GameCommand::Undo { force } => {
+                let action = match history.undo() {
+                    None => continue,
+                    Some(action) => action,
+                };
+                match tiles.revert(action, force) {
+                    Err(action) => history.push_done(action),
+                    Ok(action) => {
+                        palatability.refund(action.cost);
+                        history.push_undone(action);
+                    }
+                }
+                continue;
+            }
+            GameCommand::Redo { force } => {
+                let action = match history.redo() {
+                    None => continue,
+                    Some(action) => action,
+                };
+                if palatability.treasury() < action.cost {
+                    error!("Error on redo: insufficient funds");
+                    history.push_undone(action);
+                    continue;
+                }
+                match tiles.revert(action, force) {
+                    Err(action) => history.push_undone(action),
+                    Ok(action) => {
+                        palatability.spend(action.cost);
+                        history.push_done(action);
+                    }
+                }
+                continue;
+            }
+            GameCommand::SetEditMode(_)
+            | GameCommand::SetOrientation(_)
+            | GameCommand::SetSimulationSpeed(_)
+            | GameCommand::Step => continue,
+        };
+
+        if let Some(action) = tiles.place(batch, **orientation, &mut palatability) {
+            history.record(action);
+        }
     }
+}
 
-    let entity = events
-        .iter()
-        .filter_map(|e| match e {
-            PickingEvent::Clicked(e) => Some(e),
-            _ => None,
+/// Change the content of the tiles, keeping the managers and the entities in sync
+#[derive(SystemParam)]
+struct Tiles<'w, 's> {
+    planes: Query<
+        'w,
+        's,
+        (
+            Entity,
+            &'static PlaneComponent,
+            Option<&'static BuildingUnderConstructionComponent>,
+        ),
+    >,
+    changed_tiles: Local<'s, BTreeMap<Entity, TileState>>,
+    plane_entities: Res<'w, PlanesResource>,
+    building_manager: ResMut<'w, BuildingManagerResource>,
+    commands: Commands<'w, 's>,
+    bundles: Res<'w, PbrBundles>,
+    configuration: Res<'w, ConfigurationResource>,
+    building_created_writer: EventWriter<'w, 's, BuildingCreatedEvent>,
+    building_demolished_writer: EventWriter<'w, 's, BuildingDemolishedEvent>,
+}
+
+impl<'w, 's> Tiles<'w, 's> {
+    /// Start the buildings on the free positions, if the treasury can pay all of them
+    fn place(
+        &mut self,
+        (edit_mode, positions): (EditMode, Vec<Position>),
+        orientation: Orientation,
+        palatability: &mut PalatabilityManagerResource,
+    ) -> Option<PlayerAction> {
+        let mut buildings = vec![];
+        // The tiles taken by the previous buildings of the batch
+        let mut claimed = BTreeSet::new();
+        for position in positions {
+            let entity = match self.plane_at(position) {
+                None => {
+                    error!("No plane at {:?}", position);
+                    continue;
+                }
+                Some(entity) => entity,
+            };
+            let building = match building_of(
+                &edit_mode,
+                orientation,
+                &self.building_manager,
+                entity.to_bits(),
+                position,
+            ) {
+                None => continue,
+                Some(building) => building,
+            };
+            let footprint: Vec<Position> = self
+                .building_manager
+                .get_footprint(&building)
+                .tiles(building.get_position())
+                .collect();
+            if !self.building_manager.fits(&building, &[])
+                || footprint.iter().any(|tile| claimed.contains(tile))
+            {
+                error!("Error on creation building: Position already used");
+                continue;
+            }
+            claimed.extend(footprint);
+            buildings.push((entity, building));
+        }
+
+        let mut cost: u64 = buildings
+            .iter()
+            .map(|(_, building)| self.building_manager.get_cost(building))
+            .sum();
+        if palatability.treasury() < cost {
+            error!("Error on creation building: insufficient funds");
+            return None;
+        }
+        palatability.spend(cost);
+
+        let mut tiles = vec![];
+        for (entity, building) in buildings {
+            info!("Building {:?} at {:?}", building, building.get_position());
+
+            let position = *building.get_position();
+            let building_cost = self.building_manager.get_cost(&building);
+            let building_under_construction =
+                match self.building_manager.start_building_creation(building) {
+                    Ok(res) => res,
+                    // Checked above, but better safe than sorry: the skipped tile is not paid
+                    Err(s) => {
+                        error!("Error on creation building: {}", s);
+                        palatability.refund(building_cost);
+                        cost -= building_cost;
+                        continue;
+                    }
+                };
+            // Already reserved: not put as a restored one
+            self.spawn_under_construction(entity, building_under_construction);
+            tiles.push((position, TileState::Empty));
+        }
+
+        (!tiles.is_empty()).then_some(PlayerAction { tiles, cost })
+    }
+
+    fn demolish(&mut self, position: Position) -> Option<PlayerAction> {
+        // Any tile of the footprint demolishes the whole building
+        let position = self
+            .building_manager
+            .get_building_position(&position)
+            .copied()
+            .unwrap_or(position);
+        let entity = match self.plane_at(position) {
+            None => {
+                error!("No plane at {:?}", position);
+                return None;
+            }
+            Some(entity) => entity,
+        };
+        let tile_state = self.tile_state(entity);
+        if matches!(tile_state, TileState::Empty) {
+            error!("Error on demolition: nothing at {:?}", position);
+            return None;
+        }
+
+        info!("Demolish {:?} at {:?}", tile_state, position);
+        let tile_state = self.take(entity, tile_state);
+        Some(PlayerAction {
+            tiles: vec![(position, tile_state)],
+            cost: 0,
         })
-        .next();
+    }
 
-    let entity = match entity {
-        None => return,
-        Some(entity) => entity,
-    };
+    /// Put back what the tiles hosted before the action, returned to be reverted again.
+    /// Nothing changes if an inhabited building would be removed without forcing,
+    /// or if a building put back would overlap the ones placed meanwhile
+    fn revert(&mut self, action: PlayerAction, force: bool) -> Result<PlayerAction, PlayerAction> {
+        let replaced: Vec<_> = action.tiles.iter().map(|(position, _)| *position).collect();
+        let mut current_tiles = vec![];
+        for (position, tile_state) in &action.tiles {
+            let building = match tile_state {
+                TileState::Empty => None,
+                TileState::UnderConstruction(building_under_construction) => {
+                    Some(building_under_construction.get_building())
+                }
+                TileState::Completed(building) => Some(building),
+            };
+            if let Some(building) = building {
+                if !self.building_manager.fits(building, &replaced) {
+                    warn!("No room for {:?} at {:?}", building, position);
+                    return Err(action);
+                }
+            }
 
-    let position: &PlaneComponent = planes.get(*entity).unwrap();
-    let position = position.0;
+            let entity = match self.plane_at(*position) {
+                None => {
+                    error!("No plane at {:?}", position);
+                    return Err(action);
+                }
+                Some(entity) => entity,
+            };
+            let tile_state = self.tile_state(entity);
+            if let TileState::Completed(building) = &tile_state {
+                if building.is_inhabited() && !force {
+                    warn!("{:?} is inhabited: force to remove it", building);
+                    return Err(action);
+                }
+            }
+            current_tiles.push((entity, tile_state));
+        }
 
-    let id: EntityId = entity.to_bits();
+        let mut tiles = vec![];
+        for ((position, tile_state), (entity, current)) in
+            action.tiles.into_iter().zip(current_tiles)
+        {
+            let current = self.take(entity, current);
+            self.put(entity, tile_state);
+            tiles.push((position, current));
+        }
 
-    // TODO: move away from here!
-    let building: Building = match *edit_mode {
-        EditMode::House => Building::House(building_manager.house(id, position)),
-        EditMode::Garden => Building::Garden(building_manager.garden(id, position)),
-        EditMode::Street => Building::Street(building_manager.street(id, position)),
-        EditMode::Office => Building::Office(building_manager.office(id, position)),
-        EditMode::BiomassPowerPlant => {
-            Building::BiomassPowerPlant(building_manager.biomass_power_plant(id, position))
+        Ok(PlayerAction {
+            tiles,
+            cost: action.cost,
+        })
+    }
+
+    fn plane_at(&self, position: Position) -> Option<Entity> {
+        self.plane_entities.get(&position).copied()
+    }
+
+    fn tile_state(&self, entity: Entity) -> TileState {
+        if let Some(tile_state) = self.changed_tiles.get(&entity) {
+            return tile_state.clone();
         }
-        EditMode::None => unreachable!("EditMode::None is handled before"),
-    };
+        // Completed in this frame too
+        if let Some(building) = self.building_manager.get_building(&entity.to_bits()) {
+            return TileState::Completed(building.clone());
+        }
+        match self.planes.get(entity) {
+            Ok((_, _, Some(under_construction))) => {
+                TileState::UnderConstruction(under_construction.building_under_construction.clone())
+            }
+            _ => TileState::Empty,
+        }
+    }
 
-    info!("Building {:?} at {:?}", building, position);
+    /// Empty the tile, returning what it hosted
+    fn take(&mut self, entity: Entity, tile_state: TileState) -> TileState {
+        self.changed_tiles.insert(entity, TileState::Empty);
 
-    let building_under_construction = match building_manager.start_building_creation(building) {
-        Ok(res) => res,
-        Err(s) => {
-            error!("Error on creation building: {}", s);
-            return;
+        match &tile_state {
+            TileState::Empty => return tile_state,
+            TileState::UnderConstruction(building_under_construction) => {
+                self.building_manager
+                    .cancel_building_creation(building_under_construction);
+            }
+            TileState::Completed(building) => {
+                let building = match self.building_manager.demolish(&building.get_id()) {
+                    None => return TileState::Empty,
+                    Some(building) => building,
+                };
+                self.building_demolished_writer
+                    .send(BuildingDemolishedEvent {
+                        building_snapshot: BuildingSnapshot::from(&building),
+                    });
+            }
         }
-    };
 
-    commands
-        .entity(*entity)
+        let mut command = self.commands.entity(entity);
+        command.despawn_descendants();
+        command
+            .remove::<BuildingUnderConstructionComponent>()
+            .remove::<HouseComponent>()
+            .remove::<GardenComponent>()
+            .remove::<StreetComponent>()
+            .remove::<OfficeComponent>()
+            .remove::<BiomassPowerPlantComponent>()
+            .remove::<BatteryComponent>()
+            .remove::<SchoolComponent>();
+
+        match tile_state {
+            TileState::Completed(building) => {
+                TileState::Completed(self.building_manager.vacated(&building))
+            }
+            tile_state => tile_state,
+        }
+    }
+
+    /// Fill the empty tile
+    fn put(&mut self, entity: Entity, tile_state: TileState) {
+        let tile_state = match tile_state {
+            TileState::Empty => TileState::Empty,
+            TileState::UnderConstruction(building_under_construction) => {
+                let building_under_construction = match self
+                    .building_manager
+                    .restore_building_creation(building_under_construction)
+                {
+                    Ok(res) => res,
+                    Err(s) => {
+                        error!("Error on restoring building: {}", s);
+                        return;
+                    }
+                };
+                self.spawn_under_construction(entity, building_under_construction);
+                return;
+            }
+            TileState::Completed(building) => {
+                if let Err(s) = self.building_manager.restore_building(building.clone()) {
+                    error!("Error on restoring building: {}", s);
+                    return;
+                }
+                spawn_building(
+                    &mut self.commands.entity(entity),
+                    &self.bundles,
+                    mesh_transform(&self.configuration, &self.building_manager, &building),
+                    &building,
+                );
+                self.building_created_writer.send(BuildingCreatedEvent {
+                    building_snapshot: BuildingSnapshot::from(&building),
+                    entrances: self.building_manager.get_entrances(&building),
+                });
+                TileState::Completed(building)
+            }
+        };
+        self.changed_tiles.insert(entity, tile_state);
+    }
+
+    /// The building under construction appears on the tile, its footprint already reserved
+    fn spawn_under_construction(
+        &mut self,
+        entity: Entity,
+        building_under_construction: BuildingUnderConstruction,
+    ) {
+        spawn_building_under_construction(
+            &mut self.commands.entity(entity),
+            &self.bundles,
+            mesh_transform(
+                &self.configuration,
+                &self.building_manager,
+                building_under_construction.get_building(),
+            ),
+            building_under_construction.clone(),
+        );
+        self.changed_tiles.insert(
+            entity,
+            TileState::UnderConstruction(building_under_construction),
+        );
+    }
+}
+
+/// Relative to the building position: the mesh covers the footprint, facing the orientation
+fn mesh_transform(
+    configuration: &ConfigurationResource,
+    building_manager: &BuildingManager,
+    building: &Building,
+) -> Transform {
+    convert_footprint_into_bevy_transform(
+        configuration,
+        &building_manager.get_footprint(building),
+        building.get_orientation().unwrap_or_default(),
+    )
+}
+
+/// The meshes are placed by `footprint_transform` to cover the whole footprint
+fn spawn_building_under_construction(
+    command: &mut EntityCommands,
+    bundles: &PbrBundles,
+    footprint_transform: Transform,
+    building_under_construction: BuildingUnderConstruction,
+) {
+    command
         .insert(BuildingUnderConstructionComponent {
             building_under_construction,
         })
         .with_children(|parent| {
             let mut sprite = bundles.in_progress();
-            sprite.transform.translation = Vec3::new(0., 0.0001, 0.);
+            sprite.transform = footprint_transform;
+            sprite.transform.translation.y += 0.0001;
             parent.spawn(sprite);
         });
 }
 
+/// Replace the construction site with the completed building
+fn spawn_building(
+    command: &mut EntityCommands,
+    bundles: &PbrBundles,
+    footprint_transform: Transform,
+    building: &Building,
+) {
+    let mut bundle = match building {
+        Building::House(_) => bundles.house(),
+        Building::Garden(_) => bundles.garden(),
+        Building::Street(_) => bundles.street(),
+        Building::Office(_) => bundles.office(),
+        Building::BiomassPowerPlant(_) => bundles.biomass_power_plant(),
+        Building::Battery(_) => bundles.battery(),
+        Building::School(_) => bundles.school(),
+    };
+    bundle.transform = footprint_transform;
+
+    command.despawn_descendants();
+    command
+        .remove::<BuildingUnderConstructionComponent>()
+        .with_children(|parent| {
+            parent.spawn(bundle);
+        });
+
+    // TODO: rework this part
+    // This part need to be reworked in order to let it scalable
+    // on the BuildingType enumeration growing.
+    let id = building.get_id();
+    match building {
+        Building::House(_) => command.insert(HouseComponent(id)),
+        Building::Garden(_) => command.insert(GardenComponent(id)),
+        Building::Street(_) => command.insert(StreetComponent(id)),
+        Building::Office(_) => command.insert(OfficeComponent(id)),
+        Building::BiomassPowerPlant(_) => command.insert(BiomassPowerPlantComponent(id)),
+        Building::Battery(_) => command.insert(BatteryComponent(id)),
+        Building::School(_) => command.insert(SchoolComponent(id)),
+    };
+}
+
+/// The building the edit mode places, `None` when it places nothing.
+/// The orientation is ignored by the buildings without a facing
+fn building_of(
+    edit_mode: &EditMode,
+    orientation: Orientation,
+    building_manager: &BuildingManager,
+    id: EntityId,
+    position: Position,
+) -> Option<Building> {
+    let building = match edit_mode {
+        EditMode::House => Building::House(building_manager.house(id, position, orientation)),
+        EditMode::Garden => Building::Garden(building_manager.garden(id, position)),
+        EditMode::Street => Building::Street(building_manager.street(id, position)),
+        EditMode::Office => Building::Office(building_manager.office(id, position, orientation)),
+        EditMode::BiomassPowerPlant => Building::BiomassPowerPlant(
+            building_manager.biomass_power_plant(id, position, orientation),
+        ),
+        EditMode::Battery => Building::Battery(building_manager.battery(id, position)),
+        EditMode::School => Building::School(building_manager.school(id, position)),
+        EditMode::None | EditMode::Demolish => return None,
+    };
+    Some(building)
+}
+
+/// How many buildings a drag from `from` to `to` places and their total cost,
+/// `None` if the edit mode is not dragged
+pub fn dragged_placement(
+    edit_mode: &EditMode,
+    building_manager: &BuildingManager,
+    from: Position,
+    to: Position,
+) -> Option<(usize, u64)> {
+    let positions = if *edit_mode == EditMode::Street {
+        building_manager.street_path(from, to)
+    } else if edit_mode.fills_area() {
+        building_manager.area(from, to)
+    } else {
+        return None;
+    };
+
+    let cost = positions
+        .iter()
+        // The streets and the gardens have no facing
+        .filter_map(|position| {
+            building_of(
+                edit_mode,
+                Orientation::default(),
+                building_manager,
+                0,
+                *position,
+            )
+        })
+        .map(|building| building_manager.get_cost(&building))
+        .sum();
+    Some((positions.len(), cost))
+}
+
+/// Relative to the hovered tile: the mesh of the building the edit mode places,
+/// `None` when it places nothing
+pub fn placement_transform(
+    edit_mode: &EditMode,
+    orientation: Orientation,
+    building_manager: &BuildingManager,
+    configuration: &ConfigurationResource,
+) -> Option<Transform> {
+    let building = building_of(
+        edit_mode,
+        orientation,
+        building_manager,
+        0,
+        Position { x: 0, y: 0 },
+    )?;
+    Some(mesh_transform(configuration, building_manager, &building))
+}
+
+/// What would prevent the building of the edit mode at the position
+/// from being built or from working, `None` when there's no edit mode
+pub fn check_placement(
+    edit_mode: &EditMode,
+    orientation: Orientation,
+    building_manager: &BuildingManager,
+    palatability: &PalatabilityManagerResource,
+    navigator: &NavigatorResource,
+    position: Position,
+) -> Option<Vec<PlacementProblem>> {
+    // Not placed yet: no entity
+    let building = building_of(edit_mode, orientation, building_manager, 0, position)?;
+
+    let context = PlacementContext {
+        palatable: palatability
+            .get_palatability(&BuildingSnapshot::from(&building))
+            .is_positive(),
+        funds: palatability.treasury(),
+        street_access: navigator.reaches_street(&building_manager.get_entrances(&building)),
+    };
+    Some(building_manager.check_placement(&building, &context))
+}
+
 // TODO: split this function: too many arguments
 /// Make BuildingInConstruction progress. Then:
 /// - if the building is not yet finished, stop
@@ -142,13 +677,13 @@ fn start_building_creation(
 /// NB: the progress is made if and only if there's sufficient palatability
 #[allow(clippy::too_many_arguments)]
 fn make_progress_for_building_under_construction(
-    game_tick: EventReader<GameTick>,
+    mut game_tick: EventReader<GameTick>,
     mut buildings_in_progress: Query<(Entity, &mut BuildingUnderConstructionComponent)>,
     mut building_manager: ResMut<BuildingManagerResource>,
     palatability: Res<PalatabilityManagerResource>,
     mut commands: Commands,
     bundles: Res<PbrBundles>,
-    _configuration: Res<ConfigurationResource>,
+    configuration: Res<ConfigurationResource>,
     mut building_created_writer: EventWriter<BuildingCreatedEvent>,
 ) {
     // TODO: split the following logic among frames
@@ -158,7 +693,8 @@ fn make_progress_for_building_under_construction(
     // process little by little them.
     // we can create a dedicated entity to store the entities ids when the events count is not 0
     // and process them little by little in the following frames.
-    if game_tick.is_empty() {
+    let ticks = GameTick::count(&mut game_tick);
+    if ticks == 0 {
         return;
     }
 
@@ -170,7 +706,6 @@ fn make_progress_for_building_under_construction(
 
         let building_under_construction = &mut building.building_under_construction;
         let building = building_under_construction.get_building();
-        let id = building.get_id();
         let position = building.get_position();
 
         // TODO: is this line impact too much the performance?
@@ -185,7 +720,7 @@ fn make_progress_for_building_under_construction(
             continue;
         }
 
-        let is_completed = building_manager.make_progress(building_under_construction);
+        let is_completed = building_manager.make_progress(building_under_construction, ticks);
 
         if !is_completed {
             continue;
@@ -199,35 +734,17 @@ fn make_progress_for_building_under_construction(
         );
 
         let building = building_under_construction.get_building();
+        spawn_building(
+            &mut commands.entity(entity),
+            &bundles,
+            mesh_transform(&configuration, &building_manager, building),
+            building,
+        );
 
-        let bundle = match building {
-            Building::House(_) => bundles.house(),
-            Building::Garden(_) => bundles.garden(),
-            Building::Street(_) => bundles.street(),
-            Building::Office(_) => bundles.office(),
-            Building::BiomassPowerPlant(_) => bundles.biomass_power_plant(),
-        };
-
-        let mut command = commands.entity(entity);
-        command.despawn_descendants();
-        command
-            .remove::<BuildingUnderConstructionComponent>()
-            .with_children(|parent| {
-                parent.spawn(bundle);
-            });
-
-        // TODO: rework this part
-        // This part need to be reworked in order to let it scalable
-        // on the BuildingType enumeration growing.
-        match building {
-            Building::House(_) => command.insert(HouseComponent(id)),
-            Building::Garden(_) => command.insert(GardenComponent(id)),
-            Building::Street(_) => command.insert(StreetComponent(id)),
-            Building::Office(_) => command.insert(OfficeComponent(id)),
-            Building::BiomassPowerPlant(_) => command.insert(BiomassPowerPlantComponent(id)),
-        };
-
-        building_created_writer.send(BuildingCreatedEvent { building_snapshot });
+        building_created_writer.send(BuildingCreatedEvent {
+            building_snapshot,
+            entrances: building_manager.get_entrances(building),
+        });
     }
 }
 
@@ -283,10 +800,118 @@ fn work_on_office(
     }
 }
 
+/// frees the spots of the inhabitants that left the city
+fn leave_buildings(
+    mut building_manager: ResMut<BuildingManagerResource>,
+    mut inhabitant_left_reader: EventReader<InhabitantLeftEvent>,
+) {
+    for left in inhabitant_left_reader.iter() {
+        if let Some(house_entity_id) = left.house_entity_id {
+            building_manager.inhabitant_left_home(house_entity_id);
+        }
+        if let Some(work_place_entity_id) = left.work_place_entity_id {
+            building_manager.worker_left_job(work_place_entity_id);
+        }
+        if let Some(school_entity_id) = left.school_entity_id {
+            building_manager.student_left_school(school_entity_id);
+        }
+    }
+}
+
+/// the newborns live with their parents
+fn welcome_newborns(
+    mut building_manager: ResMut<BuildingManagerResource>,
+    mut inhabitant_born_reader: EventReader<InhabitantBornEvent>,
+) {
+    for born in inhabitant_born_reader.iter() {
+        building_manager.inhabitants_arrived_at_home(born.house_entity_id, 1);
+    }
+}
+
+/// frees the spots of the retirees
+fn retire_workers(
+    mut building_manager: ResMut<BuildingManagerResource>,
+    mut inhabitant_retired_reader: EventReader<InhabitantRetiredEvent>,
+) {
+    for retired in inhabitant_retired_reader.iter() {
+        debug!(
+            "inhabitant {} retired from {}",
+            retired.inhabitant_entity_id, retired.work_place_entity_id
+        );
+        building_manager.worker_left_job(retired.work_place_entity_id);
+    }
+}
+
+/// marks the school as attended
+fn attend_school(
+    mut building_manager: ResMut<BuildingManagerResource>,
+    mut school_assigned_reader: EventReader<SchoolAssignedToInhabitantEvent>,
+) {
+    for assigned in school_assigned_reader.iter() {
+        building_manager.students_arrived_at_school(
+            assigned.building_entity_id,
+            assigned
+                .students_entity_ids
+                .len()
+                .try_into()
+                .expect("unable to convert usize into u32"),
+        );
+    }
+}
+
+/// frees the spots of the graduates
+fn leave_school(
+    mut building_manager: ResMut<BuildingManagerResource>,
+    mut inhabitant_graduated_reader: EventReader<InhabitantGraduatedEvent>,
+) {
+    for graduated in inhabitant_graduated_reader.iter() {
+        debug!(
+            "inhabitant {} left school {} with {:?}",
+            graduated.inhabitant_entity_id, graduated.school_entity_id, graduated.education_level
+        );
+        building_manager.student_left_school(graduated.school_entity_id);
+    }
+}
+
+/// Houses on valuable land host more residents
+fn upgrade_house_density(
+    mut game_tick: EventReader<GameTick>,
+    palatability: Res<PalatabilityManagerResource>,
+    mut building_manager: ResMut<BuildingManagerResource>,
+    mut house_density_upgraded_writer: EventWriter<HouseDensityUpgradedEvent>,
+) {
+    if GameTick::count(&mut game_tick) == 0 {
+        return;
+    }
+
+    let upgrades: Vec<_> = building_manager
+        .get_houses()
+        .filter_map(|house| {
+            let max_residents = palatability.get_house_density(&house.position)?;
+            (max_residents > house.max_residents).then_some((house, max_residents))
+        })
+        .collect();
+
+    for (house, max_residents) in upgrades {
+        let added_residents = building_manager.upgrade_house_density(house.id, max_residents);
+        info!(
+            "house at {:?} upgraded to {} residents",
+            house.position, max_residents
+        );
+
+        house_density_upgraded_writer.send(HouseDensityUpgradedEvent {
+            house_id: house.id,
+            house_position: house.position,
+            added_residents,
+        });
+    }
+}
+
 fn setup(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
+    mut plane_entities: ResMut<PlanesResource>,
     configuration: Res<ConfigurationResource>,
 ) {
     let grid_positions: Vec<_> = (0..configuration.game.width_table)
@@ -298,7 +923,7 @@ fn setup(
         let position = convert_bevy_coords_into_position(&configuration, &translation);
 
         let transform = Transform::from_translation(translation);
-        commands
+        let entity = commands
             .spawn(PbrBundle {
                 mesh: meshes.add(Mesh::from(shape::Plane {
                     size: configuration.cube_size,
@@ -308,18 +933,29 @@ fn setup(
                 ..default()
             })
             .insert(PlaneComponent(position))
-            .insert(PickableBundle::default());
+            .insert(PickableBundle::default())
+            .id();
+        plane_entities.insert(position, entity);
     }
 }
 
 mod resources {
-    use std::ops::{Deref, DerefMut};
+    use std::{
+        collections::BTreeMap,
+        ops::{Deref, DerefMut},
+    };
 
-    use bevy::prelude::Resource;
+    use bevy::prelude::{Entity, Resource};
 
-    use crate::building::manager::BuildingManager;
+    use crate::{
+        building::{
+            history::{History, PlayerAction},
+            manager::BuildingManager,
+        },
+        common::position::{Orientation, Position},
+    };
 
-    #[derive(Debug, Hash, PartialEq, Eq, Resource)]
+    #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, Resource)]
     pub enum EditMode {
         None,
         House,
@@ -327,6 +963,16 @@ mod resources {
         Street,
         Office,
         BiomassPowerPlant,
+        Battery,
+        School,
+        Demolish,
+    }
+
+    impl EditMode {
+        /// The tile-filling buildings are placed dragging a rectangle
+        pub fn fills_area(&self) -> bool {
+            matches!(self, EditMode::Garden)
+        }
     }
 
     #[derive(Resource)]
@@ -345,15 +991,86 @@ mod resources {
             &mut self.0
         }
     }
+    /// Where the next placed buildings face
+    #[derive(Resource, Default)]
+    pub struct PlacementOrientationResource(pub Orientation);
+
+    impl Deref for PlacementOrientationResource {
+        type Target = Orientation;
+
+        fn deref(&self) -> &Self::Target {
+            &self.0
+        }
+    }
+
+    impl DerefMut for PlacementOrientationResource {
+        fn deref_mut(&mut self) -> &mut Self::Target {
+            &mut self.0
+        }
+    }
+
+    /// The plane of every tile: the tiles never change
+    #[derive(Resource, Default)]
+    pub struct PlanesResource(pub BTreeMap<Position, Entity>);
+
+    impl Deref for PlanesResource {
+        type Target = BTreeMap<Position, Entity>;
+
+        fn deref(&self) -> &Self::Target {
+            &self.0
+        }
+    }
+
+    impl DerefMut for PlanesResource {
+        fn deref_mut(&mut self) -> &mut Self::Target {
+            &mut self.0
+        }
+    }
+
+    /// The placements and the demolitions of the player
+    #[derive(Resource)]
+    pub struct PlayerActionHistoryResource(pub History<PlayerAction>);
+
+    impl Deref for PlayerActionHistoryResource {
+        type Target = History<PlayerAction>;
+
+        fn deref(&self) -> &Self::Target {
+            &self.0
+        }
+    }
+
+    impl DerefMut for PlayerActionHistoryResource {
+        fn deref_mut(&mut self) -> &mut Self::Target {
+            &mut self.0
+        }
+    }
 }
 
 pub mod events {
-    use crate::building::BuildingSnapshot;
+    use std::collections::BTreeSet;
+
+    use crate::{
+        building::BuildingSnapshot,
+        common::{position::Position, EntityId},
+    };
     use bevy::prelude::Component;
 
     #[derive(Component)]
     pub struct BuildingCreatedEvent {
         pub building_snapshot: BuildingSnapshot,
+        /// Where the streets give access to the building
+        pub entrances: BTreeSet<Position>,
+    }
+
+    /// The completed building is removed: the snapshot is taken before its inhabitants leave
+    pub struct BuildingDemolishedEvent {
+        pub building_snapshot: BuildingSnapshot,
+    }
+
+    pub struct HouseDensityUpgradedEvent {
+        pub house_id: EntityId,
+        pub house_position: Position,
+        pub added_residents: u32,
     }
 }
 
@@ -379,6 +1096,10 @@ mod components {
     pub struct OfficeComponent(pub EntityId);
     #[derive(Component)]
     pub struct BiomassPowerPlantComponent(pub EntityId);
+    #[derive(Component)]
+    pub struct BatteryComponent(pub EntityId);
+    #[derive(Component)]
+    pub struct SchoolComponent(pub EntityId);
 
     #[derive(Component)]
     pub struct BuildingUnderConstructionComponent {
@@ -0,0 +1,155 @@
+use std::sync::Arc;
+
+use chrono::{Duration, NaiveDate, NaiveDateTime, Timelike};
+
+use crate::common::configuration::{ClockConfiguration, Configuration};
+
+/// The in-game date and time: every tick is a fraction of an hour
+pub struct GameClock {
+    configuration: Arc<Configuration>,
+    start: NaiveDateTime,
+    ticks: u64,
+}
+
+impl GameClock {
+    pub fn new(configuration: Arc<Configuration>) -> Self {
+        let clock = &configuration.clock;
+        let start = NaiveDate::from_ymd_opt(clock.start_year, clock.start_month, clock.start_day)
+            .and_then(|date| date.and_hms_opt(clock.start_hour, 0, 0))
+            .expect("invalid start date");
+
+        Self {
+            configuration,
+            start,
+            ticks: 0,
+        }
+    }
+
+    /// A tick passes: return the new date when a day starts
+    pub fn tick(&mut self) -> Option<NaiveDate> {
+        let yesterday = self.now().date();
+        self.ticks += 1;
+        let today = self.now().date();
+
+        (today != yesterday).then_some(today)
+    }
+
+    pub fn now(&self) -> NaiveDateTime {
+        let ticks_per_hour = u64::from(self.clock().ticks_per_hour.max(1));
+        let minutes = self.ticks * 60 / ticks_per_hour;
+        self.start + Duration::minutes(minutes as i64)
+    }
+
+    /// The hours since midnight, with the fraction
+    pub fn time_of_day(&self) -> f32 {
+        let now = self.now();
+        now.hour() as f32 + now.minute() as f32 / 60.
+    }
+
+    /// From 0 at sunrise to 1 at sunset, `None` at night
+    pub fn day_progress(&self) -> Option<f32> {
+        let clock = self.clock();
+        let sunrise = clock.sunrise_hour as f32;
+        let sunset = clock.sunset_hour as f32;
+        let time_of_day = self.time_of_day();
+
+        (sunrise..sunset)
+            .contains(&time_of_day)
+            .then(|| (time_of_day - sunrise) / (sunset - sunrise))
+    }
+
+    /// From 0 at night to 1 at midday
+    pub fn daylight(&self) -> f32 {
+        self.day_progress()
+            .map_or(0., |progress| (progress * std::f32::consts::PI).sin())
+    }
+
+    pub fn illuminance(&self) -> f32 {
+        let clock = self.clock();
+        clock.night_illuminance
+            + (clock.day_illuminance - clock.night_illuminance) * self.daylight()
+    }
+
+    /// The power demand in percentage of the nominal one: lower at night
+    pub fn power_demand_percentage(&self) -> u32 {
+        let clock = self.clock();
+        if self.day_progress().is_some() {
+            clock.day_power_demand_percentage
+        } else {
+            clock.night_power_demand_percentage
+        }
+    }
+
+    /// The commuters on the streets in percentage: everybody at rush hours, few at night
+    pub fn traffic_percentage(&self) -> u32 {
+        let clock = self.clock();
+        if clock.rush_hours.contains(&self.now().hour()) {
+            100
+        } else if self.day_progress().is_some() {
+            clock.day_traffic_percentage
+        } else {
+            clock.night_traffic_percentage
+        }
+    }
+
+    fn clock(&self) -> &ClockConfiguration {
+        &self.configuration.clock
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Datelike;
+
+    use crate::common::configuration::CONFIGURATION;
+
+    use super::*;
+
+    fn clock_at(start_hour: u32) -> GameClock {
+        let mut configuration = CONFIGURATION;
+        configuration.clock.start_hour = start_hour;
+        GameClock::new(Arc::new(configuration))
+    }
+
+    #[test]
+    fn test_days_pass() {
+        let mut clock = clock_at(0);
+        let ticks_per_day = 24 * CONFIGURATION.clock.ticks_per_hour;
+
+        for _ in 1..ticks_per_day {
+            assert_eq!(clock.tick(), None);
+        }
+        let day = clock.tick().unwrap();
+        assert_eq!(day.day(), CONFIGURATION.clock.start_day + 1);
+        assert_eq!(clock.time_of_day(), 0.);
+    }
+
+    #[test]
+    fn test_day_and_night() {
+        let configuration = &CONFIGURATION.clock;
+
+        let night = clock_at(configuration.sunrise_hour - 1);
+        assert_eq!(night.day_progress(), None);
+        assert_eq!(night.daylight(), 0.);
+        assert_eq!(night.illuminance(), configuration.night_illuminance);
+        assert_eq!(
+            night.power_demand_percentage(),
+            configuration.night_power_demand_percentage
+        );
+        assert_eq!(
+            night.traffic_percentage(),
+            configuration.night_traffic_percentage
+        );
+
+        let midday = clock_at((configuration.sunrise_hour + configuration.sunset_hour) / 2);
+        assert_eq!(midday.day_progress(), Some(0.5));
+        assert!((midday.illuminance() - configuration.day_illuminance).abs() < 1.);
+        assert_eq!(
+            midday.power_demand_percentage(),
+            configuration.day_power_demand_percentage
+        );
+
+        let rush_hour = clock_at(configuration.rush_hours[0]);
+        assert_eq!(rush_hour.traffic_percentage(), 100);
+    }
+}
@@ -0,0 +1,5 @@
+mod manager;
+mod plugin;
+
+pub use plugin::events;
+pub use plugin::{ClockPlugin, GameClockResource};
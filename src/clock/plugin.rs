@@ -0,0 +1,110 @@
+use bevy::prelude::*;
+use chrono::Datelike;
+
+use crate::{resources::ConfigurationResource, GameTick};
+
+use super::manager::GameClock;
+
+pub use self::events::*;
+pub use self::resources::*;
+
+pub struct ClockPlugin;
+
+impl Plugin for ClockPlugin {
+    fn build(&self, app: &mut App) {
+        let configuration: &ConfigurationResource = app.world.resource();
+        let clock = GameClock::new((*configuration).clone());
+
+        app.insert_resource(GameClockResource(clock))
+            .add_event::<NewDayEvent>()
+            .add_event::<NewMonthEvent>()
+            .add_system(advance_clock)
+            .add_system(move_sun.after(advance_clock));
+    }
+}
+
+/// Every tick is a fraction of an hour
+fn advance_clock(
+    mut game_tick: EventReader<GameTick>,
+    mut clock: ResMut<GameClockResource>,
+    mut new_day_writer: EventWriter<NewDayEvent>,
+    mut new_month_writer: EventWriter<NewMonthEvent>,
+) {
+    for _ in 0..GameTick::count(&mut game_tick) {
+        let date = match clock.tick() {
+            None => continue,
+            Some(date) => date,
+        };
+        info!("new day: {date}");
+
+        new_day_writer.send(NewDayEvent { date });
+        if date.day() == 1 {
+            new_month_writer.send(NewMonthEvent {
+                year: date.year(),
+                month: date.month(),
+            });
+        }
+    }
+}
+
+/// The sun rises, crosses the sky and sets: dim light at night
+fn move_sun(
+    clock: Res<GameClockResource>,
+    mut lights: Query<(&mut DirectionalLight, &mut Transform)>,
+) {
+    if !clock.is_changed() {
+        return;
+    }
+
+    // From east to west, at night the light comes from above
+    let (azimuth, elevation) = match clock.day_progress() {
+        None => (0., std::f32::consts::FRAC_PI_2),
+        Some(progress) => (
+            std::f32::consts::PI * progress,
+            std::f32::consts::FRAC_PI_2 * clock.daylight().max(0.1),
+        ),
+    };
+
+    for (mut light, mut transform) in lights.iter_mut() {
+        light.illuminance = clock.illuminance();
+        transform.rotation = Quat::from_rotation_y(azimuth) * Quat::from_rotation_x(-elevation);
+    }
+}
+
+mod resources {
+    use std::ops::{Deref, DerefMut};
+
+    use bevy::prelude::Resource;
+
+    use crate::clock::manager::GameClock;
+
+    #[derive(Resource)]
+    pub struct GameClockResource(pub GameClock);
+
+    impl Deref for GameClockResource {
+        type Target = GameClock;
+
+        fn deref(&self) -> &Self::Target {
+            &self.0
+        }
+    }
+
+    impl DerefMut for GameClockResource {
+        fn deref_mut(&mut self) -> &mut Self::Target {
+            &mut self.0
+        }
+    }
+}
+
+pub mod events {
+    use chrono::NaiveDate;
+
+    pub struct NewDayEvent {
+        pub date: NaiveDate,
+    }
+
+    pub struct NewMonthEvent {
+        pub year: i32,
+        pub month: u32,
+    }
+}
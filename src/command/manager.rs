@@ -0,0 +1,300 @@
+use std::{collections::VecDeque, fmt::Display, io::Write, str::FromStr};
+
+use crate::{
+    building::EditMode,
+    common::position::{Orientation, Position},
+    SimulationSpeed,
+};
+
+/// A player action: the only way the player changes the simulation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameCommand {
+    SetEditMode(EditMode),
+    /// Turn the next placed buildings
+    SetOrientation(Orientation),
+    /// Place a building of the current edit mode
+    PlaceBuilding(Position),
+    /// Place the streets on the free tiles between the two positions
+    PlaceStreets {
+        from: Position,
+        to: Position,
+    },
+    /// Place a building of the current edit mode on every free tile of the rectangle
+    PlaceArea {
+        from: Position,
+        to: Position,
+    },
+    /// Remove the building, completed or not, evicting its inhabitants
+    Demolish(Position),
+    /// Revert the last placement or demolition.
+    /// An inhabited building is removed only if forced
+    Undo {
+        force: bool,
+    },
+    /// Apply again the last undone action
+    Redo {
+        force: bool,
+    },
+    SetSimulationSpeed(SimulationSpeed),
+    /// A single tick while paused
+    Step,
+}
+
+impl Display for GameCommand {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GameCommand::SetEditMode(edit_mode) => write!(f, "edit-mode {edit_mode:?}"),
+            GameCommand::SetOrientation(orientation) => write!(f, "orientation {orientation:?}"),
+            GameCommand::PlaceBuilding(position) => {
+                write!(f, "place {} {}", position.x, position.y)
+            }
+            GameCommand::PlaceStreets { from, to } => {
+                write!(f, "place-streets {} {} {} {}", from.x, from.y, to.x, to.y)
+            }
+            GameCommand::PlaceArea { from, to } => {
+                write!(f, "place-area {} {} {} {}", from.x, from.y, to.x, to.y)
+            }
+            GameCommand::Demolish(position) => {
+                write!(f, "demolish {} {}", position.x, position.y)
+            }
+            GameCommand::Undo { force: false } => write!(f, "undo"),
+            GameCommand::Undo { force: true } => write!(f, "undo force"),
+            GameCommand::Redo { force: false } => write!(f, "redo"),
+            GameCommand::Redo { force: true } => write!(f, "redo force"),
+            GameCommand::SetSimulationSpeed(speed) => write!(f, "speed {speed:?}"),
+            GameCommand::Step => write!(f, "step"),
+        }
+    }
+}
+
+impl FromStr for GameCommand {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let tokens: Vec<_> = s.split_whitespace().collect();
+        let command = match tokens.as_slice() {
+            ["edit-mode", edit_mode] => GameCommand::SetEditMode(match *edit_mode {
+                "None" => EditMode::None,
+                "House" => EditMode::House,
+                "Garden" => EditMode::Garden,
+                "Street" => EditMode::Street,
+                "Office" => EditMode::Office,
+                "BiomassPowerPlant" => EditMode::BiomassPowerPlant,
+                "Battery" => EditMode::Battery,
+                "School" => EditMode::School,
+                "Demolish" => EditMode::Demolish,
+                _ => return Err(format!("unknown edit mode: {edit_mode}")),
+            }),
+            ["orientation", orientation] => GameCommand::SetOrientation(match *orientation {
+                "North" => Orientation::North,
+                "East" => Orientation::East,
+                "South" => Orientation::South,
+                "West" => Orientation::West,
+                _ => return Err(format!("unknown orientation: {orientation}")),
+            }),
+            ["place", x, y] => GameCommand::PlaceBuilding(parse_position(x, y)?),
+            ["place-streets", from_x, from_y, to_x, to_y] => GameCommand::PlaceStreets {
+                from: parse_position(from_x, from_y)?,
+                to: parse_position(to_x, to_y)?,
+            },
+            ["place-area", from_x, from_y, to_x, to_y] => GameCommand::PlaceArea {
+                from: parse_position(from_x, from_y)?,
+                to: parse_position(to_x, to_y)?,
+            },
+            ["demolish", x, y] => GameCommand::Demolish(parse_position(x, y)?),
+            ["undo"] => GameCommand::Undo { force: false },
+            ["undo", "force"] => GameCommand::Undo { force: true },
+            ["redo"] => GameCommand::Redo { force: false },
+            ["redo", "force"] => GameCommand::Redo { force: true },
+            ["speed", speed] => GameCommand::SetSimulationSpeed(match *speed {
+                "Paused" => SimulationSpeed::Paused,
+                "Normal" => SimulationSpeed::Normal,
+                "Fast" => SimulationSpeed::Fast,
+                "Ultra" => SimulationSpeed::Ultra,
+                _ => return Err(format!("unknown speed: {speed}")),
+            }),
+            ["step"] => GameCommand::Step,
+            _ => return Err(format!("unknown command: {s}")),
+        };
+        Ok(command)
+    }
+}
+
+fn parse_position(x: &str, y: &str) -> Result<Position, String> {
+    Ok(Position {
+        x: x.parse().map_err(|_| format!("invalid x: {x}"))?,
+        y: y.parse().map_err(|_| format!("invalid y: {y}"))?,
+    })
+}
+
+/// A command with the ticks passed before it was given
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecordedCommand {
+    pub tick: u64,
+    pub command: GameCommand,
+}
+
+impl Display for RecordedCommand {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.tick, self.command)
+    }
+}
+
+impl FromStr for RecordedCommand {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (tick, command) = s
+            .trim()
+            .split_once(' ')
+            .ok_or_else(|| format!("missing tick: {s}"))?;
+        Ok(Self {
+            tick: tick.parse().map_err(|_| format!("invalid tick: {tick}"))?,
+            command: command.parse()?,
+        })
+    }
+}
+
+/// Write every command, one per line, as soon as it is given:
+/// the replay survives a crash
+pub struct CommandRecorder<W: Write> {
+    tick: u64,
+    output: W,
+}
+
+impl<W: Write> CommandRecorder<W> {
+    pub fn new(output: W) -> Self {
+        Self { tick: 0, output }
+    }
+
+    pub fn advance(&mut self, ticks: u32) {
+        self.tick += u64::from(ticks);
+    }
+
+    pub fn record(&mut self, command: GameCommand) -> std::io::Result<()> {
+        let recorded = RecordedCommand {
+            tick: self.tick,
+            command,
+        };
+        writeln!(self.output, "{recorded}")?;
+        self.output.flush()
+    }
+
+    #[cfg(test)]
+    fn into_output(self) -> W {
+        self.output
+    }
+}
+
+/// Give back the recorded commands at the same ticks
+#[derive(Debug)]
+pub struct CommandReplayer {
+    tick: u64,
+    commands: VecDeque<RecordedCommand>,
+}
+
+impl CommandReplayer {
+    pub fn parse(replay: &str) -> Result<Self, String> {
+        let commands = replay
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(str::parse)
+            .collect::<Result<_, _>>()?;
+        Ok(Self { tick: 0, commands })
+    }
+
+    pub fn advance(&mut self, ticks: u32) {
+        self.tick += u64::from(ticks);
+    }
+
+    /// The commands given before the current tick passed
+    pub fn next_commands(&mut self) -> Vec<GameCommand> {
+        let mut commands = vec![];
+        while let Some(recorded) = self.commands.front() {
+            if recorded.tick > self.tick {
+                break;
+            }
+            commands.push(recorded.command);
+            self.commands.pop_front();
+        }
+        commands
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.commands.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_commands() {
+        let commands = [
+            GameCommand::SetEditMode(EditMode::BiomassPowerPlant),
+            GameCommand::SetEditMode(EditMode::None),
+            GameCommand::SetOrientation(Orientation::West),
+            GameCommand::PlaceBuilding(Position { x: -3, y: 12 }),
+            GameCommand::PlaceStreets {
+                from: Position { x: 0, y: 1 },
+                to: Position { x: 5, y: -2 },
+            },
+            GameCommand::PlaceArea {
+                from: Position { x: 4, y: 4 },
+                to: Position { x: 1, y: 2 },
+            },
+            GameCommand::SetEditMode(EditMode::Demolish),
+            GameCommand::Demolish(Position { x: 7, y: 0 }),
+            GameCommand::Undo { force: false },
+            GameCommand::Undo { force: true },
+            GameCommand::Redo { force: false },
+            GameCommand::Redo { force: true },
+            GameCommand::SetSimulationSpeed(SimulationSpeed::Paused),
+            GameCommand::Step,
+        ];
+        for command in commands {
+            assert_eq!(command.to_string().parse(), Ok(command));
+        }
+
+        assert!("place 3".parse::<GameCommand>().is_err());
+        assert!("undo please".parse::<GameCommand>().is_err());
+        assert!("edit-mode Castle".parse::<GameCommand>().is_err());
+        assert!("orientation Up".parse::<GameCommand>().is_err());
+        assert!("step".parse::<RecordedCommand>().is_err());
+    }
+
+    #[test]
+    fn test_replay_gives_back_the_commands_at_the_same_ticks() {
+        let mut recorder = CommandRecorder::new(vec![]);
+        recorder
+            .record(GameCommand::SetEditMode(EditMode::House))
+            .unwrap();
+        recorder
+            .record(GameCommand::PlaceBuilding(Position { x: 1, y: 2 }))
+            .unwrap();
+        recorder.advance(3);
+        recorder
+            .record(GameCommand::SetSimulationSpeed(SimulationSpeed::Fast))
+            .unwrap();
+        let replay = String::from_utf8(recorder.into_output()).unwrap();
+
+        let mut replayer = CommandReplayer::parse(&replay).unwrap();
+        assert_eq!(
+            replayer.next_commands(),
+            vec![
+                GameCommand::SetEditMode(EditMode::House),
+                GameCommand::PlaceBuilding(Position { x: 1, y: 2 }),
+            ]
+        );
+        replayer.advance(2);
+        assert!(replayer.next_commands().is_empty());
+        assert!(!replayer.is_finished());
+        replayer.advance(1);
+        assert_eq!(
+            replayer.next_commands(),
+            vec![GameCommand::SetSimulationSpeed(SimulationSpeed::Fast)]
+        );
+        assert!(replayer.is_finished());
+    }
+}
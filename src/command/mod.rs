@@ -0,0 +1,6 @@
+mod manager;
+mod plugin;
+
+pub use manager::GameCommand;
+pub use plugin::events;
+pub use plugin::{CommandMode, CommandPlugin, PlacementDrag};
@@ -0,0 +1,334 @@
+use std::fs::File;
+
+use bevy::{
+    app::AppExit,
+    input::{keyboard::KeyboardInput, mouse::MouseButtonInput, ButtonState},
+    prelude::*,
+};
+use bevy_mod_picking::PickingEvent;
+
+use crate::{
+    building::{EditMode, PlacementOrientationResource, PlaneComponent},
+    overlay::HoveredTile,
+    GameTick, SimulationSpeed,
+};
+
+use super::manager::{CommandRecorder, CommandReplayer, GameCommand};
+
+pub use self::events::*;
+pub use self::resources::*;
+
+pub struct CommandPlugin;
+
+impl Plugin for CommandPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<GameCommandEvent>()
+            .init_resource::<PlacementDrag>()
+            .add_system_to_stage(CoreStage::PreUpdate, read_player_input)
+            .add_system_to_stage(CoreStage::PreUpdate, replay_commands)
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                run_headless_replay.after(replay_commands),
+            )
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                record_commands
+                    .after(read_player_input)
+                    .after(replay_commands),
+            );
+
+        let mode = app.world.get_resource::<CommandMode>().cloned();
+        match mode {
+            None | Some(CommandMode::Play) => {}
+            Some(CommandMode::Record { path }) => match File::create(&path) {
+                Ok(file) => {
+                    info!("recording the commands into {path}");
+                    app.insert_resource(CommandRecorderResource(CommandRecorder::new(file)));
+                }
+                Err(e) => error!("cannot record the commands into {path}: {e}"),
+            },
+            Some(CommandMode::Replay { path, headless }) => {
+                let replayer = std::fs::read_to_string(&path)
+                    .map_err(|e| e.to_string())
+                    .and_then(|replay| CommandReplayer::parse(&replay));
+                match replayer {
+                    Ok(replayer) => {
+                        info!("replaying the commands of {path}");
+                        app.insert_resource(CommandReplayerResource(replayer));
+                    }
+                    // Nothing to replay: headless it ends, otherwise the city is played as usual
+                    Err(e) => {
+                        error!("invalid replay {path}: {e}");
+                        if headless {
+                            app.world.send_event(AppExit);
+                        } else {
+                            app.insert_resource(CommandMode::Play);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Turn the keyboard and the clicks into commands. R turns the placement clockwise.
+/// The streets and the areas are dragged from the pressed tile to the released one.
+/// The player cannot interfere with a replay
+#[allow(clippy::too_many_arguments)]
+fn read_player_input(
+    keys: Res<Input<KeyCode>>,
+    mut keyboard_input_events: EventReader<KeyboardInput>,
+    mut picking_events: EventReader<PickingEvent>,
+    mut mouse_button_events: EventReader<MouseButtonInput>,
+    mut drag: ResMut<PlacementDrag>,
+    planes: Query<&PlaneComponent>,
+    edit_mode: Res<EditMode>,
+    orientation: Res<PlacementOrientationResource>,
+    hovered_tile: Res<HoveredTile>,
+    replayer: Option<Res<CommandReplayerResource>>,
+    mut command_writer: EventWriter<GameCommandEvent>,
+) {
+    // The input during the replay is dropped, not kept for later
+    if replayer.is_some_and(|replayer| !replayer.is_finished()) {
+        keyboard_input_events.clear();
+        picking_events.clear();
+        mouse_button_events.clear();
+        return;
+    }
+
+    let control = keys.any_pressed([KeyCode::LControl, KeyCode::RControl]);
+    // Shift forces the removal of the inhabited buildings
+    let force = keys.any_pressed([KeyCode::LShift, KeyCode::RShift]);
+    // Every press turns it once more
+    let mut orientation = **orientation;
+
+    let keys = keyboard_input_events
+        .iter()
+        .filter(|e| e.state == ButtonState::Released)
+        .filter_map(|e| e.key_code)
+        .filter_map(|key_code| match key_code {
+            KeyCode::Z if control => Some(GameCommand::Undo { force }),
+            KeyCode::Y if control => Some(GameCommand::Redo { force }),
+            KeyCode::S => Some(GameCommand::SetEditMode(EditMode::Street)),
+            KeyCode::G => Some(GameCommand::SetEditMode(EditMode::Garden)),
+            KeyCode::H => Some(GameCommand::SetEditMode(EditMode::House)),
+            KeyCode::O => Some(GameCommand::SetEditMode(EditMode::Office)),
+            KeyCode::B => Some(GameCommand::SetEditMode(EditMode::BiomassPowerPlant)),
+            KeyCode::A => Some(GameCommand::SetEditMode(EditMode::Battery)),
+            KeyCode::E => Some(GameCommand::SetEditMode(EditMode::School)),
+            KeyCode::X => Some(GameCommand::SetEditMode(EditMode::Demolish)),
+            KeyCode::R => {
+                orientation = orientation.next();
+                Some(GameCommand::SetOrientation(orientation))
+            }
+            KeyCode::Escape => Some(GameCommand::SetEditMode(EditMode::None)),
+            KeyCode::P => Some(GameCommand::SetSimulationSpeed(SimulationSpeed::Paused)),
+            KeyCode::Key1 => Some(GameCommand::SetSimulationSpeed(SimulationSpeed::Normal)),
+            KeyCode::Key2 => Some(GameCommand::SetSimulationSpeed(SimulationSpeed::Fast)),
+            KeyCode::Key3 => Some(GameCommand::SetSimulationSpeed(SimulationSpeed::Ultra)),
+            KeyCode::N => Some(GameCommand::Step),
+            _ => None,
+        });
+    let mut commands: Vec<_> = keys.collect();
+
+    let clicked = picking_events.iter().filter_map(|e| match e {
+        PickingEvent::Clicked(entity) => planes.get(*entity).ok(),
+        _ => None,
+    });
+    for plane in clicked {
+        if *edit_mode == EditMode::Street || edit_mode.fills_area() {
+            drag.start = Some(plane.0);
+        } else if *edit_mode == EditMode::Demolish {
+            commands.push(GameCommand::Demolish(plane.0));
+        } else {
+            commands.push(GameCommand::PlaceBuilding(plane.0));
+        }
+    }
+
+    let released = mouse_button_events
+        .iter()
+        .any(|e| e.button == MouseButton::Left && e.state == ButtonState::Released);
+    if let (true, Some(from)) = (released, drag.start) {
+        drag.start = None;
+        let to = hovered_tile.position.unwrap_or(from);
+        if *edit_mode == EditMode::Street {
+            commands.push(GameCommand::PlaceStreets { from, to });
+        } else {
+            commands.push(GameCommand::PlaceArea { from, to });
+        }
+    }
+
+    command_writer.send_batch(
+        commands
+            .into_iter()
+            .map(|command| GameCommandEvent { command }),
+    );
+}
+
+/// Give back the recorded commands when their tick comes
+fn replay_commands(
+    mut game_tick: EventReader<GameTick>,
+    replayer: Option<ResMut<CommandReplayerResource>>,
+    mut command_writer: EventWriter<GameCommandEvent>,
+) {
+    let ticks = GameTick::count(&mut game_tick);
+    let mut replayer = match replayer {
+        None => return,
+        Some(replayer) => replayer,
+    };
+    if replayer.is_finished() {
+        return;
+    }
+
+    replayer.advance(ticks);
+    for command in replayer.next_commands() {
+        debug!("replay {command}");
+        command_writer.send(GameCommandEvent { command });
+    }
+}
+
+/// Headless, a tick passes every frame and the app exits at the end of the replay.
+/// Apart from `replay_commands`: a system cannot both read and send the ticks
+fn run_headless_replay(
+    replayer: Option<Res<CommandReplayerResource>>,
+    mode: Option<Res<CommandMode>>,
+    simulation_speed: Res<SimulationSpeed>,
+    mut game_tick_writer: EventWriter<GameTick>,
+    mut app_exit_writer: EventWriter<AppExit>,
+) {
+    let replayer = match replayer {
+        None => return,
+        Some(replayer) => replayer,
+    };
+    if !mode.is_some_and(|mode| mode.is_headless()) {
+        return;
+    }
+    if replayer.is_finished() {
+        info!("replay finished");
+        app_exit_writer.send(AppExit);
+    } else if *simulation_speed != SimulationSpeed::Paused {
+        game_tick_writer.send(GameTick(1));
+    }
+}
+
+/// Write every command with the ticks passed before it
+fn record_commands(
+    mut game_tick: EventReader<GameTick>,
+    mut commands: EventReader<GameCommandEvent>,
+    recorder: Option<ResMut<CommandRecorderResource>>,
+) {
+    let ticks = GameTick::count(&mut game_tick);
+    let mut recorder = match recorder {
+        None => return,
+        Some(recorder) => recorder,
+    };
+
+    recorder.advance(ticks);
+    for GameCommandEvent { command } in commands.iter() {
+        if let Err(e) = recorder.record(*command) {
+            error!("cannot record {command}: {e}");
+        }
+    }
+}
+
+mod resources {
+    use std::{
+        fs::File,
+        ops::{Deref, DerefMut},
+    };
+
+    use bevy::prelude::Resource;
+
+    use crate::{
+        command::manager::{CommandRecorder, CommandReplayer},
+        common::position::Position,
+    };
+
+    /// The tile where the player started dragging streets or an area
+    #[derive(Resource, Default)]
+    pub struct PlacementDrag {
+        pub start: Option<Position>,
+    }
+
+    /// Where the commands come from
+    #[derive(Debug, Clone, Resource)]
+    pub enum CommandMode {
+        /// The player gives the commands, nothing is recorded
+        Play,
+        /// The player gives the commands, written into the file
+        Record { path: String },
+        /// The commands come from a recorded file, the player only watches
+        Replay { path: String, headless: bool },
+    }
+
+    impl CommandMode {
+        /// `--replay <path> [--headless]` replays, `--record <path>` records the game
+        pub fn from_args(args: impl Iterator<Item = String>) -> Self {
+            let args: Vec<_> = args.collect();
+            let value_of = |flag: &str| {
+                args.iter()
+                    .position(|arg| arg == flag)
+                    .and_then(|i| args.get(i + 1))
+                    .cloned()
+            };
+
+            match value_of("--replay") {
+                Some(path) => CommandMode::Replay {
+                    path,
+                    headless: args.iter().any(|arg| arg == "--headless"),
+                },
+                None => match value_of("--record") {
+                    Some(path) => CommandMode::Record { path },
+                    None => CommandMode::Play,
+                },
+            }
+        }
+
+        pub fn is_headless(&self) -> bool {
+            matches!(self, CommandMode::Replay { headless: true, .. })
+        }
+    }
+
+    #[derive(Resource)]
+    pub struct CommandRecorderResource(pub CommandRecorder<File>);
+
+    impl Deref for CommandRecorderResource {
+        type Target = CommandRecorder<File>;
+
+        fn deref(&self) -> &Self::Target {
+            &self.0
+        }
+    }
+
+    impl DerefMut for CommandRecorderResource {
+        fn deref_mut(&mut self) -> &mut Self::Target {
+            &mut self.0
+        }
+    }
+
+    #[derive(Resource)]
+    pub struct CommandReplayerResource(pub CommandReplayer);
+
+    impl Deref for CommandReplayerResource {
+        type Target = CommandReplayer;
+
+        fn deref(&self) -> &Self::Target {
+            &self.0
+        }
+    }
+
+    impl DerefMut for CommandReplayerResource {
+        fn deref_mut(&mut self) -> &mut Self::Target {
+            &mut self.0
+        }
+    }
+}
+
+pub mod events {
+    use crate::command::manager::GameCommand;
+
+    /// Sent for every command, given by the player or replayed
+    pub struct GameCommandEvent {
+        pub command: GameCommand,
+    }
+}
@@ -1,3 +1,5 @@
+use super::{enums::EducationLevel, position::Footprint};
+
 #[derive(Debug, Clone)]
 pub struct Configuration {
     pub cube_size: f32,
@@ -6,6 +8,22 @@ pub struct Configuration {
 
     pub game: GameConfiguration,
 
+    pub power: PowerConfiguration,
+
+    pub environment: EnvironmentConfiguration,
+
+    pub land_value: LandValueConfiguration,
+
+    pub happiness: HappinessConfiguration,
+
+    pub emigration: EmigrationConfiguration,
+
+    pub population: PopulationConfiguration,
+
+    pub assignment: AssignmentConfiguration,
+
+    pub clock: ClockConfiguration,
+
     pub buildings: BuildingsConfiguration,
 }
 
@@ -23,6 +41,154 @@ impl Configuration {
 pub struct GameConfiguration {
     pub width_table: usize,
     pub depth_table: usize,
+    /// Same seed, same city: every stochastic decision comes from it
+    pub seed: u64,
+    /// How many player actions can be undone
+    pub undo_history_size: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct PowerConfiguration {
+    pub assignment_strategy: PowerAssignmentStrategyKind,
+}
+
+#[derive(Debug, Clone)]
+pub enum PowerAssignmentStrategyKind {
+    /// The first producer with enough capacity
+    FirstFit,
+    /// The producer nearest to the consumer
+    Nearest,
+    /// The producer with the lowest percentage of used capacity
+    LeastLoaded,
+    /// The producer with the highest `PowerSourceConfiguration::priority`
+    Priority,
+}
+
+#[derive(Debug, Clone)]
+pub struct EnvironmentConfiguration {
+    pub pollution: EnvironmentFieldConfiguration,
+    pub noise: EnvironmentFieldConfiguration,
+}
+
+#[derive(Debug, Clone)]
+pub struct EnvironmentFieldConfiguration {
+    /// The fraction of the value moved to the neighbors every tick
+    pub diffusion_rate: f32,
+    /// The fraction of the value kept every tick
+    pub decay_rate: f32,
+    /// How much a unit of the field reduces the house palatability
+    pub house_palatability_weight: f32,
+}
+
+#[derive(Debug, Clone)]
+pub struct LandValueConfiguration {
+    pub base_value: i32,
+    /// Multiplied by the house palatability
+    pub palatability_weight: i32,
+    /// Over this distance, the tile has no access to the streets
+    pub max_street_distance: u32,
+    /// Given to the tiles next to a street, it decreases with the distance
+    pub street_access_value: i32,
+    /// Given to the tiles completely covered by the power
+    pub power_value: i32,
+    pub service_radius: u32,
+    /// Given for every service in `service_radius`, up to `max_services`
+    pub service_value: i32,
+    pub max_services: u32,
+    /// Sorted by `min_land_value`
+    pub house_densities: &'static [HouseDensityConfiguration],
+    /// Collected every tick for every resident and unit of land value
+    pub tax_rate: f32,
+}
+
+#[derive(Debug, Clone)]
+pub struct HouseDensityConfiguration {
+    pub min_land_value: i32,
+    pub max_residents: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct HappinessConfiguration {
+    pub base_value: i32,
+    /// Multiplied by the house palatability of the home
+    pub palatability_weight: i32,
+    pub employed_value: i32,
+    pub unemployed_value: i32,
+    /// Given to the homes completely covered by the power, taken from the uncovered ones
+    pub power_value: i32,
+    /// Over this distance, every tile of the commute costs `commute_penalty`
+    pub max_comfortable_commute: u32,
+    pub commute_penalty: i32,
+    /// Multiplied by the taxes paid every tick
+    pub tax_weight: f32,
+    /// Under this happiness, the inhabitant could leave the city
+    pub unhappy_threshold: u8,
+    /// The chance to leave on every tick, reached with no happiness at all
+    pub max_leave_probability: f32,
+}
+
+#[derive(Debug, Clone)]
+pub struct EmigrationConfiguration {
+    /// After these ticks without a job, the inhabitant leaves the city
+    pub max_unemployed_ticks: u32,
+    /// After these ticks in an house without power, the inhabitant leaves the city
+    pub max_unpowered_ticks: u32,
+    /// Under this house palatability, the inhabitant leaves the city.
+    /// The houses lower the palatability around them, so it is below 0
+    pub min_house_palatability: i32,
+}
+
+#[derive(Debug, Clone)]
+pub struct PopulationConfiguration {
+    /// A year passes every these ticks
+    pub ticks_per_year: u32,
+    /// The children become adults, looking for a job
+    pub adult_age: u32,
+    /// The workers leave their job
+    pub retirement_age: u32,
+    /// From this age, the chance to die grows every year
+    pub old_age: u32,
+    /// Added to the chance to die for every year over `old_age`
+    pub death_probability_per_year: f32,
+    /// The chance that a child is born in an house with at least two adults, every year
+    pub birth_probability: f32,
+    /// The immigrants are adults younger than this age
+    pub max_immigrant_age: u32,
+}
+
+/// How the inhabitants are matched with the houses and the offices
+#[derive(Debug, Clone)]
+pub struct AssignmentConfiguration {
+    /// The households or the workers matched together
+    pub batch_size: usize,
+    /// Multiplied by the palatability of the house
+    pub palatability_weight: i64,
+    /// Multiplied by the tiles between home and office
+    pub commute_weight: i64,
+    /// Multiplied by the free spots of the office
+    pub vacancy_weight: i64,
+}
+
+/// The in-game calendar
+#[derive(Debug, Clone)]
+pub struct ClockConfiguration {
+    pub ticks_per_hour: u32,
+    /// The date and the hour when the game starts
+    pub start_year: i32,
+    pub start_month: u32,
+    pub start_day: u32,
+    pub start_hour: u32,
+    pub sunrise_hour: u32,
+    pub sunset_hour: u32,
+    pub day_illuminance: f32,
+    pub night_illuminance: f32,
+    /// The power demand in percentage of the nominal one
+    pub day_power_demand_percentage: u32,
+    pub night_power_demand_percentage: u32,
+    /// The commuters on the streets in percentage, at rush hours everybody commutes
+    pub rush_hours: &'static [u32],
+    pub day_traffic_percentage: u32,
+    pub night_traffic_percentage: u32,
 }
 
 #[derive(Debug, Clone)]
@@ -32,11 +198,14 @@ pub struct BuildingsConfiguration {
     pub garden: GardenConfiguration,
     pub street: StreetConfiguration,
     pub biomass_power_plant: BiomassPowerPlantConfiguration,
+    pub battery: BatteryConfiguration,
+    pub school: SchoolConfiguration,
 }
 
 #[derive(Debug, Clone)]
 pub struct HouseConfiguration {
     pub max_residents: u32,
+    /// The biggest household, moving together into an house
     pub max_inhabitant_per_travel: u32,
     pub common: CommonBuildingConfiguration,
     pub palatability_configuration: PalatabilityConfiguration,
@@ -45,6 +214,8 @@ pub struct HouseConfiguration {
 #[derive(Debug, Clone)]
 pub struct OfficeConfiguration {
     pub max_worker: u32,
+    /// The workers need at least this education
+    pub required_education_level: EducationLevel,
     pub common: CommonBuildingConfiguration,
     pub palatability_configuration: PalatabilityConfiguration,
     pub power_consumer_configuration: PowerConsumerConfiguration,
@@ -58,6 +229,7 @@ pub struct GardenConfiguration {
 pub struct StreetConfiguration {
     pub common: CommonBuildingConfiguration,
     pub palatability_configuration: PalatabilityConfiguration,
+    pub traffic_emission: TrafficEmissionConfiguration,
 }
 #[derive(Debug, Clone)]
 pub struct BiomassPowerPlantConfiguration {
@@ -66,21 +238,78 @@ pub struct BiomassPowerPlantConfiguration {
     pub power_source: PowerSourceConfiguration,
 }
 #[derive(Debug, Clone)]
+pub struct BatteryConfiguration {
+    pub common: CommonBuildingConfiguration,
+    pub palatability_configuration: PalatabilityConfiguration,
+    pub power_storage: PowerStorageConfiguration,
+}
+#[derive(Debug, Clone)]
+pub struct SchoolConfiguration {
+    pub max_students: u32,
+    /// The students reach the next education level after these ticks
+    pub ticks_for_level: u32,
+    pub common: CommonBuildingConfiguration,
+    pub palatability_configuration: PalatabilityConfiguration,
+}
+#[derive(Debug, Clone)]
 pub struct CommonBuildingConfiguration {
     pub building_name: &'static str,
     pub time_for_building: u8,
+    /// Paid from the treasury when the building is placed
+    pub cost: u64,
+    pub footprint: Footprint,
 }
 #[derive(Debug, Clone)]
 pub struct PalatabilityConfiguration {
     pub source_for_house: Option<SourcePalatabilityConfiguration>,
     pub source_for_office: Option<SourcePalatabilityConfiguration>,
+    pub emission: Option<EmissionConfiguration>,
+}
+
+/// Emitted every tick
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EmissionConfiguration {
+    pub pollution: f32,
+    pub noise: f32,
+}
+
+#[derive(Debug, Clone)]
+pub struct TrafficEmissionConfiguration {
+    /// A street is busy from this number of commuters
+    pub busy_commuters: u32,
+    pub per_commuter: EmissionConfiguration,
 }
 #[derive(Debug, Clone)]
 pub struct SourcePalatabilityConfiguration {
     pub value: i32,
+    /// Under this distance, the value is `value`
     pub max_horizontal_distribution_distance: u32,
-    pub max_linear_distribution_distance: u32,
-    pub linear_factor: i32,
+    /// Under this distance, the value decreases following `falloff`
+    pub max_falloff_distribution_distance: u32,
+    pub falloff: PalatabilityFalloff,
+    pub distance: DistanceKind,
+}
+
+/// How the value decreases after `max_horizontal_distribution_distance`.
+/// `d` is the distance from the end of the horizontal distribution
+#[derive(Debug, Clone)]
+pub enum PalatabilityFalloff {
+    /// `value`
+    Flat,
+    /// `value - factor * d`, never crossing 0
+    Linear { factor: i32 },
+    /// `value` halved every `half_life`
+    Exponential { half_life: u32 },
+    /// `value / (1 + d)^2`
+    InverseSquare,
+    /// `value * table[d] / 100`, 0 after the end of the table
+    LookupTable(&'static [u8]),
+}
+
+#[derive(Debug, Clone)]
+pub enum DistanceKind {
+    Manhattan,
+    Euclidean,
 }
 
 #[derive(Debug, Clone)]
@@ -91,6 +320,14 @@ pub struct PowerConsumerConfiguration {
 #[derive(Debug, Clone)]
 pub struct PowerSourceConfiguration {
     pub capacity_wh: u32,
+    pub priority: u8,
+}
+
+#[derive(Debug, Clone)]
+pub struct PowerStorageConfiguration {
+    pub capacity_wh: u32,
+    pub max_charge_wh: u32,
+    pub max_discharge_wh: u32,
 }
 
 // #[cfg(test)]
@@ -101,6 +338,100 @@ pub const CONFIGURATION: Configuration = Configuration {
     game: GameConfiguration {
         width_table: 32,
         depth_table: 32,
+        seed: 0x2545_f491_4f6c_dd1d,
+        undo_history_size: 100,
+    },
+
+    power: PowerConfiguration {
+        assignment_strategy: PowerAssignmentStrategyKind::Nearest,
+    },
+
+    environment: EnvironmentConfiguration {
+        pollution: EnvironmentFieldConfiguration {
+            diffusion_rate: 0.2,
+            decay_rate: 0.9,
+            house_palatability_weight: 1.,
+        },
+        noise: EnvironmentFieldConfiguration {
+            diffusion_rate: 0.1,
+            decay_rate: 0.6,
+            house_palatability_weight: 0.5,
+        },
+    },
+
+    land_value: LandValueConfiguration {
+        base_value: 10,
+        palatability_weight: 2,
+        max_street_distance: 3,
+        street_access_value: 20,
+        power_value: 20,
+        service_radius: 8,
+        service_value: 10,
+        max_services: 3,
+        house_densities: &[
+            HouseDensityConfiguration {
+                min_land_value: 80,
+                max_residents: 12,
+            },
+            HouseDensityConfiguration {
+                min_land_value: 100,
+                max_residents: 16,
+            },
+        ],
+        tax_rate: 0.01,
+    },
+
+    happiness: HappinessConfiguration {
+        base_value: 50,
+        palatability_weight: 2,
+        employed_value: 15,
+        unemployed_value: -15,
+        power_value: 15,
+        max_comfortable_commute: 10,
+        commute_penalty: 1,
+        tax_weight: 10.,
+        unhappy_threshold: 30,
+        max_leave_probability: 0.05,
+    },
+
+    emigration: EmigrationConfiguration {
+        max_unemployed_ticks: 120,
+        max_unpowered_ticks: 60,
+        min_house_palatability: -5,
+    },
+
+    population: PopulationConfiguration {
+        ticks_per_year: 12,
+        adult_age: 18,
+        retirement_age: 65,
+        old_age: 70,
+        death_probability_per_year: 0.05,
+        birth_probability: 0.2,
+        max_immigrant_age: 45,
+    },
+
+    assignment: AssignmentConfiguration {
+        batch_size: 10,
+        palatability_weight: 1,
+        commute_weight: 2,
+        vacancy_weight: 1,
+    },
+
+    clock: ClockConfiguration {
+        ticks_per_hour: 1,
+        start_year: 2000,
+        start_month: 1,
+        start_day: 1,
+        start_hour: 8,
+        sunrise_hour: 6,
+        sunset_hour: 20,
+        day_illuminance: 5000.,
+        night_illuminance: 500.,
+        day_power_demand_percentage: 100,
+        night_power_demand_percentage: 60,
+        rush_hours: &[8, 18],
+        day_traffic_percentage: 50,
+        night_traffic_percentage: 10,
     },
 
     buildings: BuildingsConfiguration {
@@ -110,31 +441,43 @@ pub const CONFIGURATION: Configuration = Configuration {
             common: CommonBuildingConfiguration {
                 building_name: "house",
                 time_for_building: 10,
+                cost: 0,
+                footprint: Footprint::SINGLE,
             },
             palatability_configuration: PalatabilityConfiguration {
                 source_for_house: Some(SourcePalatabilityConfiguration {
                     value: -1,
                     max_horizontal_distribution_distance: 2,
-                    max_linear_distribution_distance: 1,
-                    linear_factor: 0,
+                    max_falloff_distribution_distance: 0,
+                    falloff: PalatabilityFalloff::Flat,
+                    distance: DistanceKind::Manhattan,
                 }),
                 source_for_office: None,
+                emission: None,
             },
             power_consumer_configuration: PowerConsumerConfiguration { consume_wh: 300 },
         },
         office: OfficeConfiguration {
             max_worker: 6,
+            required_education_level: EducationLevel::None,
             common: CommonBuildingConfiguration {
                 building_name: "office",
                 time_for_building: 5,
+                cost: 0,
+                footprint: Footprint::SINGLE,
             },
             palatability_configuration: PalatabilityConfiguration {
                 source_for_house: None,
                 source_for_office: Some(SourcePalatabilityConfiguration {
                     value: 1,
                     max_horizontal_distribution_distance: 3,
-                    max_linear_distribution_distance: 0,
-                    linear_factor: 0,
+                    max_falloff_distribution_distance: 0,
+                    falloff: PalatabilityFalloff::Flat,
+                    distance: DistanceKind::Manhattan,
+                }),
+                emission: Some(EmissionConfiguration {
+                    pollution: 0.,
+                    noise: 1.,
                 }),
             },
             power_consumer_configuration: PowerConsumerConfiguration { consume_wh: 2000 },
@@ -143,43 +486,107 @@ pub const CONFIGURATION: Configuration = Configuration {
             common: CommonBuildingConfiguration {
                 building_name: "garden",
                 time_for_building: 2,
+                cost: 0,
+                footprint: Footprint::SINGLE,
             },
             palatability_configuration: PalatabilityConfiguration {
                 source_for_house: Some(SourcePalatabilityConfiguration {
                     value: 10,
                     max_horizontal_distribution_distance: 3,
-                    max_linear_distribution_distance: 10,
-                    linear_factor: 2,
+                    max_falloff_distribution_distance: 10,
+                    falloff: PalatabilityFalloff::Linear { factor: 2 },
+                    distance: DistanceKind::Manhattan,
                 }),
                 source_for_office: Some(SourcePalatabilityConfiguration {
                     value: 10,
                     max_horizontal_distribution_distance: 3,
-                    max_linear_distribution_distance: 10,
-                    linear_factor: 2,
+                    max_falloff_distribution_distance: 10,
+                    falloff: PalatabilityFalloff::Linear { factor: 2 },
+                    distance: DistanceKind::Manhattan,
                 }),
+                emission: None,
             },
         },
         street: StreetConfiguration {
             common: CommonBuildingConfiguration {
                 building_name: "street",
                 time_for_building: 2,
+                cost: 0,
+                footprint: Footprint::SINGLE,
             },
             palatability_configuration: PalatabilityConfiguration {
                 source_for_house: None,
                 source_for_office: None,
+                emission: None,
+            },
+            traffic_emission: TrafficEmissionConfiguration {
+                busy_commuters: 3,
+                per_commuter: EmissionConfiguration {
+                    pollution: 0.2,
+                    noise: 0.5,
+                },
             },
         },
         biomass_power_plant: BiomassPowerPlantConfiguration {
             common: CommonBuildingConfiguration {
                 building_name: "biomassPowerPlant",
                 time_for_building: 10,
+                cost: 0,
+                footprint: Footprint { width: 2, depth: 2 },
             },
             palatability_configuration: PalatabilityConfiguration {
                 source_for_house: None,
                 source_for_office: None,
+                emission: Some(EmissionConfiguration {
+                    pollution: 4.,
+                    noise: 2.,
+                }),
             },
             power_source: PowerSourceConfiguration {
                 capacity_wh: 7_000_000,
+                priority: 0,
+            },
+        },
+        battery: BatteryConfiguration {
+            common: CommonBuildingConfiguration {
+                building_name: "battery",
+                time_for_building: 5,
+                cost: 0,
+                footprint: Footprint::SINGLE,
+            },
+            palatability_configuration: PalatabilityConfiguration {
+                source_for_house: None,
+                source_for_office: None,
+                emission: None,
+            },
+            power_storage: PowerStorageConfiguration {
+                capacity_wh: 2_000_000,
+                max_charge_wh: 500_000,
+                max_discharge_wh: 500_000,
+            },
+        },
+        school: SchoolConfiguration {
+            max_students: 20,
+            ticks_for_level: 60,
+            common: CommonBuildingConfiguration {
+                building_name: "school",
+                time_for_building: 8,
+                cost: 0,
+                footprint: Footprint::SINGLE,
+            },
+            palatability_configuration: PalatabilityConfiguration {
+                source_for_house: Some(SourcePalatabilityConfiguration {
+                    value: 3,
+                    max_horizontal_distribution_distance: 3,
+                    max_falloff_distribution_distance: 6,
+                    falloff: PalatabilityFalloff::Linear { factor: 1 },
+                    distance: DistanceKind::Manhattan,
+                }),
+                source_for_office: None,
+                emission: Some(EmissionConfiguration {
+                    pollution: 0.,
+                    noise: 0.5,
+                }),
             },
         },
     },
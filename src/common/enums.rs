@@ -1,17 +1,20 @@
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Sorted from the lowest to the highest level
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub enum EducationLevel {
     None,
-    #[allow(dead_code)]
     Low,
+    Medium,
+    High,
 }
 
-impl PartialOrd for EducationLevel {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        match (self, other) {
-            (EducationLevel::None, EducationLevel::None)
-            | (EducationLevel::Low, EducationLevel::Low) => Some(std::cmp::Ordering::Equal),
-            (_, EducationLevel::Low) => Some(std::cmp::Ordering::Less),
-            (EducationLevel::Low, _) => Some(std::cmp::Ordering::Greater),
+impl EducationLevel {
+    /// The level reached studying, `None` for the highest one
+    pub fn next(&self) -> Option<EducationLevel> {
+        match self {
+            EducationLevel::None => Some(EducationLevel::Low),
+            EducationLevel::Low => Some(EducationLevel::Medium),
+            EducationLevel::Medium => Some(EducationLevel::High),
+            EducationLevel::High => None,
         }
     }
 }
@@ -0,0 +1,64 @@
+use std::{borrow::Cow, collections::BTreeSet};
+
+use bevy::{
+    ecs::schedule::{ParallelSystemExecutor, SystemContainer},
+    prelude::World,
+};
+
+/// Run the systems one at a time, always in the same order.
+/// Bevy sorts the systems through randomly seeded hash maps: two unordered systems
+/// sharing a resource or an event could see each other's changes in any order
+#[derive(Debug, Default)]
+pub struct OrderedExecutor {
+    order: Vec<usize>,
+}
+
+impl ParallelSystemExecutor for OrderedExecutor {
+    /// The dependencies first, then the names decide
+    fn rebuild_cached_data(&mut self, systems: &[SystemContainer]) {
+        let mut missing_dependencies: Vec<usize> = systems
+            .iter()
+            .map(|system| system.dependencies().len())
+            .collect();
+        let mut dependants = vec![vec![]; systems.len()];
+        for (index, system) in systems.iter().enumerate() {
+            for dependency in system.dependencies() {
+                dependants[*dependency].push(index);
+            }
+        }
+
+        let mut ready: BTreeSet<(Cow<'static, str>, usize)> = systems
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| missing_dependencies[*index] == 0)
+            .map(|(index, system)| (system.name(), index))
+            .collect();
+        self.order.clear();
+        while let Some((_, index)) = ready.pop_first() {
+            self.order.push(index);
+            for dependant in &dependants[index] {
+                missing_dependencies[*dependant] -= 1;
+                if missing_dependencies[*dependant] == 0 {
+                    ready.insert((systems[*dependant].name(), *dependant));
+                }
+            }
+        }
+        debug_assert_eq!(self.order.len(), systems.len());
+    }
+
+    /// The commands are applied in the same order too: the stage finds them empty
+    fn run_systems(&mut self, systems: &mut [SystemContainer], world: &mut World) {
+        for index in &self.order {
+            let system = &mut systems[*index];
+            if system.should_run() {
+                system.system_mut().run((), world);
+            }
+        }
+        for index in &self.order {
+            let system = &mut systems[*index];
+            if system.should_run() {
+                system.system_mut().apply_buffers(world);
+            }
+        }
+    }
+}
@@ -1,6 +1,8 @@
 pub mod configuration;
 pub mod enums;
+pub mod executor;
 pub mod position;
 pub mod position_utils;
+pub mod random;
 
 pub type EntityId = u64;
@@ -1,3 +1,5 @@
+use std::collections::BTreeSet;
+
 static DELTA: [Position; 4] = [
     Position { x: -1, y: 0 },
     Position { x: 1, y: 0 },
@@ -5,7 +7,7 @@ static DELTA: [Position; 4] = [
     Position { x: 0, y: 1 },
 ];
 
-#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
 pub struct Position {
     pub x: i64,
     pub y: i64,
@@ -23,4 +25,85 @@ impl Position {
             .try_into()
             .expect("i64 cannot be converted to u32")
     }
+
+    pub fn euclidean_distance(&self, position: &Position) -> f32 {
+        let dx = (position.x - self.x) as f32;
+        let dy = (position.y - self.y) as f32;
+        (dx * dx + dy * dy).sqrt()
+    }
+}
+
+/// The tiles covered by a building, from its position towards the positive axes
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Footprint {
+    pub width: u32,
+    pub depth: u32,
+}
+impl Footprint {
+    pub const SINGLE: Footprint = Footprint { width: 1, depth: 1 };
+
+    /// Turned sideways when facing east or west
+    pub fn rotated(&self, orientation: Orientation) -> Footprint {
+        match orientation {
+            Orientation::North | Orientation::South => *self,
+            Orientation::East | Orientation::West => Footprint {
+                width: self.depth,
+                depth: self.width,
+            },
+        }
+    }
+
+    pub fn tiles(&self, position: &Position) -> impl Iterator<Item = Position> {
+        let Position { x, y } = *position;
+        let (width, depth) = (i64::from(self.width), i64::from(self.depth));
+        (y..y + depth).flat_map(move |y| (x..x + width).map(move |x| Position { x, y }))
+    }
+
+    /// The tiles sharing a side with the footprint, outside of it
+    pub fn neighbors(&self, position: &Position) -> BTreeSet<Position> {
+        let tiles: BTreeSet<_> = self.tiles(position).collect();
+        tiles
+            .iter()
+            .flat_map(|tile| tile.neighbors().collect::<Vec<_>>())
+            .filter(|neighbor| !tiles.contains(neighbor))
+            .collect()
+    }
+
+    /// The tiles along the side the building faces, outside of the footprint
+    pub fn front(&self, position: &Position, orientation: Orientation) -> BTreeSet<Position> {
+        let Position { x, y } = *position;
+        let (width, depth) = (i64::from(self.width), i64::from(self.depth));
+        match orientation {
+            Orientation::North => (x..x + width).map(|x| Position { x, y: y - 1 }).collect(),
+            Orientation::South => (x..x + width)
+                .map(|x| Position { x, y: y + depth })
+                .collect(),
+            Orientation::West => (y..y + depth).map(|y| Position { x: x - 1, y }).collect(),
+            Orientation::East => (y..y + depth)
+                .map(|y| Position { x: x + width, y })
+                .collect(),
+        }
+    }
+}
+
+/// Where the building faces: its entrance is on that side
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub enum Orientation {
+    /// Towards the negative y
+    #[default]
+    North,
+    East,
+    South,
+    West,
+}
+impl Orientation {
+    /// A quarter turn clockwise
+    pub fn next(&self) -> Orientation {
+        match self {
+            Orientation::North => Orientation::East,
+            Orientation::East => Orientation::South,
+            Orientation::South => Orientation::West,
+            Orientation::West => Orientation::North,
+        }
+    }
 }
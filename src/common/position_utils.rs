@@ -1,6 +1,14 @@
-use bevy::math::Vec3;
+use std::f32::consts::{FRAC_PI_2, PI};
 
-use super::{configuration::Configuration, position::Position};
+use bevy::{
+    math::{Quat, Vec3},
+    prelude::Transform,
+};
+
+use super::{
+    configuration::Configuration,
+    position::{Footprint, Orientation, Position},
+};
 
 pub fn convert_bevy_coords_into_position(configuration: &Configuration, coords: &Vec3) -> Position {
     let (half_width, half_depth) = configuration.half();
@@ -20,6 +28,35 @@ pub fn convert_position_into_bevy_coords(
     Vec3::new(x, 0.5, z)
 }
 
+/// Relative to the building position: a single tile mesh, scaled to the footprint
+/// as placed and turned towards the orientation
+pub fn convert_footprint_into_bevy_transform(
+    configuration: &Configuration,
+    footprint: &Footprint,
+    orientation: Orientation,
+) -> Transform {
+    let (width, depth) = (footprint.width as f32, footprint.depth as f32);
+    // Scaled before turning it
+    let unrotated = footprint.rotated(orientation);
+    let angle = match orientation {
+        Orientation::North => 0.,
+        Orientation::East => -FRAC_PI_2,
+        Orientation::South => PI,
+        Orientation::West => FRAC_PI_2,
+    };
+    Transform::from_xyz(
+        (width - 1.) / 2. * configuration.cube_size,
+        0.,
+        (depth - 1.) / 2. * configuration.cube_size,
+    )
+    .with_rotation(Quat::from_rotation_y(angle))
+    .with_scale(Vec3::new(
+        unrotated.width as f32,
+        1.,
+        unrotated.depth as f32,
+    ))
+}
+
 #[cfg(test)]
 mod tests {
     use crate::common::{
@@ -0,0 +1,45 @@
+/// Xorshift: deterministic given the seed, used for every stochastic decision
+#[derive(Debug, Clone)]
+pub struct Random {
+    state: u64,
+}
+
+impl Random {
+    pub fn new(seed: u64) -> Self {
+        // Xorshift gets stuck on 0
+        Self { state: seed.max(1) }
+    }
+
+    /// A number in `[0, 1)`
+    pub fn next_f32(&mut self) -> f32 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        (self.state >> 40) as f32 / (1_u64 << 24) as f32
+    }
+
+    /// True with the given probability
+    pub fn chance(&mut self, probability: f32) -> bool {
+        probability > 0. && self.next_f32() < probability
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_same_seed_same_sequence() {
+        let mut a = Random::new(42);
+        let mut b = Random::new(42);
+        let mut c = Random::new(43);
+
+        let a: Vec<_> = (0..100).map(|_| a.next_f32()).collect();
+        let b: Vec<_> = (0..100).map(|_| b.next_f32()).collect();
+        let c: Vec<_> = (0..100).map(|_| c.next_f32()).collect();
+
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+        assert!(a.iter().all(|n| (0. ..1.).contains(n)));
+    }
+}
@@ -1,9 +1,10 @@
 use bevy::prelude::Entity;
 
 use crate::{
-    building::{HouseComponent, PlaneComponent},
-    common::position::Position,
+    building::{GardenComponent, HouseComponent, PlaneComponent},
+    common::{configuration::CONFIGURATION, enums::EducationLevel, position::Position},
     e2e_test::utils::*,
+    palatability::PalatabilityManagerResource,
 };
 
 #[test]
@@ -55,9 +56,7 @@ fn test_house_can_be_fulfilled() {
 
     let house_position = Position { x: 1, y: 2 };
     create_house_at(&mut app, &house_position);
-    create_street_at(&mut app, &Position { x: 0, y: 0 });
-    create_street_at(&mut app, &Position { x: 0, y: 1 });
-    create_street_at(&mut app, &Position { x: 0, y: 2 });
+    create_streets_between(&mut app, &Position { x: 0, y: 0 }, &Position { x: 0, y: 2 });
 
     run_till(&mut app, |app| {
         get_house_snapshot_at(app, &house_position).is_none()
@@ -97,10 +96,7 @@ fn test_office_can_be_fulfilled() {
 
     let office_position = Position { x: 1, y: 3 };
 
-    create_street_at(&mut app, &Position { x: 0, y: 0 });
-    create_street_at(&mut app, &Position { x: 0, y: 1 });
-    create_street_at(&mut app, &Position { x: 0, y: 2 });
-    create_street_at(&mut app, &Position { x: 0, y: 3 });
+    create_streets_between(&mut app, &Position { x: 0, y: 0 }, &Position { x: 0, y: 3 });
     create_house_at(&mut app, &Position { x: 1, y: 2 });
     create_office_at(&mut app, &office_position);
 
@@ -114,6 +110,44 @@ fn test_office_can_be_fulfilled() {
     });
 }
 
+#[test]
+fn test_office_hires_the_graduates() {
+    let mut configuration = CONFIGURATION;
+    configuration.buildings.office.required_education_level = EducationLevel::Low;
+    let mut app = create_app_with(configuration);
+
+    let house_position = Position { x: 1, y: 1 };
+    let office_position = Position { x: 1, y: 2 };
+    let school_position = Position { x: 1, y: 3 };
+    create_streets_between(&mut app, &Position { x: 0, y: 0 }, &Position { x: 0, y: 3 });
+    create_house_at(&mut app, &house_position);
+    create_office_at(&mut app, &office_position);
+
+    run_till(&mut app, |app| {
+        get_house_snapshot_at(app, &house_position)
+            .is_none_or(|house| house.get_current_residents() < house.get_max_residents())
+    });
+    let mut frames = 0;
+    run_till(&mut app, |_| {
+        frames += 1;
+        frames < 20
+    });
+
+    // Without a school nobody has the education required by the office
+    let office = get_office_snapshot_at(&mut app, &office_position).unwrap();
+    assert_eq!(office.get_current_workers(), &0_u32);
+
+    create_school_at(&mut app, &school_position);
+    run_till(&mut app, |app| {
+        get_snapshot_at(app, &school_position).is_none()
+    });
+
+    run_till(&mut app, |app| {
+        let office = get_office_snapshot_at(app, &office_position).unwrap();
+        office.get_current_workers() == &0_u32
+    });
+}
+
 #[test]
 fn test_position_is_already_used() {
     let mut app = create_app();
@@ -131,3 +165,28 @@ fn test_position_is_already_used() {
 
     assert_eq!(items.len(), 1);
 }
+
+#[test]
+fn test_area_batch_skips_occupied_tiles() {
+    let mut configuration = CONFIGURATION;
+    configuration.buildings.garden.common.cost = 10;
+    let mut app = create_app_with(configuration);
+
+    let house_position = Position { x: 1, y: 1 };
+    create_house_at(&mut app, &house_position);
+    get_manager_resource_mut::<PalatabilityManagerResource>(&mut app).refund(100);
+
+    // The house is in the middle of the dragged gardens
+    create_gardens_between(&mut app, &Position { x: 0, y: 1 }, &Position { x: 2, y: 1 });
+    let palatability: &mut PalatabilityManagerResource = get_manager_resource_mut(&mut app);
+    assert_eq!(palatability.treasury(), 80);
+
+    run_till(&mut app, |app| {
+        get_garden_snapshot_at(app, &Position { x: 2, y: 1 }).is_none()
+    });
+
+    let items = get_entities::<(Entity, &PlaneComponent), &GardenComponent>(&mut app);
+    let positions: Vec<_> = items.iter().map(|(_, plane)| plane.0).collect();
+    assert_eq!(positions.len(), 2);
+    assert!(!positions.contains(&house_position));
+}
@@ -0,0 +1,90 @@
+use std::fmt::Write;
+
+use bevy::prelude::App;
+
+use crate::{
+    building::PlaneComponent, common::position::Position, e2e_test::utils::*,
+    navigation::NavigatorResource, palatability::PalatabilityManagerResource,
+    power::PowerManagerResource, SimulationSpeed,
+};
+
+/// A small city grown for some days
+fn grow_city() -> App {
+    let mut app = create_app();
+    // Only the ticks of the test
+    *get_manager_resource_mut::<SimulationSpeed>(&mut app) = SimulationSpeed::Paused;
+
+    create_streets_between(&mut app, &Position { x: 0, y: 0 }, &Position { x: 0, y: 6 });
+    create_house_at(&mut app, &Position { x: 1, y: 1 });
+    create_house_at(&mut app, &Position { x: 1, y: 2 });
+    create_office_at(&mut app, &Position { x: 1, y: 3 });
+    create_biomass_power_plant_at(&mut app, &Position { x: 1, y: 4 });
+    create_garden_at(&mut app, &Position { x: 1, y: 0 });
+
+    for _ in 0..5 {
+        run(&mut app, 90);
+    }
+
+    app
+}
+
+/// Everything the managers tell about every tile.
+/// The entity ids depend on the engine: they are left out
+fn dump(app: &mut App) -> String {
+    let mut positions: Vec<Position> = get_entities::<&PlaneComponent, ()>(app)
+        .into_iter()
+        .map(|plane| plane.0)
+        .collect();
+    positions.sort();
+
+    let mut dump = String::new();
+    for position in positions {
+        let snapshot = get_snapshot_at(app, &position);
+        let power = app.world.resource::<PowerManagerResource>();
+        let (building, coverage) = match &snapshot {
+            None => (String::new(), None),
+            Some(snapshot) => {
+                let id = snapshot.get_id();
+                (
+                    format!("{snapshot:?}").replace(&format!("id: {id}, "), ""),
+                    power.get_power_coverage(id),
+                )
+            }
+        };
+        let palatability = app.world.resource::<PalatabilityManagerResource>();
+        writeln!(
+            dump,
+            "{position:?} {building} {coverage:?} {:?} {:?} {}",
+            palatability.get_palatability_values(&position),
+            palatability.get_environment(&position),
+            palatability.get_land_value(&position),
+        )
+        .unwrap();
+    }
+
+    let power = app.world.resource::<PowerManagerResource>();
+    let palatability = app.world.resource::<PalatabilityManagerResource>();
+    let navigator = app.world.resource::<NavigatorResource>();
+    writeln!(
+        dump,
+        "{} {} {} {} {:?}",
+        power.stored_energy(),
+        palatability.treasury(),
+        palatability.unemployed_inhabitants().len(),
+        palatability.total_populations(),
+        navigator.get_traffic(),
+    )
+    .unwrap();
+
+    dump
+}
+
+#[test]
+fn test_same_seed_same_city() {
+    let (mut first_app, mut second_app) = (grow_city(), grow_city());
+    let first = dump(&mut first_app);
+    let second = dump(&mut second_app);
+
+    assert!(first.contains("current_residents: 8"));
+    assert_eq!(first, second);
+}
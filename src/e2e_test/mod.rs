@@ -1,3 +1,4 @@
 pub mod building;
+pub mod determinism;
 pub mod palatability;
 pub mod utils;
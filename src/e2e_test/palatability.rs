@@ -9,9 +9,7 @@ fn test_house_creation_increment_population_and_unemployee() {
 
     let house_position = Position { x: 1, y: 2 };
     create_house_at(&mut app, &house_position);
-    create_street_at(&mut app, &Position { x: 0, y: 0 });
-    create_street_at(&mut app, &Position { x: 0, y: 1 });
-    create_street_at(&mut app, &Position { x: 0, y: 2 });
+    create_streets_between(&mut app, &Position { x: 0, y: 0 }, &Position { x: 0, y: 2 });
 
     run_till(&mut app, |app| {
         get_house_snapshot_at(app, &house_position).is_none()
@@ -29,9 +27,7 @@ fn test_office_creation_decrement_unemployee() {
 
     let house_position = Position { x: 1, y: 2 };
     create_house_at(&mut app, &house_position);
-    create_street_at(&mut app, &Position { x: 0, y: 0 });
-    create_street_at(&mut app, &Position { x: 0, y: 1 });
-    create_street_at(&mut app, &Position { x: 0, y: 2 });
+    create_streets_between(&mut app, &Position { x: 0, y: 0 }, &Position { x: 0, y: 2 });
 
     run_till(&mut app, |app| {
         get_house_snapshot_at(app, &house_position).is_none()
@@ -1,4 +1,8 @@
-use crate::{building::*, common::position::Position};
+use crate::{
+    building::*,
+    common::position::{Orientation, Position},
+    overlay::HoveredTile,
+};
 use bevy::{
     ecs::query::{ReadOnlyWorldQuery, WorldQuery},
     prelude::{Component, Entity, KeyCode, Resource},
@@ -8,7 +12,9 @@ use tracing_log::LogTracer;
 use std::sync::{Arc, atomic::AtomicBool, Mutex};
 
 use crate::{
-    common::configuration::CONFIGURATION, resources::ConfigurationResource, GameTick, MainPlugin,
+    common::configuration::{Configuration, CONFIGURATION},
+    resources::ConfigurationResource,
+    GameTick, MainPlugin,
 };
 use bevy::{
     input::ButtonState,
@@ -21,17 +27,63 @@ pub fn create_house_at(app: &mut App, position: &Position) {
 }
 
 pub fn create_street_at(app: &mut App, position: &Position) {
-    create_building_at(app, position, KeyCode::S);
+    create_streets_between(app, position, position);
+}
+
+/// Drag the streets from `from` to `to`
+pub fn create_streets_between(app: &mut App, from: &Position, to: &Position) {
+    drag_between(app, KeyCode::S, from, to);
+}
+
+/// Press on `from` and release on `to`, in the edit mode of the key
+fn drag_between(app: &mut App, code: KeyCode, from: &Position, to: &Position) {
+    use bevy::{
+        ecs::event::Events,
+        input::mouse::{MouseButton, MouseButtonInput},
+    };
+
+    release_keyboard_key(app, code);
+    run(app, 1);
+    let plane_entity = get_plane_at(app, from);
+    select_plane(app, &plane_entity);
+    run(app, 1);
+
+    get_manager_resource_mut::<HoveredTile>(app).position = Some(*to);
+    let world = &mut app.world;
+    let mut mouse_button_input = world
+        .get_resource_mut::<Events<MouseButtonInput>>()
+        .unwrap();
+    mouse_button_input.send(MouseButtonInput {
+        button: MouseButton::Left,
+        state: ButtonState::Released,
+    });
+    run(app, 1);
 }
 
 pub fn create_office_at(app: &mut App, position: &Position) {
     create_building_at(app, position, KeyCode::O);
 }
+
+pub fn create_school_at(app: &mut App, position: &Position) {
+    create_building_at(app, position, KeyCode::E);
+}
+
+pub fn create_biomass_power_plant_at(app: &mut App, position: &Position) {
+    create_building_at(app, position, KeyCode::B);
+}
+/// The gardens are dragged: a single tile area
 pub fn create_garden_at(app: &mut App, position: &Position) {
-    create_building_at(app, position, KeyCode::G);
+    drag_between(app, KeyCode::G, position, position);
+}
+
+/// Drag the gardens over the area from `from` to `to`
+pub fn create_gardens_between(app: &mut App, from: &Position, to: &Position) {
+    drag_between(app, KeyCode::G, from, to);
 }
 
+/// The streets of the tests are on the west of the buildings
 fn create_building_at(app: &mut App, position: &Position, code: KeyCode) {
+    **get_manager_resource_mut::<PlacementOrientationResource>(app) = Orientation::West;
     release_keyboard_key(app, code);
     run(app, 1);
     let plane_entity = get_plane_at(app, position);
@@ -97,7 +149,7 @@ pub fn get_garden_snapshot_at(app: &mut App, position: &Position) -> Option<Gard
         ),
     }
 }
-fn get_snapshot_at(app: &mut App, position: &Position) -> Option<BuildingSnapshot> {
+pub fn get_snapshot_at(app: &mut App, position: &Position) -> Option<BuildingSnapshot> {
     let plane_entity = get_plane_at(app, position);
     let building_manager = app.world.get_resource::<BuildingManagerResource>().unwrap();
     building_manager
@@ -154,7 +206,7 @@ where
         let world = &mut app.world;
         let mut game_tick = world.get_resource_mut::<Events<GameTick>>().unwrap();
 
-        game_tick.send(GameTick(0));
+        game_tick.send(GameTick(1));
         app.update();
 
         if !f(app) {
@@ -190,11 +242,26 @@ pub fn select_plane(app: &mut App, entity: &Entity) {
 static LOG_TRACER_INITIALIZED: Mutex<bool> = Mutex::new(false);
 
 pub fn create_app() -> App {
+    create_app_with(CONFIGURATION)
+}
+
+/// The app of a scenario with its own rules
+pub fn create_app_with(configuration: Configuration) -> App {
     use bevy::{
-        asset::AssetPlugin, core::CorePlugin, core_pipeline::CorePipelinePlugin,
-        hierarchy::HierarchyPlugin, input::InputPlugin, pbr::PbrPlugin, render::RenderPlugin,
-        scene::ScenePlugin, sprite::SpritePlugin, text::TextPlugin, transform::TransformPlugin,
-        ui::UiPlugin, utils::tracing::subscriber::set_global_default, window::WindowPlugin,
+        asset::AssetPlugin,
+        core::CorePlugin,
+        core_pipeline::CorePipelinePlugin,
+        hierarchy::HierarchyPlugin,
+        input::InputPlugin,
+        pbr::PbrPlugin,
+        render::{settings::WgpuSettings, RenderPlugin},
+        scene::ScenePlugin,
+        sprite::SpritePlugin,
+        text::TextPlugin,
+        transform::TransformPlugin,
+        ui::UiPlugin,
+        utils::tracing::subscriber::set_global_default,
+        window::WindowPlugin,
     };
     use tracing_subscriber::{prelude::*, registry::Registry, EnvFilter};
 
@@ -218,6 +285,12 @@ pub fn create_app() -> App {
     app.world.clear_entities();
     app.world.clear_trackers();
 
+    // As the headless replay: nothing is drawn
+    app.insert_resource(WgpuSettings {
+        backends: None,
+        ..default()
+    });
+
     app.add_plugin(CorePlugin::default())
         .add_plugin(TimePlugin::default())
         .add_plugin(TransformPlugin::default())
@@ -237,7 +310,7 @@ pub fn create_app() -> App {
         .add_plugin(PbrPlugin::default())
         .add_plugin(SpritePlugin::default());
 
-    app.insert_resource(ConfigurationResource(Arc::new(CONFIGURATION)));
+    app.insert_resource(ConfigurationResource(Arc::new(configuration)));
 
     app.add_plugin(MainPlugin);
 
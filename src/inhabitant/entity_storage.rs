@@ -1,17 +1,30 @@
-use std::collections::HashMap;
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, BTreeSet},
+};
 
-use bevy::utils::HashSet;
-use tracing::info;
+use tracing::{debug, info};
 
-use crate::common::{enums::EducationLevel, position::Position, EntityId};
+use crate::common::{
+    configuration::{AssignmentConfiguration, PopulationConfiguration},
+    enums::EducationLevel,
+    position::Position,
+    EntityId,
+};
 
-use super::inhabitant_entity::Inhabitant;
+use super::{
+    happiness::HappinessFactors,
+    inhabitant_entity::{Inhabitant, LifeStage},
+    manager::LeaveReason,
+};
 
 #[derive(Debug)]
 pub struct BuildingNeedToBeFulfilled {
     building_entity_id: EntityId,
     building_position: Position,
     remain: u32,
+    /// Only for offices
+    required_education_level: EducationLevel,
 }
 
 impl BuildingNeedToBeFulfilled {
@@ -20,29 +33,82 @@ impl BuildingNeedToBeFulfilled {
             building_entity_id,
             building_position,
             remain,
+            required_education_level: EducationLevel::None,
         }
     }
+
+    pub fn requiring(mut self, required_education_level: EducationLevel) -> Self {
+        self.required_education_level = required_education_level;
+        self
+    }
+}
+
+/// An inhabitant reached the next education level
+#[derive(Debug)]
+pub struct Graduation {
+    pub inhabitant_entity_id: EntityId,
+    pub school_entity_id: EntityId,
+    pub education_level: EducationLevel,
+}
+
+/// A worker reached the retirement age, leaving the work place
+#[derive(Debug)]
+pub struct Retirement {
+    pub inhabitant_entity_id: EntityId,
+    pub work_place_entity_id: EntityId,
+}
+
+/// The inhabitants with a home, by age bracket
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PopulationStatistics {
+    pub children: u32,
+    pub adults: u32,
+    pub retirees: u32,
 }
 
 #[derive(Default, Debug)]
 pub struct EntityStorage {
-    inhabitants: HashMap<EntityId, Inhabitant>,
+    inhabitants: BTreeMap<EntityId, Inhabitant>,
 
-    /// Inhabitants that are waiting for being introducing the the game
-    inhabitants_need_to_be_introduced: HashSet<EntityId>,
+    /// The members of the households, by household id
+    households: BTreeMap<EntityId, Vec<EntityId>>,
+    /// Households that are waiting for being introducing the the game
+    households_need_to_be_introduced: BTreeSet<EntityId>,
     /// Houses that need to be fulfilled with inhabitants
-    houses_needs_to_be_fulfilled: HashMap<EntityId, BuildingNeedToBeFulfilled>,
+    houses_needs_to_be_fulfilled: BTreeMap<EntityId, BuildingNeedToBeFulfilled>,
     /// Offices that need to be fulfilled with workers
-    offices_needs_to_be_fulfilled: HashMap<EntityId, BuildingNeedToBeFulfilled>,
+    offices_needs_to_be_fulfilled: BTreeMap<EntityId, BuildingNeedToBeFulfilled>,
     /// Inhabitants that are waiting for a job
-    inhabitants_need_to_work: HashSet<EntityId>,
+    inhabitants_need_to_work: BTreeSet<EntityId>,
+    /// Schools that need to be fulfilled with students
+    schools_needs_to_be_fulfilled: BTreeMap<EntityId, BuildingNeedToBeFulfilled>,
+    /// Inhabitants that would like to leave the city
+    inhabitants_want_to_leave: BTreeMap<EntityId, LeaveReason>,
 }
 
 impl EntityStorage {
+    /// An household of a single inhabitant
+    #[cfg(test)]
     pub fn introduce_inhabitant(&mut self, inhabitant: Inhabitant) {
-        let id = *inhabitant.get_id();
-        self.inhabitants_need_to_be_introduced.insert(id);
-        self.inhabitants.entry(id).or_insert(inhabitant);
+        self.introduce_household(vec![inhabitant]);
+    }
+
+    /// The members move together: the first one gives the id to the household
+    pub fn introduce_household(&mut self, members: Vec<Inhabitant>) {
+        let household_id = match members.first() {
+            None => return,
+            Some(first) => *first.get_id(),
+        };
+
+        let mut ids = Vec::with_capacity(members.len());
+        for mut inhabitant in members {
+            let id = *inhabitant.get_id();
+            inhabitant.join_household(household_id);
+            self.inhabitants.entry(id).or_insert(inhabitant);
+            ids.push(id);
+        }
+        self.households.insert(household_id, ids);
+        self.households_need_to_be_introduced.insert(household_id);
     }
 
     pub fn register_house(&mut self, house_to_be_fulfilled: BuildingNeedToBeFulfilled) {
@@ -52,6 +118,14 @@ impl EntityStorage {
         entry.or_insert(house_to_be_fulfilled);
     }
 
+    /// The house hosts more residents
+    pub fn increase_house_capacity(&mut self, house_to_be_fulfilled: BuildingNeedToBeFulfilled) {
+        self.houses_needs_to_be_fulfilled
+            .entry(house_to_be_fulfilled.building_entity_id)
+            .and_modify(|house| house.remain += house_to_be_fulfilled.remain)
+            .or_insert(house_to_be_fulfilled);
+    }
+
     pub fn register_office(&mut self, office_to_be_fulfilled: BuildingNeedToBeFulfilled) {
         let entry = self
             .offices_needs_to_be_fulfilled
@@ -59,24 +133,52 @@ impl EntityStorage {
         entry.or_insert(office_to_be_fulfilled);
     }
 
+    pub fn register_school(&mut self, school_to_be_fulfilled: BuildingNeedToBeFulfilled) {
+        let entry = self
+            .schools_needs_to_be_fulfilled
+            .entry(school_to_be_fulfilled.building_entity_id);
+        entry.or_insert(school_to_be_fulfilled);
+    }
+
     pub fn register_unemployee(&mut self, id: EntityId) {
-        debug_assert!(self.inhabitants[&id].get_work_place_id().is_none());
+        let inhabitant = match self.inhabitants.get(&id) {
+            // Already left the city
+            None => return,
+            Some(inhabitant) => inhabitant,
+        };
+        if inhabitant.get_work_place_id().is_some() {
+            return;
+        }
+        // Not forgotten: the graduates and the new adults look for a job on their own
+        if inhabitant.get_school().is_some() || *inhabitant.get_life_stage() != LifeStage::Adult {
+            debug!("inhabitant {} looks for a job later", id);
+            return;
+        }
         self.inhabitants_need_to_work.insert(id);
     }
 
-    pub fn found_home_for_inhabitant(
+    /// Return the members of the household, moving into the house
+    pub fn found_home_for_household(
         &mut self,
-        inhabitant_id: &EntityId,
+        household_id: &EntityId,
         house_id: EntityId,
         house_position: Position,
-    ) -> &Inhabitant {
-        info!("Found home for {}", inhabitant_id);
-        self.inhabitants
-            .get_mut(inhabitant_id)
-            .unwrap()
-            .home_found(house_id, house_position);
+    ) -> Vec<EntityId> {
+        info!("Found home for household {}", household_id);
+        self.households_need_to_be_introduced.remove(household_id);
 
-        self.inhabitants.get(inhabitant_id).unwrap()
+        let members = match self.households.get(household_id) {
+            None => return vec![],
+            Some(members) => members.clone(),
+        };
+        for member in &members {
+            self.inhabitants
+                .get_mut(member)
+                .unwrap()
+                .home_found(house_id, house_position);
+        }
+
+        members
     }
 
     pub fn found_job_for_unemployee(
@@ -92,131 +194,547 @@ impl EntityStorage {
             .work_place_found(office_id, office_position);
     }
 
-    pub fn get_inhabitant_house_assignment(&mut self) -> Vec<AssignmentResult> {
-        if self.houses_needs_to_be_fulfilled.is_empty()
-            || self.inhabitants_need_to_be_introduced.is_empty()
-        {
-            return vec![];
+    pub fn found_school_for_inhabitant(
+        &mut self,
+        inhabitant_id: &EntityId,
+        school_id: EntityId,
+        school_position: Position,
+    ) {
+        info!("Found school for {}", inhabitant_id);
+        self.inhabitants
+            .get_mut(inhabitant_id)
+            .unwrap()
+            .school_found(school_id, school_position);
+    }
+
+    /// The students study for a tick: the graduates leave the school and look for a job
+    pub fn study(&mut self, ticks_for_level: u32) -> Vec<Graduation> {
+        let mut graduations = vec![];
+
+        for inhabitant in self.inhabitants.values_mut() {
+            let school = match inhabitant.study(ticks_for_level) {
+                None => continue,
+                Some(school) => school,
+            };
+            free_spot(
+                &mut self.schools_needs_to_be_fulfilled,
+                *school.get_school_id(),
+                *school.get_school_position(),
+            );
+            if *inhabitant.get_life_stage() == LifeStage::Adult {
+                self.inhabitants_need_to_work.insert(*inhabitant.get_id());
+            }
+
+            graduations.push(Graduation {
+                inhabitant_entity_id: *inhabitant.get_id(),
+                school_entity_id: *school.get_school_id(),
+                education_level: *inhabitant.get_education_level(),
+            });
+        }
+
+        graduations
+    }
+
+    /// A year passes for everybody: the children become adults looking for a job,
+    /// the workers retire, and the old inhabitants with a home could die.
+    /// Return the retirements
+    pub fn grow_old(
+        &mut self,
+        configuration: &PopulationConfiguration,
+        mut dies: impl FnMut(&Inhabitant) -> bool,
+    ) -> Vec<Retirement> {
+        let mut retirements = vec![];
+
+        for inhabitant in self.inhabitants.values_mut() {
+            let id = *inhabitant.get_id();
+
+            match inhabitant.grow_old(configuration) {
+                Some(LifeStage::Adult)
+                    if inhabitant.get_home().is_some() && inhabitant.get_school().is_none() =>
+                {
+                    self.inhabitants_need_to_work.insert(id);
+                }
+                Some(LifeStage::Retired) => {
+                    self.inhabitants_need_to_work.remove(&id);
+                    if let Some(work_place) = inhabitant.retire() {
+                        free_spot(
+                            &mut self.offices_needs_to_be_fulfilled,
+                            *work_place.get_work_place_id(),
+                            *work_place.get_work_place_position(),
+                        );
+                        retirements.push(Retirement {
+                            inhabitant_entity_id: id,
+                            work_place_entity_id: *work_place.get_work_place_id(),
+                        });
+                    }
+                }
+                _ => {}
+            }
+
+            if inhabitant.get_home().is_some() && dies(inhabitant) {
+                self.inhabitants_want_to_leave.insert(id, LeaveReason::Died);
+            }
+        }
+
+        retirements
+    }
+
+    /// The households with at least two adults, in an house with a free spot,
+    /// where a child can be born: one for every house.
+    /// The spots go to the waiting households first, if they fit
+    pub fn get_households_for_births(&self) -> Vec<EntityId> {
+        let smallest_waiting = self
+            .households_need_to_be_introduced
+            .iter()
+            .map(|household_id| self.households[household_id].len() as u32)
+            .min()
+            .unwrap_or(u32::MAX);
+
+        let mut houses = BTreeSet::new();
+        let mut households = vec![];
+        for (household_id, members) in &self.households {
+            let adults = members
+                .iter()
+                .filter(|member| *self.inhabitants[*member].get_life_stage() == LifeStage::Adult)
+                .count();
+            if adults < 2 {
+                continue;
+            }
+
+            let house_id = match self.inhabitants[&members[0]].get_home() {
+                None => continue,
+                Some(home) => *home.get_house_id(),
+            };
+            let remain = self
+                .houses_needs_to_be_fulfilled
+                .get(&house_id)
+                .map_or(0, |house| house.remain);
+            if remain == 0 || remain >= smallest_waiting || !houses.insert(house_id) {
+                continue;
+            }
+
+            households.push(*household_id);
         }
 
-        // We would like to find an house to fulfill: so find a "empty" house and
-        // remove the number of inhabitants we would like to spawn
+        households
+    }
+
+    /// The child takes a free spot in the house of the household, returned
+    pub fn give_birth(&mut self, mut child: Inhabitant, household_id: &EntityId) -> EntityId {
+        let members = self.households.get_mut(household_id).unwrap();
+        let home = self.inhabitants[&members[0]].get_home().as_ref().unwrap();
+        let house_id = *home.get_house_id();
+        let house_position = *home.get_house_position();
 
-        // TODO: choose the house better, not "randomly"
-        // Currently keys().next() is not a good algorithm: we can be smarter here
-        let building_entity_id = *self.houses_needs_to_be_fulfilled.keys().next().unwrap();
-        let building_needed_to_be_fulfilled = self
+        let house = self
             .houses_needs_to_be_fulfilled
-            .get_mut(&building_entity_id)
-            .unwrap();
-        if building_needed_to_be_fulfilled.remain == 0 {
-            self.houses_needs_to_be_fulfilled
-                .remove(&building_entity_id);
-            return self.get_inhabitant_house_assignment();
+            .get_mut(&house_id)
+            .expect("the house has no free spot");
+        house.remain -= 1;
+
+        info!("{} born in {}", child.get_id(), house_id);
+        child.join_household(*household_id);
+        child.home_found(house_id, house_position);
+        members.push(*child.get_id());
+        self.inhabitants.insert(*child.get_id(), child);
+
+        house_id
+    }
+
+    pub fn population_statistics(&self) -> PopulationStatistics {
+        let mut statistics = PopulationStatistics::default();
+        for inhabitant in self.inhabitants.values() {
+            if inhabitant.get_home().is_none() {
+                continue;
+            }
+            match inhabitant.get_life_stage() {
+                LifeStage::Child => statistics.children += 1,
+                LifeStage::Adult => statistics.adults += 1,
+                LifeStage::Retired => statistics.retirees += 1,
+            }
         }
+        statistics
+    }
+
+    /// Recompute the happiness of the inhabitants with a home.
+    /// Return the average happiness, `None` if nobody lives in the city
+    pub fn update_happiness(
+        &mut self,
+        factors_of: impl Fn(&Inhabitant) -> Option<HappinessFactors>,
+        happiness_of: impl Fn(&HappinessFactors) -> u8,
+    ) -> Option<u8> {
+        let mut total: u32 = 0;
+        let mut count: u32 = 0;
+        for inhabitant in self.inhabitants.values_mut() {
+            if let Some(factors) = factors_of(inhabitant) {
+                let happiness = happiness_of(&factors);
+                inhabitant.update_happiness(happiness, &factors);
+                total += u32::from(happiness);
+                count += 1;
+            }
+        }
+
+        (count > 0).then(|| (total / count) as u8)
+    }
 
-        let from = *self
-            .inhabitants_need_to_be_introduced
+    /// Mark the inhabitants with a home that decide to leave the city
+    pub fn find_inhabitants_want_to_leave(
+        &mut self,
+        mut emigration_reason_of: impl FnMut(&Inhabitant) -> Option<LeaveReason>,
+    ) {
+        let leaving = self
+            .inhabitants
+            .values()
+            .filter(|inhabitant| inhabitant.get_home().is_some())
+            .filter_map(|inhabitant| {
+                Some((*inhabitant.get_id(), emigration_reason_of(inhabitant)?))
+            });
+        self.inhabitants_want_to_leave.extend(leaving);
+    }
+
+    /// Remove the inhabitants that want to leave, freeing their spots
+    /// in the houses, in the offices and in the schools
+    pub fn remove_emigrants(&mut self) -> Vec<(Inhabitant, LeaveReason)> {
+        let mut emigrants = vec![];
+
+        let leaving = std::mem::take(&mut self.inhabitants_want_to_leave);
+        for (id, reason) in leaving {
+            if let Some(inhabitant) = self.remove_inhabitant(&id) {
+                emigrants.push((inhabitant, reason));
+            }
+        }
+
+        emigrants
+    }
+
+    /// The building is demolished: the residents leave the city, returned,
+    /// the workers and the students look for another place
+    pub fn demolish(&mut self, building_id: &EntityId) -> Vec<Inhabitant> {
+        let mut residents = vec![];
+        let mut displaced = vec![];
+        for inhabitant in self.inhabitants.values_mut() {
+            let id = *inhabitant.get_id();
+            if inhabitant
+                .get_home()
+                .as_ref()
+                .is_some_and(|home| home.get_house_id() == building_id)
+            {
+                residents.push(id);
+            }
+            if inhabitant
+                .get_work_place_id()
+                .as_ref()
+                .is_some_and(|work_place| work_place.get_work_place_id() == building_id)
+            {
+                inhabitant.lose_job();
+                displaced.push(id);
+            }
+            if inhabitant
+                .get_school()
+                .as_ref()
+                .is_some_and(|school| school.get_school_id() == building_id)
+            {
+                inhabitant.leave_school();
+                displaced.push(id);
+            }
+        }
+
+        let residents = residents
             .iter()
-            .next()
-            .unwrap();
-        self.inhabitants_need_to_be_introduced.remove(&from);
+            .filter_map(|id| self.remove_inhabitant(id))
+            .collect();
+        for id in displaced {
+            self.register_unemployee(id);
+        }
 
-        // TODO: only one?
-        building_needed_to_be_fulfilled.remain -= 1;
+        // After the residents, who free their spots
+        self.houses_needs_to_be_fulfilled.remove(building_id);
+        self.offices_needs_to_be_fulfilled.remove(building_id);
+        self.schools_needs_to_be_fulfilled.remove(building_id);
 
-        vec![AssignmentResult {
-            from,
-            from_position: Position { x: 0, y: 0 },
-            to: building_needed_to_be_fulfilled.building_entity_id,
-            to_position: building_needed_to_be_fulfilled.building_position,
-            count: 1,
-            assignment_type: AssignmentType::InhabitantHouse,
-        }]
+        residents
     }
 
-    pub fn get_inhabitant_job_assignment(&mut self) -> Vec<AssignmentResult> {
-        if self.offices_needs_to_be_fulfilled.is_empty() || self.inhabitants_need_to_work.is_empty()
-        {
-            return vec![];
+    /// Remove the inhabitant, freeing the spots in the house, in the office and in the school
+    fn remove_inhabitant(&mut self, id: &EntityId) -> Option<Inhabitant> {
+        let inhabitant = self.inhabitants.remove(id)?;
+        self.leave_household(&inhabitant);
+        self.inhabitants_need_to_work.remove(id);
+        self.inhabitants_want_to_leave.remove(id);
+
+        if let Some(home) = inhabitant.get_home() {
+            free_spot(
+                &mut self.houses_needs_to_be_fulfilled,
+                *home.get_house_id(),
+                *home.get_house_position(),
+            );
+        }
+        if let Some(work_place) = inhabitant.get_work_place_id() {
+            free_spot(
+                &mut self.offices_needs_to_be_fulfilled,
+                *work_place.get_work_place_id(),
+                *work_place.get_work_place_position(),
+            );
+        }
+        if let Some(school) = inhabitant.get_school() {
+            free_spot(
+                &mut self.schools_needs_to_be_fulfilled,
+                *school.get_school_id(),
+                *school.get_school_position(),
+            );
         }
 
-        // We would like to find an office to fulfill: so find a "empty" office and
-        // remove the number of inhabitants we would like to assign
-        // NB: this method is really too equal of "get_inhabitant_house_assignment" method
+        Some(inhabitant)
+    }
 
-        // TODO: choose the office better, not "randomly"
-        // Currently keys().next() is not a good algorithm: we can be smarter here
-        let building_entity = *self.offices_needs_to_be_fulfilled.keys().next().unwrap();
-        let building_needed_to_be_fulfilled = self
-            .offices_needs_to_be_fulfilled
-            .get_mut(&building_entity)
-            .unwrap();
-        // Because the algorithm is not so "stable", we probably find some buildings
-        // without remains.
-        // TODO: avoid that
-        if building_needed_to_be_fulfilled.remain == 0 {
-            self.offices_needs_to_be_fulfilled.remove(&building_entity);
-            return self.get_inhabitant_job_assignment();
+    /// Move a whole household into an house with enough free spots
+    /// Move the waiting households into the most palatable houses with enough free spots,
+    /// reachable from the origin
+    pub fn get_inhabitant_house_assignment(
+        &mut self,
+        configuration: &AssignmentConfiguration,
+        mut distance: impl FnMut(&Position, &Position) -> Option<u32>,
+        house_palatability: impl Fn(&Position) -> i32,
+    ) -> Vec<AssignmentResult> {
+        // TODO: an household bigger than the free spots of every house waits forever
+        // TODO: for the time being the households come from the origin
+        let origin = Position { x: 0, y: 0 };
+
+        let mut households: Vec<_> = self.households_need_to_be_introduced.iter().collect();
+        households.sort_unstable();
+        households.truncate(configuration.batch_size);
+
+        let mut candidates = vec![];
+        for household_id in households {
+            let size = self.households[household_id].len() as u32;
+            for house in self.houses_needs_to_be_fulfilled.values() {
+                if house.remain < size || distance(&origin, &house.building_position).is_none() {
+                    continue;
+                }
+                candidates.push(Candidate {
+                    from: *household_id,
+                    from_position: origin,
+                    to: house.building_entity_id,
+                    count: size,
+                    score: configuration.palatability_weight
+                        * i64::from(house_palatability(&house.building_position)),
+                });
+            }
+        }
+
+        let assignments = match_greedily(
+            candidates,
+            &mut self.houses_needs_to_be_fulfilled,
+            AssignmentType::InhabitantHouse,
+        );
+        for assignment in &assignments {
+            self.households_need_to_be_introduced
+                .remove(&assignment.from);
         }
 
-        let building_required_education_level = &EducationLevel::None;
+        assignments
+    }
 
-        let from = self.inhabitants_need_to_work.iter().find(|i| {
-            let inhabitant_education_level: &EducationLevel =
-                self.inhabitants[*i].get_education_level();
-            inhabitant_education_level >= building_required_education_level
-        });
+    /// Match the unemployed with the qualified offices, preferring short commutes
+    /// and offices with many vacancies
+    pub fn get_inhabitant_job_assignment(
+        &mut self,
+        configuration: &AssignmentConfiguration,
+        mut distance: impl FnMut(&Position, &Position) -> Option<u32>,
+    ) -> Vec<AssignmentResult> {
+        let mut workers: Vec<_> = self.inhabitants_need_to_work.iter().collect();
+        workers.sort_unstable();
 
-        let from = match from {
+        let mut candidates = vec![];
+        // Only the workers with a reachable office for their education fill the batch
+        let mut batch = 0;
+        for worker in workers {
+            if batch == configuration.batch_size {
+                break;
+            }
+            let inhabitant = &self.inhabitants[worker];
+            let house_position = match inhabitant.get_home() {
+                None => continue,
+                Some(home) => home.get_house_position(),
+            };
+
+            // The offices are kept also without remains: they remember the required education
+            for office in self.offices_needs_to_be_fulfilled.values() {
+                if office.remain == 0
+                    || inhabitant.get_education_level() < &office.required_education_level
+                {
+                    continue;
+                }
+                let commute = match distance(house_position, &office.building_position) {
+                    None => continue,
+                    Some(commute) => commute,
+                };
+                candidates.push(Candidate {
+                    from: *worker,
+                    from_position: *house_position,
+                    to: office.building_entity_id,
+                    count: 1,
+                    score: configuration.vacancy_weight * i64::from(office.remain)
+                        - configuration.commute_weight * i64::from(commute),
+                });
+            }
+            if candidates
+                .last()
+                .is_some_and(|candidate| candidate.from == *worker)
+            {
+                batch += 1;
+            }
+        }
+
+        let assignments = match_greedily(
+            candidates,
+            &mut self.offices_needs_to_be_fulfilled,
+            AssignmentType::InhabitantOffice,
+        );
+        for assignment in &assignments {
+            self.inhabitants_need_to_work.remove(&assignment.from);
+        }
+
+        assignments
+    }
+
+    /// Enrol an inhabitant without a job in a school with a free spot
+    pub fn get_inhabitant_school_assignment(&mut self) -> Vec<AssignmentResult> {
+        let school = self
+            .schools_needs_to_be_fulfilled
+            .values_mut()
+            .find(|school| school.remain > 0);
+        let school = match school {
             None => return vec![],
-            Some(from) => *from,
+            Some(school) => school,
         };
-        self.inhabitants_need_to_work.remove(&from);
 
-        // TODO: only one?
-        building_needed_to_be_fulfilled.remain -= 1;
+        // The children first
+        let student = self
+            .inhabitants
+            .values()
+            .filter(|inhabitant| inhabitant.can_study())
+            .min_by_key(|inhabitant| *inhabitant.get_life_stage() != LifeStage::Child);
+        let student = match student {
+            None => return vec![],
+            Some(student) => student,
+        };
 
-        let house_position = *self.inhabitants[&from]
-            .get_home()
-            .as_ref()
-            .unwrap()
-            .get_house_position();
+        let from = *student.get_id();
+        let house_position = *student.get_home().as_ref().unwrap().get_house_position();
+
+        // TODO: only one?
+        school.remain -= 1;
+        // Students don't look for a job
+        self.inhabitants_need_to_work.remove(&from);
 
         vec![AssignmentResult {
             from,
             from_position: house_position,
-            to: building_needed_to_be_fulfilled.building_entity_id,
-            to_position: building_needed_to_be_fulfilled.building_position,
+            to: school.building_entity_id,
+            to_position: school.building_position,
             count: 1,
-            assignment_type: AssignmentType::InhabitantOffice,
+            assignment_type: AssignmentType::InhabitantSchool,
         }]
     }
 
+    fn leave_household(&mut self, inhabitant: &Inhabitant) {
+        let household_id = inhabitant.get_household_id();
+        let members = match self.households.get_mut(household_id) {
+            None => return,
+            Some(members) => members,
+        };
+        members.retain(|member| member != inhabitant.get_id());
+        if members.is_empty() {
+            self.households.remove(household_id);
+            self.households_need_to_be_introduced.remove(household_id);
+        }
+    }
+
     pub fn resign_assign_result(&mut self, assign_result: AssignmentResult) {
-        let inhabitants_list: &mut HashSet<EntityId> = match assign_result.assignment_type {
-            AssignmentType::InhabitantHouse => &mut self.inhabitants_need_to_be_introduced,
-            AssignmentType::InhabitantOffice => &mut self.inhabitants_need_to_work,
+        match assign_result.assignment_type {
+            AssignmentType::InhabitantHouse => {
+                self.households_need_to_be_introduced
+                    .insert(assign_result.from);
+            }
+            // The students come back to look for a job
+            AssignmentType::InhabitantOffice | AssignmentType::InhabitantSchool => {
+                self.register_unemployee(assign_result.from);
+            }
         };
-        inhabitants_list.insert(assign_result.from);
 
-        let building_map_to_be_fulfilled: &mut HashMap<EntityId, BuildingNeedToBeFulfilled> =
+        let building_map_to_be_fulfilled: &mut BTreeMap<EntityId, BuildingNeedToBeFulfilled> =
             match assign_result.assignment_type {
                 AssignmentType::InhabitantHouse => &mut self.houses_needs_to_be_fulfilled,
                 AssignmentType::InhabitantOffice => &mut self.offices_needs_to_be_fulfilled,
+                AssignmentType::InhabitantSchool => &mut self.schools_needs_to_be_fulfilled,
             };
 
         let building_need_to_be_fulfilled = building_map_to_be_fulfilled
             .entry(assign_result.to)
-            .or_insert(BuildingNeedToBeFulfilled {
-                building_entity_id: assign_result.to,
-                building_position: assign_result.to_position,
-                remain: 0,
+            .or_insert_with(|| {
+                BuildingNeedToBeFulfilled::new(assign_result.to, assign_result.to_position, 0)
             });
         building_need_to_be_fulfilled.remain += assign_result.count;
     }
 }
 
+/// A possible assignment, the higher the score the better
+struct Candidate {
+    from: EntityId,
+    from_position: Position,
+    to: EntityId,
+    count: u32,
+    score: i64,
+}
+
+/// The best candidates first, while the buildings have room:
+/// everybody is assigned at most once
+fn match_greedily(
+    mut candidates: Vec<Candidate>,
+    buildings: &mut BTreeMap<EntityId, BuildingNeedToBeFulfilled>,
+    assignment_type: AssignmentType,
+) -> Vec<AssignmentResult> {
+    candidates
+        .sort_unstable_by_key(|candidate| (Reverse(candidate.score), candidate.from, candidate.to));
+
+    let mut assigned = BTreeSet::new();
+    let mut assignments = vec![];
+    for candidate in candidates {
+        if assigned.contains(&candidate.from) {
+            continue;
+        }
+        let building = buildings.get_mut(&candidate.to).unwrap();
+        if building.remain < candidate.count {
+            continue;
+        }
+
+        building.remain -= candidate.count;
+        assigned.insert(candidate.from);
+        assignments.push(AssignmentResult {
+            assignment_type,
+            from: candidate.from,
+            from_position: candidate.from_position,
+            to: candidate.to,
+            to_position: building.building_position,
+            count: candidate.count,
+        });
+    }
+
+    assignments
+}
+
+fn free_spot(
+    buildings: &mut BTreeMap<EntityId, BuildingNeedToBeFulfilled>,
+    building_entity_id: EntityId,
+    building_position: Position,
+) {
+    buildings
+        .entry(building_entity_id)
+        .or_insert_with(|| BuildingNeedToBeFulfilled::new(building_entity_id, building_position, 0))
+        .remain += 1;
+}
+
 #[derive(Debug)]
 pub struct AssignmentResult {
     pub assignment_type: AssignmentType,
@@ -227,20 +745,36 @@ pub struct AssignmentResult {
     pub count: u32,
 }
 
-#[derive(Debug)]
+#[allow(clippy::enum_variant_names)]
+#[derive(Debug, Clone, Copy)]
 pub enum AssignmentType {
     InhabitantHouse,
     InhabitantOffice,
+    InhabitantSchool,
 }
 
 #[cfg(test)]
 mod tests {
     use crate::{
-        common::{enums::EducationLevel, position::Position},
-        inhabitant::inhabitant_entity::Inhabitant,
+        common::{configuration::CONFIGURATION, enums::EducationLevel, position::Position},
+        inhabitant::{inhabitant_entity::Inhabitant, manager::LeaveReason},
     };
 
-    use super::{BuildingNeedToBeFulfilled, EntityStorage};
+    use super::{AssignmentResult, BuildingNeedToBeFulfilled, EntityStorage, PopulationStatistics};
+
+    fn house_assignment(entity_storage: &mut EntityStorage) -> Vec<AssignmentResult> {
+        entity_storage.get_inhabitant_house_assignment(
+            &CONFIGURATION.assignment,
+            |from, to| Some(from.distance(to)),
+            |_| 0,
+        )
+    }
+
+    fn job_assignment(entity_storage: &mut EntityStorage) -> Vec<AssignmentResult> {
+        entity_storage.get_inhabitant_job_assignment(&CONFIGURATION.assignment, |from, to| {
+            Some(from.distance(to))
+        })
+    }
 
     #[test]
     fn test_consume_assignments() {
@@ -252,12 +786,13 @@ mod tests {
             building_entity_id: house,
             building_position: house_position,
             remain: 1,
+            required_education_level: EducationLevel::None,
         });
 
         let inhabitant = 1_u64;
         entity_storage.introduce_inhabitant(Inhabitant::new(inhabitant, EducationLevel::None));
 
-        let mut assignments = entity_storage.get_inhabitant_house_assignment();
+        let mut assignments = house_assignment(&mut entity_storage);
         assert_eq!(assignments.len(), 1);
 
         let assignment = assignments.pop().unwrap();
@@ -266,10 +801,10 @@ mod tests {
 
         entity_storage.resign_assign_result(assignment);
 
-        let assignments = entity_storage.get_inhabitant_house_assignment();
+        let assignments = house_assignment(&mut entity_storage);
         assert_eq!(assignments.len(), 1);
 
-        let assignments = entity_storage.get_inhabitant_house_assignment();
+        let assignments = house_assignment(&mut entity_storage);
         assert_eq!(assignments.len(), 0);
     }
 
@@ -283,6 +818,7 @@ mod tests {
             building_entity_id: house,
             building_position: house_position,
             remain: 5,
+            required_education_level: EducationLevel::None,
         });
 
         let inhabitant1 = 1_u64;
@@ -292,8 +828,9 @@ mod tests {
         let inhabitant3 = 3_u64;
         entity_storage.introduce_inhabitant(Inhabitant::new(inhabitant3, EducationLevel::None));
 
-        let mut assignments = entity_storage.get_inhabitant_house_assignment();
-        assert_eq!(assignments.len(), 1);
+        // All together
+        let mut assignments = house_assignment(&mut entity_storage);
+        assert_eq!(assignments.len(), 3);
 
         let assignment = assignments.pop().unwrap();
         assert!(vec![inhabitant1, inhabitant2, inhabitant3].contains(&assignment.from));
@@ -301,17 +838,11 @@ mod tests {
 
         entity_storage.resign_assign_result(assignment);
 
-        let assignments = entity_storage.get_inhabitant_house_assignment();
-        assert_eq!(assignments.len(), 1);
-
-        let assignments = entity_storage.get_inhabitant_house_assignment();
-        assert_eq!(assignments.len(), 1);
-
-        let assignments = entity_storage.get_inhabitant_house_assignment();
+        let assignments = house_assignment(&mut entity_storage);
         assert_eq!(assignments.len(), 1);
 
         // inhabitants are missing
-        let assignments = entity_storage.get_inhabitant_house_assignment();
+        let assignments = house_assignment(&mut entity_storage);
         assert_eq!(assignments.len(), 0);
 
         let inhabitant4 = 4_u64;
@@ -321,13 +852,11 @@ mod tests {
         let inhabitant6 = 6_u64;
         entity_storage.introduce_inhabitant(Inhabitant::new(inhabitant6, EducationLevel::None));
 
-        let assignments = entity_storage.get_inhabitant_house_assignment();
-        assert_eq!(assignments.len(), 1);
-        let assignments = entity_storage.get_inhabitant_house_assignment();
-        assert_eq!(assignments.len(), 1);
+        let assignments = house_assignment(&mut entity_storage);
+        assert_eq!(assignments.len(), 2);
 
         // houses are missing
-        let assignments = entity_storage.get_inhabitant_house_assignment();
+        let assignments = house_assignment(&mut entity_storage);
         assert_eq!(assignments.len(), 0);
     }
 
@@ -341,15 +870,514 @@ mod tests {
             building_entity_id: house,
             building_position: house_position,
             remain: 0,
+            required_education_level: EducationLevel::None,
         });
 
         let inhabitant = 1_u64;
         entity_storage.introduce_inhabitant(Inhabitant::new(inhabitant, EducationLevel::None));
 
-        let assignments = entity_storage.get_inhabitant_house_assignment();
+        let assignments = house_assignment(&mut entity_storage);
         assert_eq!(assignments.len(), 0);
 
-        let assignments = entity_storage.get_inhabitant_house_assignment();
+        let assignments = house_assignment(&mut entity_storage);
         assert_eq!(assignments.len(), 0);
     }
+
+    #[test]
+    fn test_emigrants_free_their_spots() {
+        let mut entity_storage = EntityStorage::default();
+
+        let house = 0_u64;
+        let house_position = Position { x: 0, y: 0 };
+        entity_storage.register_house(BuildingNeedToBeFulfilled {
+            building_entity_id: house,
+            building_position: house_position,
+            remain: 1,
+            required_education_level: EducationLevel::None,
+        });
+        let office = 1_u64;
+        let office_position = Position { x: 5, y: 0 };
+        entity_storage.register_office(BuildingNeedToBeFulfilled {
+            building_entity_id: office,
+            building_position: office_position,
+            remain: 1,
+            required_education_level: EducationLevel::None,
+        });
+
+        let inhabitant = 2_u64;
+        entity_storage.introduce_inhabitant(Inhabitant::new(inhabitant, EducationLevel::None));
+        house_assignment(&mut entity_storage);
+        entity_storage.found_home_for_household(&inhabitant, house, house_position);
+        entity_storage.register_unemployee(inhabitant);
+        job_assignment(&mut entity_storage);
+        entity_storage.found_job_for_unemployee(&inhabitant, office, office_position);
+
+        // Nobody wants to leave
+        entity_storage.find_inhabitants_want_to_leave(|_| None);
+        assert!(entity_storage.remove_emigrants().is_empty());
+
+        entity_storage.find_inhabitants_want_to_leave(|_| Some(LeaveReason::Unhappy));
+        let emigrants = entity_storage.remove_emigrants();
+        assert_eq!(emigrants.len(), 1);
+        assert_eq!(*emigrants[0].0.get_id(), inhabitant);
+        assert_eq!(emigrants[0].1, LeaveReason::Unhappy);
+        assert!(entity_storage.remove_emigrants().is_empty());
+
+        // A late registration is ignored
+        entity_storage.register_unemployee(inhabitant);
+        assert!(job_assignment(&mut entity_storage).is_empty());
+
+        // The spots can host new inhabitants
+        let newcomer = 3_u64;
+        entity_storage.introduce_inhabitant(Inhabitant::new(newcomer, EducationLevel::None));
+        let assignments = house_assignment(&mut entity_storage);
+        assert_eq!(assignments.len(), 1);
+        assert_eq!(assignments[0].to, house);
+        entity_storage.found_home_for_household(&newcomer, house, house_position);
+
+        entity_storage.register_unemployee(newcomer);
+        let assignments = job_assignment(&mut entity_storage);
+        assert_eq!(assignments.len(), 1);
+        assert_eq!(assignments[0].to, office);
+    }
+
+    #[test]
+    fn test_demolish_evicts_residents() {
+        let mut entity_storage = EntityStorage::default();
+
+        let house = 0_u64;
+        let house_position = Position { x: 0, y: 0 };
+        entity_storage.register_house(BuildingNeedToBeFulfilled::new(house, house_position, 2));
+        let office = 1_u64;
+        let office_position = Position { x: 5, y: 0 };
+        entity_storage.register_office(BuildingNeedToBeFulfilled::new(office, office_position, 1));
+
+        let resident = 2_u64;
+        entity_storage.introduce_inhabitant(Inhabitant::new(resident, EducationLevel::None));
+        house_assignment(&mut entity_storage);
+        entity_storage.found_home_for_household(&resident, house, house_position);
+        entity_storage.register_unemployee(resident);
+        job_assignment(&mut entity_storage);
+        entity_storage.found_job_for_unemployee(&resident, office, office_position);
+
+        // The worker looks for another job
+        assert!(entity_storage.demolish(&office).is_empty());
+        assert!(entity_storage.inhabitants[&resident]
+            .get_work_place_id()
+            .is_none());
+        assert!(job_assignment(&mut entity_storage).is_empty());
+
+        // The resident leaves the city, the house hosts nobody anymore
+        let evicted = entity_storage.demolish(&house);
+        assert_eq!(evicted.len(), 1);
+        assert_eq!(*evicted[0].get_id(), resident);
+        assert!(!entity_storage.inhabitants.contains_key(&resident));
+
+        let newcomer = 3_u64;
+        entity_storage.introduce_inhabitant(Inhabitant::new(newcomer, EducationLevel::None));
+        assert!(house_assignment(&mut entity_storage).is_empty());
+    }
+
+    fn inhabitant_at_home(
+        entity_storage: &mut EntityStorage,
+        inhabitant: u64,
+        education_level: EducationLevel,
+    ) {
+        let house = 100 + inhabitant;
+        let house_position = Position { x: 0, y: 0 };
+        entity_storage.register_house(BuildingNeedToBeFulfilled::new(house, house_position, 1));
+        entity_storage.introduce_inhabitant(Inhabitant::new(inhabitant, education_level));
+        entity_storage.found_home_for_household(&inhabitant, house, house_position);
+    }
+
+    #[test]
+    fn test_job_assignment_requires_education() {
+        let mut entity_storage = EntityStorage::default();
+
+        let office = 0_u64;
+        entity_storage.register_office(
+            BuildingNeedToBeFulfilled::new(office, Position { x: 5, y: 0 }, 1)
+                .requiring(EducationLevel::Medium),
+        );
+
+        let uneducated = 1_u64;
+        inhabitant_at_home(&mut entity_storage, uneducated, EducationLevel::Low);
+        entity_storage.register_unemployee(uneducated);
+        assert!(job_assignment(&mut entity_storage).is_empty());
+
+        let educated = 2_u64;
+        inhabitant_at_home(&mut entity_storage, educated, EducationLevel::High);
+        entity_storage.register_unemployee(educated);
+        let assignments = job_assignment(&mut entity_storage);
+        assert_eq!(assignments.len(), 1);
+        assert_eq!(assignments[0].from, educated);
+        assert_eq!(assignments[0].to, office);
+    }
+
+    #[test]
+    fn test_unqualified_workers_leave_room_in_the_batch() {
+        let mut entity_storage = EntityStorage::default();
+
+        let office = 0_u64;
+        entity_storage.register_office(
+            BuildingNeedToBeFulfilled::new(office, Position { x: 5, y: 0 }, 1)
+                .requiring(EducationLevel::Low),
+        );
+
+        // A whole batch ahead of the qualified worker
+        let batch_size = CONFIGURATION.assignment.batch_size as u64;
+        for uneducated in 1..=batch_size {
+            inhabitant_at_home(&mut entity_storage, uneducated, EducationLevel::None);
+            entity_storage.register_unemployee(uneducated);
+        }
+        let educated = batch_size + 1;
+        inhabitant_at_home(&mut entity_storage, educated, EducationLevel::Low);
+        entity_storage.register_unemployee(educated);
+
+        let assignments = job_assignment(&mut entity_storage);
+        assert_eq!(assignments.len(), 1);
+        assert_eq!(assignments[0].from, educated);
+    }
+
+    #[test]
+    fn test_students_graduate() {
+        let mut entity_storage = EntityStorage::default();
+        let ticks_for_level = 3;
+
+        let school = 0_u64;
+        let school_position = Position { x: 3, y: 0 };
+        entity_storage.register_school(BuildingNeedToBeFulfilled::new(school, school_position, 1));
+        let office = 1_u64;
+        entity_storage.register_office(
+            BuildingNeedToBeFulfilled::new(office, Position { x: 5, y: 0 }, 1)
+                .requiring(EducationLevel::Low),
+        );
+
+        let student = 2_u64;
+        inhabitant_at_home(&mut entity_storage, student, EducationLevel::None);
+        entity_storage.register_unemployee(student);
+        assert!(job_assignment(&mut entity_storage).is_empty());
+
+        let assignments = entity_storage.get_inhabitant_school_assignment();
+        assert_eq!(assignments.len(), 1);
+        assert_eq!(assignments[0].from, student);
+        assert_eq!(assignments[0].to, school);
+        entity_storage.found_school_for_inhabitant(&student, school, school_position);
+        // The students look for a job after graduating
+        entity_storage.register_unemployee(student);
+        assert!(job_assignment(&mut entity_storage).is_empty());
+
+        // The school is full
+        let other = 3_u64;
+        inhabitant_at_home(&mut entity_storage, other, EducationLevel::None);
+        assert!(entity_storage.get_inhabitant_school_assignment().is_empty());
+
+        for _ in 1..ticks_for_level {
+            assert!(entity_storage.study(ticks_for_level).is_empty());
+        }
+        let graduations = entity_storage.study(ticks_for_level);
+        assert_eq!(graduations.len(), 1);
+        assert_eq!(graduations[0].inhabitant_entity_id, student);
+        assert_eq!(graduations[0].school_entity_id, school);
+        assert_eq!(graduations[0].education_level, EducationLevel::Low);
+
+        // The graduate is qualified for the office
+        let assignments = job_assignment(&mut entity_storage);
+        assert_eq!(assignments.len(), 1);
+        assert_eq!(assignments[0].from, student);
+        entity_storage.found_job_for_unemployee(&student, office, assignments[0].to_position);
+
+        // The spot at school is free again
+        let assignments = entity_storage.get_inhabitant_school_assignment();
+        assert_eq!(assignments.len(), 1);
+        assert_eq!(assignments[0].from, other);
+    }
+
+    #[test]
+    fn test_workers_retire() {
+        let mut entity_storage = EntityStorage::default();
+        let configuration = &CONFIGURATION.population;
+
+        let office = 0_u64;
+        let office_position = Position { x: 5, y: 0 };
+        entity_storage.register_office(BuildingNeedToBeFulfilled::new(office, office_position, 1));
+
+        let worker = 1_u64;
+        let house = 100 + worker;
+        let house_position = Position { x: 0, y: 0 };
+        entity_storage.register_house(BuildingNeedToBeFulfilled::new(house, house_position, 1));
+        entity_storage.introduce_inhabitant(
+            Inhabitant::new(worker, EducationLevel::None)
+                .with_age(configuration.retirement_age - 1, configuration),
+        );
+        entity_storage.found_home_for_household(&worker, house, house_position);
+        entity_storage.register_unemployee(worker);
+        job_assignment(&mut entity_storage);
+        entity_storage.found_job_for_unemployee(&worker, office, office_position);
+
+        let retirements = entity_storage.grow_old(configuration, |_| false);
+        assert_eq!(retirements.len(), 1);
+        assert_eq!(retirements[0].inhabitant_entity_id, worker);
+        assert_eq!(retirements[0].work_place_entity_id, office);
+
+        // The retiree doesn't look for a job anymore, the office hires somebody else
+        entity_storage.register_unemployee(worker);
+        assert!(job_assignment(&mut entity_storage).is_empty());
+        let young = 2_u64;
+        inhabitant_at_home(&mut entity_storage, young, EducationLevel::None);
+        entity_storage.register_unemployee(young);
+        let assignments = job_assignment(&mut entity_storage);
+        assert_eq!(assignments.len(), 1);
+        assert_eq!(assignments[0].from, young);
+        assert_eq!(assignments[0].to, office);
+    }
+
+    #[test]
+    fn test_births_and_deaths() {
+        let mut entity_storage = EntityStorage::default();
+        let configuration = &CONFIGURATION.population;
+
+        let house = 0_u64;
+        let house_position = Position { x: 0, y: 0 };
+        entity_storage.register_house(BuildingNeedToBeFulfilled::new(house, house_position, 3));
+        let parents = [1_u64, 2_u64];
+        entity_storage.introduce_household(
+            parents
+                .iter()
+                .map(|parent| {
+                    Inhabitant::new(*parent, EducationLevel::None)
+                        .with_age(configuration.adult_age, configuration)
+                })
+                .collect(),
+        );
+        let assignments = house_assignment(&mut entity_storage);
+        assert_eq!(assignments[0].count, 2);
+        let household = assignments[0].from;
+        entity_storage.found_home_for_household(&household, house, house_position);
+
+        assert_eq!(entity_storage.get_households_for_births(), vec![household]);
+        let child = 3_u64;
+        assert_eq!(
+            entity_storage.give_birth(Inhabitant::newborn(child), &household),
+            house
+        );
+        assert_eq!(
+            entity_storage.population_statistics(),
+            PopulationStatistics {
+                children: 1,
+                adults: 2,
+                retirees: 0,
+            }
+        );
+
+        // The house is full
+        assert!(entity_storage.get_households_for_births().is_empty());
+
+        // The children don't work
+        entity_storage.register_unemployee(child);
+        entity_storage.register_office(BuildingNeedToBeFulfilled::new(
+            4,
+            Position { x: 5, y: 0 },
+            1,
+        ));
+        assert!(job_assignment(&mut entity_storage).is_empty());
+
+        // Until they grow up
+        for _ in 0..configuration.adult_age {
+            entity_storage.grow_old(configuration, |_| false);
+        }
+        let assignments = job_assignment(&mut entity_storage);
+        assert_eq!(assignments.len(), 1);
+        assert_eq!(assignments[0].from, child);
+
+        // The parents die, freeing their spots
+        entity_storage.grow_old(configuration, |inhabitant| {
+            parents.contains(inhabitant.get_id())
+        });
+        let dead = entity_storage.remove_emigrants();
+        assert_eq!(dead.len(), 2);
+        assert!(dead.iter().all(|(_, reason)| *reason == LeaveReason::Died));
+        assert_eq!(
+            entity_storage.population_statistics(),
+            PopulationStatistics {
+                children: 0,
+                adults: 1,
+                retirees: 0,
+            }
+        );
+        // Only one adult: no births
+        assert!(entity_storage.get_households_for_births().is_empty());
+    }
+
+    #[test]
+    fn test_households_move_together() {
+        let mut entity_storage = EntityStorage::default();
+        let configuration = &CONFIGURATION.population;
+
+        let small_house = 0_u64;
+        entity_storage.register_house(BuildingNeedToBeFulfilled::new(
+            small_house,
+            Position { x: 0, y: 0 },
+            2,
+        ));
+
+        let members = [1_u64, 2_u64, 3_u64];
+        let ages = [30, 30, 6];
+        entity_storage.introduce_household(
+            members
+                .iter()
+                .zip(ages)
+                .map(|(member, age)| {
+                    Inhabitant::new(*member, EducationLevel::None).with_age(age, configuration)
+                })
+                .collect(),
+        );
+
+        // The household doesn't fit
+        assert!(house_assignment(&mut entity_storage).is_empty());
+
+        let house = 4_u64;
+        let house_position = Position { x: 5, y: 0 };
+        entity_storage.register_house(BuildingNeedToBeFulfilled::new(house, house_position, 4));
+        let assignments = house_assignment(&mut entity_storage);
+        assert_eq!(assignments.len(), 1);
+        assert_eq!(assignments[0].from, members[0]);
+        assert_eq!(assignments[0].to, house);
+        assert_eq!(assignments[0].count, 3);
+
+        let mut moved = entity_storage.found_home_for_household(&members[0], house, house_position);
+        moved.sort_unstable();
+        assert_eq!(moved, members);
+
+        // The adults look for a job, the child goes to school first
+        for member in members {
+            entity_storage.register_unemployee(member);
+        }
+        let school = 5_u64;
+        entity_storage.register_school(BuildingNeedToBeFulfilled::new(
+            school,
+            Position { x: 3, y: 0 },
+            1,
+        ));
+        let assignments = entity_storage.get_inhabitant_school_assignment();
+        assert_eq!(assignments.len(), 1);
+        assert_eq!(assignments[0].from, members[2]);
+
+        let office = 6_u64;
+        entity_storage.register_office(BuildingNeedToBeFulfilled::new(
+            office,
+            Position { x: 5, y: 5 },
+            3,
+        ));
+        let mut workers: Vec<_> = job_assignment(&mut entity_storage)
+            .iter()
+            .map(|assignment| assignment.from)
+            .collect();
+        workers.sort_unstable();
+        assert_eq!(workers, members[..2]);
+    }
+
+    #[test]
+    fn test_workers_go_to_the_nearest_reachable_office() {
+        let mut entity_storage = EntityStorage::default();
+
+        // The nearest office is not reachable
+        let unreachable = Position { x: 1, y: 0 };
+        let offices = [
+            (10_u64, unreachable),
+            (11_u64, Position { x: 4, y: 0 }),
+            (12_u64, Position { x: 9, y: 0 }),
+        ];
+        for (office, office_position) in offices {
+            entity_storage.register_office(BuildingNeedToBeFulfilled::new(
+                office,
+                office_position,
+                1,
+            ));
+        }
+        let distance =
+            |from: &Position, to: &Position| (*to != unreachable).then(|| from.distance(to));
+
+        let worker = 1_u64;
+        inhabitant_at_home(&mut entity_storage, worker, EducationLevel::None);
+        entity_storage.register_unemployee(worker);
+        let assignments =
+            entity_storage.get_inhabitant_job_assignment(&CONFIGURATION.assignment, distance);
+        assert_eq!(assignments.len(), 1);
+        assert_eq!(assignments[0].from, worker);
+        assert_eq!(assignments[0].to, 11);
+
+        // The next one takes the remaining one
+        let other = 2_u64;
+        inhabitant_at_home(&mut entity_storage, other, EducationLevel::None);
+        entity_storage.register_unemployee(other);
+        let assignments =
+            entity_storage.get_inhabitant_job_assignment(&CONFIGURATION.assignment, distance);
+        assert_eq!(assignments.len(), 1);
+        assert_eq!(assignments[0].to, 12);
+
+        // Nothing else is reachable
+        let last = 3_u64;
+        inhabitant_at_home(&mut entity_storage, last, EducationLevel::None);
+        entity_storage.register_unemployee(last);
+        assert!(entity_storage
+            .get_inhabitant_job_assignment(&CONFIGURATION.assignment, distance)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_batch_job_assignment() {
+        let mut entity_storage = EntityStorage::default();
+
+        let west = 10_u64;
+        let east = 11_u64;
+        entity_storage.register_office(BuildingNeedToBeFulfilled::new(
+            west,
+            Position { x: -5, y: 0 },
+            1,
+        ));
+        entity_storage.register_office(BuildingNeedToBeFulfilled::new(
+            east,
+            Position { x: 5, y: 0 },
+            1,
+        ));
+
+        let workers = [
+            (1_u64, Position { x: 4, y: 0 }),
+            (2_u64, Position { x: -4, y: 0 }),
+        ];
+        for (worker, house_position) in workers {
+            let house = 100 + worker;
+            entity_storage.register_house(BuildingNeedToBeFulfilled::new(house, house_position, 1));
+            entity_storage.introduce_inhabitant(Inhabitant::new(worker, EducationLevel::None));
+            entity_storage.found_home_for_household(&worker, house, house_position);
+            entity_storage.register_unemployee(worker);
+        }
+
+        // Both at once, everybody near home
+        let mut assignments = job_assignment(&mut entity_storage);
+        assignments.sort_unstable_by_key(|assignment| assignment.from);
+        assert_eq!(assignments.len(), 2);
+        assert_eq!((assignments[0].from, assignments[0].to), (1, east));
+        assert_eq!((assignments[1].from, assignments[1].to), (2, west));
+    }
+
+    #[test]
+    fn test_households_prefer_palatable_houses() {
+        let mut entity_storage = EntityStorage::default();
+
+        let ugly = Position { x: 1, y: 0 };
+        let nice = Position { x: 9, y: 0 };
+        entity_storage.register_house(BuildingNeedToBeFulfilled::new(0, ugly, 4));
+        entity_storage.register_house(BuildingNeedToBeFulfilled::new(1, nice, 4));
+
+        entity_storage.introduce_inhabitant(Inhabitant::new(2, EducationLevel::None));
+        let assignments = entity_storage.get_inhabitant_house_assignment(
+            &CONFIGURATION.assignment,
+            |from, to| Some(from.distance(to)),
+            |position| if *position == nice { 10 } else { -10 },
+        );
+        assert_eq!(assignments.len(), 1);
+        assert_eq!(assignments[0].to, 1);
+    }
 }
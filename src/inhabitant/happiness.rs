@@ -0,0 +1,175 @@
+use crate::{common::configuration::HappinessConfiguration, power::PowerCoverage};
+
+pub const MAX_HAPPINESS: u8 = 100;
+/// The happiness of the new inhabitants, before the first computation
+pub const INITIAL_HAPPINESS: u8 = MAX_HAPPINESS / 2;
+
+/// What the happiness of an inhabitant depends on
+#[derive(Debug)]
+pub struct HappinessFactors {
+    pub house_palatability: i32,
+    pub employed: bool,
+    /// The students are not unemployed
+    pub studying: bool,
+    /// Neither the children nor the retirees are unemployed
+    pub working_age: bool,
+    pub power_coverage: Option<PowerCoverage>,
+    /// Between home and work place, if employed
+    pub commute_distance: Option<u32>,
+    /// Paid every tick
+    pub taxes: f32,
+}
+
+pub fn calculate_happiness(
+    configuration: &HappinessConfiguration,
+    factors: &HappinessFactors,
+) -> u8 {
+    let palatability = configuration.palatability_weight * factors.house_palatability;
+
+    let employment = if factors.employed {
+        configuration.employed_value
+    } else if factors.studying || !factors.working_age {
+        0
+    } else {
+        configuration.unemployed_value
+    };
+
+    let power = match factors.power_coverage {
+        Some(PowerCoverage::Covered) => configuration.power_value,
+        Some(PowerCoverage::PartiallyCovered) | None => 0,
+        Some(PowerCoverage::Uncovered) => -configuration.power_value,
+    };
+
+    let commute = factors.commute_distance.map_or(0, |distance| {
+        let extra = distance.saturating_sub(configuration.max_comfortable_commute);
+        -configuration.commute_penalty * extra as i32
+    });
+
+    let taxes = -(factors.taxes * configuration.tax_weight) as i32;
+
+    (configuration.base_value + palatability + employment + power + commute + taxes)
+        .clamp(0, MAX_HAPPINESS.into()) as u8
+}
+
+/// The chance to leave the city on a tick: 0 over `unhappy_threshold`,
+/// growing linearly up to `max_leave_probability` with no happiness
+pub fn leave_probability(configuration: &HappinessConfiguration, happiness: u8) -> f32 {
+    if happiness >= configuration.unhappy_threshold {
+        return 0.;
+    }
+
+    let unhappiness = f32::from(configuration.unhappy_threshold - happiness)
+        / f32::from(configuration.unhappy_threshold);
+    configuration.max_leave_probability * unhappiness
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::configuration::CONFIGURATION;
+
+    use super::*;
+
+    fn factors() -> HappinessFactors {
+        HappinessFactors {
+            house_palatability: 0,
+            employed: false,
+            studying: false,
+            working_age: true,
+            power_coverage: None,
+            commute_distance: None,
+            taxes: 0.,
+        }
+    }
+
+    #[test]
+    fn test_calculate_happiness() {
+        let configuration = &CONFIGURATION.happiness;
+        let unemployed = calculate_happiness(configuration, &factors());
+        assert_eq!(
+            i32::from(unemployed),
+            configuration.base_value + configuration.unemployed_value
+        );
+
+        let employed = HappinessFactors {
+            employed: true,
+            commute_distance: Some(configuration.max_comfortable_commute),
+            ..factors()
+        };
+        let employed = calculate_happiness(configuration, &employed);
+        assert_eq!(
+            i32::from(employed),
+            configuration.base_value + configuration.employed_value
+        );
+
+        let far_away = HappinessFactors {
+            employed: true,
+            commute_distance: Some(configuration.max_comfortable_commute + 5),
+            ..factors()
+        };
+        assert!(calculate_happiness(configuration, &far_away) < employed);
+
+        let studying = HappinessFactors {
+            studying: true,
+            ..factors()
+        };
+        let studying = calculate_happiness(configuration, &studying);
+        assert!(unemployed < studying && studying < employed);
+
+        let retired = HappinessFactors {
+            working_age: false,
+            ..factors()
+        };
+        assert_eq!(calculate_happiness(configuration, &retired), studying);
+
+        let covered = HappinessFactors {
+            power_coverage: Some(PowerCoverage::Covered),
+            ..factors()
+        };
+        let uncovered = HappinessFactors {
+            power_coverage: Some(PowerCoverage::Uncovered),
+            ..factors()
+        };
+        assert!(calculate_happiness(configuration, &covered) > unemployed);
+        assert!(calculate_happiness(configuration, &uncovered) < unemployed);
+
+        let taxed = HappinessFactors {
+            taxes: 1.,
+            ..factors()
+        };
+        assert!(calculate_happiness(configuration, &taxed) < unemployed);
+
+        // Always between 0 and `MAX_HAPPINESS`
+        let wonderful = HappinessFactors {
+            house_palatability: 1000,
+            ..factors()
+        };
+        let awful = HappinessFactors {
+            house_palatability: -1000,
+            ..factors()
+        };
+        assert_eq!(
+            calculate_happiness(configuration, &wonderful),
+            MAX_HAPPINESS
+        );
+        assert_eq!(calculate_happiness(configuration, &awful), 0);
+    }
+
+    #[test]
+    fn test_leave_probability() {
+        let configuration = &CONFIGURATION.happiness;
+
+        assert_eq!(leave_probability(configuration, MAX_HAPPINESS), 0.);
+        assert_eq!(
+            leave_probability(configuration, configuration.unhappy_threshold),
+            0.
+        );
+        assert_eq!(
+            leave_probability(configuration, 0),
+            configuration.max_leave_probability
+        );
+
+        let unhappy = leave_probability(configuration, configuration.unhappy_threshold / 2);
+        assert!(unhappy > 0.);
+        assert!(unhappy < leave_probability(configuration, 0));
+    }
+}
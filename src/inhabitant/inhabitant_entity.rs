@@ -1,22 +1,97 @@
 use lombok::Getter;
 
-use crate::common::{enums::EducationLevel, position::Position, EntityId};
+use crate::common::{
+    configuration::PopulationConfiguration, enums::EducationLevel, position::Position, EntityId,
+};
+
+use crate::power::PowerCoverage;
+
+use super::happiness::{HappinessFactors, INITIAL_HAPPINESS};
 
 #[derive(Getter, Debug)]
 pub struct Inhabitant {
     id: EntityId,
+    /// The id of the first member of the household
+    household_id: EntityId,
     home: Option<Home>,
     work_place_id: Option<WorkPlace>,
+    school: Option<SchoolPlace>,
     education_level: EducationLevel,
+    /// In years
+    age: u32,
+    life_stage: LifeStage,
+    happiness: u8,
+    /// Consecutive ticks at home without a job
+    unemployed_ticks: u32,
+    /// Consecutive ticks at home without power
+    unpowered_ticks: u32,
 }
 
 impl Inhabitant {
+    /// An adult, until `with_age` tells otherwise
     pub fn new(id: EntityId, education_level: EducationLevel) -> Self {
         Self {
             id,
+            household_id: id,
             home: None,
             work_place_id: None,
+            school: None,
             education_level,
+            age: 0,
+            life_stage: LifeStage::Adult,
+            happiness: INITIAL_HAPPINESS,
+            unemployed_ticks: 0,
+            unpowered_ticks: 0,
+        }
+    }
+
+    pub fn newborn(id: EntityId) -> Self {
+        Self {
+            life_stage: LifeStage::Child,
+            ..Self::new(id, EducationLevel::None)
+        }
+    }
+
+    pub fn with_age(mut self, age: u32, configuration: &PopulationConfiguration) -> Self {
+        self.age = age;
+        self.life_stage = LifeStage::of(age, configuration);
+        self
+    }
+
+    pub fn join_household(&mut self, household_id: EntityId) {
+        self.household_id = household_id;
+    }
+
+    /// A year passes: return the new life stage if it changed
+    pub fn grow_old(&mut self, configuration: &PopulationConfiguration) -> Option<LifeStage> {
+        self.age += 1;
+        let life_stage = LifeStage::of(self.age, configuration);
+        if life_stage == self.life_stage {
+            return None;
+        }
+        self.life_stage = life_stage;
+        Some(life_stage)
+    }
+
+    /// Leave the work place, returned
+    pub fn retire(&mut self) -> Option<WorkPlace> {
+        self.work_place_id.take()
+    }
+
+    /// Called every tick with what the happiness is computed from
+    pub fn update_happiness(&mut self, happiness: u8, factors: &HappinessFactors) {
+        self.happiness = happiness;
+
+        if factors.employed || factors.studying || !factors.working_age {
+            self.unemployed_ticks = 0;
+        } else {
+            self.unemployed_ticks += 1;
+        }
+
+        if factors.power_coverage == Some(PowerCoverage::Uncovered) {
+            self.unpowered_ticks += 1;
+        } else {
+            self.unpowered_ticks = 0;
         }
     }
 
@@ -39,6 +114,69 @@ impl Inhabitant {
             work_place_position,
         })
     }
+
+    /// The work place is demolished
+    pub fn lose_job(&mut self) {
+        self.work_place_id = None;
+    }
+
+    /// The school is demolished: the studies start over
+    pub fn leave_school(&mut self) {
+        self.school = None;
+    }
+
+    pub fn school_found(&mut self, school_id: EntityId, school_position: Position) {
+        debug_assert!(self.school.is_none(), "Already at school!");
+
+        self.school = Some(SchoolPlace {
+            school_id,
+            school_position,
+            study_ticks: 0,
+        })
+    }
+
+    /// Study for a tick: after `ticks_for_level` the inhabitant reaches the next
+    /// education level and leaves the school, returned
+    pub fn study(&mut self, ticks_for_level: u32) -> Option<SchoolPlace> {
+        let school = self.school.as_mut()?;
+        school.study_ticks += 1;
+        if school.study_ticks < ticks_for_level {
+            return None;
+        }
+
+        if let Some(next) = self.education_level.next() {
+            self.education_level = next;
+        }
+        self.school.take()
+    }
+
+    /// Can go to school to reach the next education level
+    pub fn can_study(&self) -> bool {
+        self.life_stage != LifeStage::Retired
+            && self.home.is_some()
+            && self.work_place_id.is_none()
+            && self.school.is_none()
+            && self.education_level.next().is_some()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LifeStage {
+    Child,
+    Adult,
+    Retired,
+}
+
+impl LifeStage {
+    fn of(age: u32, configuration: &PopulationConfiguration) -> Self {
+        if age < configuration.adult_age {
+            LifeStage::Child
+        } else if age < configuration.retirement_age {
+            LifeStage::Adult
+        } else {
+            LifeStage::Retired
+        }
+    }
 }
 
 #[allow(dead_code)]
@@ -54,3 +192,10 @@ pub struct WorkPlace {
     work_place_id: EntityId,
     work_place_position: Position,
 }
+
+#[derive(Debug, Getter)]
+pub struct SchoolPlace {
+    school_id: EntityId,
+    school_position: Position,
+    study_ticks: u32,
+}
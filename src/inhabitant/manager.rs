@@ -1,7 +1,430 @@
-pub struct InhabitantManager {}
+use std::sync::Arc;
+
+use crate::common::{configuration::Configuration, random::Random};
+
+use super::{
+    entity_storage::PopulationStatistics,
+    happiness::{calculate_happiness, leave_probability, HappinessFactors},
+    inhabitant_entity::Inhabitant,
+};
+
+pub struct InhabitantManager {
+    configuration: Arc<Configuration>,
+    /// Ticks since the last year passed
+    ticks_in_year: u32,
+    population_statistics: PopulationStatistics,
+}
 
 impl InhabitantManager {
-    pub fn new() -> Self {
-        Self {}
+    pub fn new(configuration: Arc<Configuration>) -> Self {
+        Self {
+            configuration,
+            ticks_in_year: 0,
+            population_statistics: PopulationStatistics::default(),
+        }
+    }
+
+    /// Called every tick: true when a year passes
+    pub fn new_year(&mut self) -> bool {
+        self.ticks_in_year += 1;
+        if self.ticks_in_year < self.configuration.population.ticks_per_year {
+            return false;
+        }
+        self.ticks_in_year = 0;
+        true
+    }
+
+    /// The age of a new immigrant, between `adult_age` and `max_immigrant_age`
+    pub fn immigrant_age(&self, random: &mut Random) -> u32 {
+        let configuration = &self.configuration.population;
+        let years = configuration
+            .max_immigrant_age
+            .saturating_sub(configuration.adult_age);
+        configuration.adult_age + (random.next_f32() * years as f32) as u32
+    }
+
+    /// Between 1 and `max_inhabitant_per_travel`
+    pub fn household_size(&self, random: &mut Random) -> u32 {
+        let max = self
+            .configuration
+            .buildings
+            .house
+            .max_inhabitant_per_travel
+            .max(1);
+        1 + (random.next_f32() * max as f32) as u32
+    }
+
+    /// The ages of the members of a new household: up to two adults, the others are children
+    pub fn household_ages(&self, random: &mut Random, size: u32) -> Vec<u32> {
+        let adult_age = self.configuration.population.adult_age;
+        (0..size)
+            .map(|member| {
+                if member < 2 {
+                    self.immigrant_age(random)
+                } else {
+                    (random.next_f32() * adult_age as f32) as u32
+                }
+            })
+            .collect()
+    }
+
+    /// Group the arriving inhabitants into households, every member with its age.
+    /// Every arriving inhabitant is in a household, the last one could be smaller
+    pub fn split_into_households<T>(
+        &self,
+        random: &mut Random,
+        arriving: impl IntoIterator<Item = T>,
+    ) -> Vec<Vec<(T, u32)>> {
+        let mut arriving = arriving.into_iter().peekable();
+        let mut households = vec![];
+        while arriving.peek().is_some() {
+            let size = self.household_size(random);
+            let ages = self.household_ages(random, size);
+            // Not pulled beyond the size: the exceeding inhabitant goes in the next household
+            households.push(arriving.by_ref().take(size as usize).zip(ages).collect());
+        }
+        households
+    }
+
+    /// The older inhabitants are more likely to die
+    pub fn dies(&self, random: &mut Random, age: u32) -> bool {
+        let configuration = &self.configuration.population;
+        let probability = age.saturating_sub(configuration.old_age) as f32
+            * configuration.death_probability_per_year;
+        random.chance(probability)
+    }
+
+    pub fn gives_birth(&self, random: &mut Random) -> bool {
+        random.chance(self.configuration.population.birth_probability)
+    }
+
+    pub fn set_population_statistics(&mut self, population_statistics: PopulationStatistics) {
+        self.population_statistics = population_statistics;
+    }
+
+    pub fn population_statistics(&self) -> &PopulationStatistics {
+        &self.population_statistics
+    }
+
+    pub fn calculate_happiness(&self, factors: &HappinessFactors) -> u8 {
+        calculate_happiness(&self.configuration.happiness, factors)
+    }
+
+    /// Why the inhabitant leaves the city, `None` if they stay
+    pub fn emigration_reason(
+        &self,
+        random: &mut Random,
+        inhabitant: &Inhabitant,
+        house_palatability: i32,
+    ) -> Option<LeaveReason> {
+        let configuration = &self.configuration.emigration;
+
+        if *inhabitant.get_unemployed_ticks() > configuration.max_unemployed_ticks {
+            return Some(LeaveReason::Unemployed);
+        }
+        if *inhabitant.get_unpowered_ticks() > configuration.max_unpowered_ticks {
+            return Some(LeaveReason::Unpowered);
+        }
+        if house_palatability < configuration.min_house_palatability {
+            return Some(LeaveReason::UnpalatableHome);
+        }
+        if self.wants_to_leave(random, *inhabitant.get_happiness()) {
+            return Some(LeaveReason::Unhappy);
+        }
+
+        None
+    }
+
+    /// The unhappy inhabitants are more likely to leave
+    fn wants_to_leave(&self, random: &mut Random, happiness: u8) -> bool {
+        let probability = leave_probability(&self.configuration.happiness, happiness);
+        random.chance(probability)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LeaveReason {
+    Unemployed,
+    Unpowered,
+    UnpalatableHome,
+    Unhappy,
+    Died,
+    /// The home was demolished
+    Evicted,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        common::{configuration::CONFIGURATION, enums::EducationLevel, position::Position},
+        inhabitant::{
+            entity_storage::{BuildingNeedToBeFulfilled, EntityStorage},
+            inhabitant_entity::LifeStage,
+        },
+        power::PowerCoverage,
+    };
+
+    use super::*;
+
+    fn random() -> Random {
+        Random::new(CONFIGURATION.game.seed)
+    }
+
+    /// Some years of a small city: households arrive, work, grow old, give birth and leave.
+    /// Return the dump of the state
+    fn simulate_city(seed: u64) -> String {
+        let configuration = &CONFIGURATION;
+        let manager = InhabitantManager::new(Arc::new(CONFIGURATION));
+        let mut random = Random::new(seed);
+        let mut entity_storage = EntityStorage::default();
+        let mut next_id = 0_u64;
+
+        for i in 0..4 {
+            let position = Position { x: i * 3, y: 0 };
+            entity_storage.register_house(BuildingNeedToBeFulfilled::new(next_id, position, 6));
+            entity_storage.register_office(BuildingNeedToBeFulfilled::new(
+                next_id + 1,
+                Position { x: i * 3, y: 2 },
+                4,
+            ));
+            next_id += 2;
+        }
+
+        for _ in 0..30 {
+            let size = manager.household_size(&mut random);
+            let household = manager
+                .household_ages(&mut random, size)
+                .into_iter()
+                .map(|age| {
+                    next_id += 1;
+                    Inhabitant::new(next_id, EducationLevel::None)
+                        .with_age(age, &configuration.population)
+                })
+                .collect();
+            entity_storage.introduce_household(household);
+
+            let houses = entity_storage.get_inhabitant_house_assignment(
+                &configuration.assignment,
+                |from, to| Some(from.distance(to)),
+                |position| position.x as i32,
+            );
+            for house in houses {
+                for member in entity_storage.found_home_for_household(
+                    &house.from,
+                    house.to,
+                    house.to_position,
+                ) {
+                    entity_storage.register_unemployee(member);
+                }
+            }
+            let jobs = entity_storage
+                .get_inhabitant_job_assignment(&configuration.assignment, |from, to| {
+                    Some(from.distance(to))
+                });
+            for job in jobs {
+                entity_storage.found_job_for_unemployee(&job.from, job.to, job.to_position);
+            }
+
+            entity_storage.grow_old(&configuration.population, |inhabitant| {
+                manager.dies(&mut random, *inhabitant.get_age())
+            });
+            for household_id in entity_storage.get_households_for_births() {
+                if manager.gives_birth(&mut random) {
+                    next_id += 1;
+                    entity_storage.give_birth(Inhabitant::newborn(next_id), &household_id);
+                }
+            }
+
+            entity_storage.update_happiness(
+                |inhabitant| {
+                    Some(HappinessFactors {
+                        house_palatability: 0,
+                        employed: inhabitant.get_work_place_id().is_some(),
+                        studying: false,
+                        working_age: *inhabitant.get_life_stage() == LifeStage::Adult,
+                        power_coverage: Some(PowerCoverage::Covered),
+                        commute_distance: None,
+                        taxes: 0.,
+                    })
+                },
+                |factors| manager.calculate_happiness(factors),
+            );
+            entity_storage.find_inhabitants_want_to_leave(|inhabitant| {
+                manager.emigration_reason(&mut random, inhabitant, 0)
+            });
+            entity_storage.remove_emigrants();
+        }
+
+        format!("{entity_storage:?}")
+    }
+
+    #[test]
+    fn test_same_seed_same_city() {
+        let seed = CONFIGURATION.game.seed;
+        assert_eq!(simulate_city(seed), simulate_city(seed));
+        assert_ne!(simulate_city(seed), simulate_city(seed + 1));
+    }
+
+    #[test]
+    fn test_unhappy_inhabitants_are_more_likely_to_leave() {
+        let manager = InhabitantManager::new(Arc::new(CONFIGURATION));
+        let mut random = random();
+        let threshold = CONFIGURATION.happiness.unhappy_threshold;
+
+        let mut leaving = |happiness| {
+            (0..10_000)
+                .filter(|_| manager.wants_to_leave(&mut random, happiness))
+                .count()
+        };
+
+        assert_eq!(leaving(threshold), 0);
+        let unhappy = leaving(threshold / 2);
+        let desperate = leaving(0);
+        assert!(unhappy > 0);
+        assert!(desperate > unhappy);
+    }
+
+    #[test]
+    fn test_years_pass() {
+        let mut manager = InhabitantManager::new(Arc::new(CONFIGURATION));
+        let ticks_per_year = CONFIGURATION.population.ticks_per_year;
+
+        for _ in 0..2 {
+            for _ in 1..ticks_per_year {
+                assert!(!manager.new_year());
+            }
+            assert!(manager.new_year());
+        }
+    }
+
+    #[test]
+    fn test_old_inhabitants_are_more_likely_to_die() {
+        let manager = InhabitantManager::new(Arc::new(CONFIGURATION));
+        let mut random = random();
+        let old_age = CONFIGURATION.population.old_age;
+
+        let mut dying = |age| {
+            (0..10_000)
+                .filter(|_| manager.dies(&mut random, age))
+                .count()
+        };
+
+        assert_eq!(dying(0), 0);
+        assert_eq!(dying(old_age), 0);
+        let old = dying(old_age + 2);
+        let very_old = dying(old_age + 10);
+        assert!(old > 0);
+        assert!(very_old > old);
+    }
+
+    #[test]
+    fn test_immigrants_are_adults() {
+        let manager = InhabitantManager::new(Arc::new(CONFIGURATION));
+        let mut random = random();
+        let configuration = &CONFIGURATION.population;
+
+        for _ in 0..1_000 {
+            let age = manager.immigrant_age(&mut random);
+            assert!(age >= configuration.adult_age);
+            assert!(age <= configuration.max_immigrant_age);
+        }
+    }
+
+    #[test]
+    fn test_households() {
+        let manager = InhabitantManager::new(Arc::new(CONFIGURATION));
+        let mut random = random();
+        let configuration = &CONFIGURATION.population;
+        let max_size = CONFIGURATION.buildings.house.max_inhabitant_per_travel;
+
+        for _ in 0..1_000 {
+            let size = manager.household_size(&mut random);
+            assert!((1..=max_size).contains(&size));
+        }
+
+        // Nobody is lost between the households
+        let households = manager.split_into_households(&mut random, 0..50);
+        assert!(households.len() > 1);
+        let members: Vec<_> = households
+            .iter()
+            .flat_map(|household| household.iter().map(|(member, _)| *member))
+            .collect();
+        assert_eq!(members, (0..50).collect::<Vec<_>>());
+        assert!(households
+            .iter()
+            .all(|household| (1..=max_size as usize).contains(&household.len())));
+
+        let ages = manager.household_ages(&mut random, max_size);
+        assert_eq!(ages.len() as u32, max_size);
+        assert!(ages[..2].iter().all(|age| *age >= configuration.adult_age));
+        assert!(ages[2..].iter().all(|age| *age < configuration.adult_age));
+    }
+
+    #[test]
+    fn test_emigration_reason() {
+        let manager = InhabitantManager::new(Arc::new(CONFIGURATION));
+        let mut random = random();
+        let configuration = &CONFIGURATION.emigration;
+        let happy = CONFIGURATION.happiness.unhappy_threshold;
+
+        let factors = |employed, power_coverage| HappinessFactors {
+            house_palatability: 0,
+            employed,
+            studying: false,
+            working_age: true,
+            power_coverage,
+            commute_distance: None,
+            taxes: 0.,
+        };
+        let live = |inhabitant: &mut Inhabitant, factors: HappinessFactors, ticks| {
+            for _ in 0..ticks {
+                inhabitant.update_happiness(happy, &factors);
+            }
+        };
+
+        let mut inhabitant = Inhabitant::new(0, EducationLevel::None);
+        live(
+            &mut inhabitant,
+            factors(false, Some(PowerCoverage::Covered)),
+            configuration.max_unemployed_ticks,
+        );
+        assert_eq!(manager.emigration_reason(&mut random, &inhabitant, 0), None);
+        live(
+            &mut inhabitant,
+            factors(false, Some(PowerCoverage::Covered)),
+            1,
+        );
+        assert_eq!(
+            manager.emigration_reason(&mut random, &inhabitant, 0),
+            Some(LeaveReason::Unemployed)
+        );
+        // A job resets the count
+        live(
+            &mut inhabitant,
+            factors(true, Some(PowerCoverage::Covered)),
+            1,
+        );
+        assert_eq!(manager.emigration_reason(&mut random, &inhabitant, 0), None);
+
+        live(
+            &mut inhabitant,
+            factors(true, Some(PowerCoverage::Uncovered)),
+            configuration.max_unpowered_ticks + 1,
+        );
+        assert_eq!(
+            manager.emigration_reason(&mut random, &inhabitant, 0),
+            Some(LeaveReason::Unpowered)
+        );
+        live(&mut inhabitant, factors(true, None), 1);
+        assert_eq!(manager.emigration_reason(&mut random, &inhabitant, 0), None);
+
+        assert_eq!(
+            manager.emigration_reason(
+                &mut random,
+                &inhabitant,
+                configuration.min_house_palatability - 1
+            ),
+            Some(LeaveReason::UnpalatableHome)
+        );
     }
 }
@@ -1,3 +1,4 @@
+mod happiness;
 mod inhabitant_entity;
 mod manager;
 mod plugin;
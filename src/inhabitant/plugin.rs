@@ -1,16 +1,23 @@
 use bevy::prelude::*;
 
 use crate::{
-    building::{events::BuildingCreatedEvent, BuildingSnapshot},
-    common::{position::Position, EntityId},
+    building::{
+        events::{BuildingCreatedEvent, BuildingDemolishedEvent, HouseDensityUpgradedEvent},
+        BuildingSnapshot,
+    },
+    common::{enums::EducationLevel, position::Position, EntityId},
     navigation::NavigatorResource,
     palatability::{MoreInhabitantsNeeded, MoreWorkersNeeded, PalatabilityManagerResource},
+    power::PowerManagerResource,
+    resources::{ConfigurationResource, RandomResource},
+    GameTick,
 };
 
 use super::{
     entity_storage::{AssignmentResult, BuildingNeedToBeFulfilled, EntityStorage},
-    inhabitant_entity::Inhabitant,
-    manager::InhabitantManager,
+    happiness::HappinessFactors,
+    inhabitant_entity::{Inhabitant, LifeStage},
+    manager::{InhabitantManager, LeaveReason},
 };
 
 use self::{components::*, resources::*};
@@ -20,18 +27,32 @@ pub struct InhabitantPlugin;
 
 impl Plugin for InhabitantPlugin {
     fn build(&self, app: &mut App) {
-        let manager = InhabitantManagerResource(InhabitantManager::new());
+        let configuration: &ConfigurationResource = app.world.resource();
+        let manager = InhabitantManagerResource(InhabitantManager::new((*configuration).clone()));
 
         app.insert_resource(manager)
             .add_event::<HomeAssignedToInhabitantEvent>()
             .add_event::<JobAssignedToInhabitantEvent>()
+            .add_event::<InhabitantLeftEvent>()
+            .add_event::<SchoolAssignedToInhabitantEvent>()
+            .add_event::<InhabitantGraduatedEvent>()
+            .add_event::<InhabitantBornEvent>()
+            .add_event::<InhabitantRetiredEvent>()
             // Probably we would like to create Vecs with already-preallocated capacity
             .insert_resource(EntityStorageResource(EntityStorage::default()))
             .add_system(register_target)
+            .add_system(evict_inhabitants.after(register_target))
+            .add_system(expand_houses)
             .add_system(create_inhabitants)
             .add_system(find_houses_for_inhabitants)
             .add_system(find_job_for_inhabitants)
-            .add_system(inhabitant_want_to_work);
+            .add_system(inhabitant_want_to_work)
+            .add_system(find_school_for_inhabitants)
+            .add_system(study)
+            // The draws from the random generator follow the same order on every run
+            .add_system(update_happiness.after(create_inhabitants))
+            .add_system(age_population.after(update_happiness))
+            .add_system_to_stage(CoreStage::First, emigrate);
     }
 }
 
@@ -39,6 +60,7 @@ fn register_target(
     mut building_created_reader: EventReader<BuildingCreatedEvent>,
     mut commands: Commands,
     mut entity_storage: ResMut<EntityStorageResource>,
+    configuration: Res<ConfigurationResource>,
 ) {
     for created_building in building_created_reader.iter() {
         let building_position: &Position = created_building.building_snapshot.get_position();
@@ -72,25 +94,61 @@ fn register_target(
                     .insert(TargetTypeOffice);
 
                 info!("Register office");
-                entity_storage.register_office(BuildingNeedToBeFulfilled::new(
+                entity_storage.register_office(
+                    BuildingNeedToBeFulfilled::new(
+                        entity.to_bits(),
+                        *building_position,
+                        office.max_workers,
+                    )
+                    .requiring(configuration.buildings.office.required_education_level),
+                );
+            }
+            BuildingSnapshot::School(school) => {
+                commands
+                    .entity(entity)
+                    .insert(TargetComponent {
+                        target_position: *building_position,
+                        target_type: TargetType::School,
+                    })
+                    .insert(TargetTypeSchool);
+
+                info!("Register school");
+                entity_storage.register_school(BuildingNeedToBeFulfilled::new(
                     entity.to_bits(),
                     *building_position,
-                    office.max_workers,
+                    school.max_students,
                 ));
             }
             BuildingSnapshot::Street(_) => {}
             BuildingSnapshot::Garden(_) => {}
             BuildingSnapshot::BiomassPowerPlant(_) => {}
+            BuildingSnapshot::Battery(_) => {}
         }
     }
 }
 
+fn expand_houses(
+    mut house_density_upgraded_reader: EventReader<HouseDensityUpgradedEvent>,
+    mut entity_storage: ResMut<EntityStorageResource>,
+) {
+    for upgraded in house_density_upgraded_reader.iter() {
+        entity_storage.increase_house_capacity(BuildingNeedToBeFulfilled::new(
+            upgraded.house_id,
+            upgraded.house_position,
+            upgraded.added_residents,
+        ));
+    }
+}
+
 /// Create inhabitants
 fn create_inhabitants(
     mut commands: Commands,
     mut entity_storage: ResMut<EntityStorageResource>,
     mut more_inhabitants_needed_reader: EventReader<MoreInhabitantsNeeded>,
     _palatability_manager: Res<PalatabilityManagerResource>,
+    inhabitant_manager: Res<InhabitantManagerResource>,
+    mut random: ResMut<RandomResource>,
+    configuration: Res<ConfigurationResource>,
 ) {
     // TODO: for the time being we consider the origin as the:
     // - origin
@@ -99,25 +157,49 @@ fn create_inhabitants(
 
     // let palatability_manager: &PalatabilityManager = &*palatability_manager;
 
-    let total = more_inhabitants_needed_reader
+    let arriving = more_inhabitants_needed_reader
         .iter()
         .flat_map(|e| e.inhabitants_to_spawn.iter());
 
-    for inhabitant_to_spawn in total {
-        let entity = commands.spawn_empty().insert(InhabitantComponent).id();
-
-        let inhabitant = Inhabitant::new(entity.to_bits(), inhabitant_to_spawn.education_level);
-
-        entity_storage.introduce_inhabitant(inhabitant);
+    // The inhabitants arrive in households
+    for members in inhabitant_manager.split_into_households(&mut random, arriving) {
+        let household: Vec<Inhabitant> = members
+            .into_iter()
+            .map(|(inhabitant_to_spawn, age)| {
+                let entity = commands.spawn_empty().insert(InhabitantComponent).id();
+
+                // The children haven't studied yet
+                let education_level = if age < configuration.population.adult_age {
+                    EducationLevel::None
+                } else {
+                    inhabitant_to_spawn.education_level
+                };
+                Inhabitant::new(entity.to_bits(), education_level)
+                    .with_age(age, &configuration.population)
+            })
+            .collect();
+
+        entity_storage.introduce_household(household);
     }
 }
 
 fn find_houses_for_inhabitants(
     mut entity_storage: ResMut<EntityStorageResource>,
-    navigator: Res<NavigatorResource>,
+    mut navigator: ResMut<NavigatorResource>,
+    palatability: Res<PalatabilityManagerResource>,
+    configuration: Res<ConfigurationResource>,
     mut inhabitant_arrived_writer: EventWriter<HomeAssignedToInhabitantEvent>,
 ) {
-    let couples: Vec<AssignmentResult> = entity_storage.get_inhabitant_house_assignment();
+    // Only the reachable houses are assigned
+    let couples: Vec<AssignmentResult> = entity_storage.get_inhabitant_house_assignment(
+        &configuration.assignment,
+        |from, to| navigator.get_distance(from, to),
+        |house_position| {
+            palatability
+                .get_palatability_values(house_position)
+                .house_value
+        },
+    );
 
     if couples.is_empty() {
         return;
@@ -126,26 +208,14 @@ fn find_houses_for_inhabitants(
     info!("inhabitants-houses assignments {}", couples.len());
 
     for couple in couples {
-        let _navigation_descriptor =
-            match navigator.get_navigation_descriptor(&couple.from_position, couple.to_position) {
-                // TODO consider to have a try not immediately
-                // Avoiding removing HouseWaitingForInhabitantsComponent we are processing again
-                // every frame. So probably the best thing todo is to remove the component,
-                // adding a dedicated new one that allow us to "wait" for a while before retrying
-                None => {
-                    entity_storage.resign_assign_result(couple);
-                    continue;
-                }
-                Some(nd) => nd,
-            };
+        let members =
+            entity_storage.found_home_for_household(&couple.from, couple.to, couple.to_position);
 
         inhabitant_arrived_writer.send(HomeAssignedToInhabitantEvent {
-            inhabitants_entity_ids: vec![couple.from],
+            inhabitants_entity_ids: members,
             building_entity_id: couple.to,
             house_position: couple.to_position,
         });
-
-        entity_storage.found_home_for_inhabitant(&couple.from, couple.to, couple.to_position);
     }
 }
 
@@ -164,10 +234,15 @@ fn inhabitant_want_to_work(
 
 fn find_job_for_inhabitants(
     mut entity_storage: ResMut<EntityStorageResource>,
-    navigator: Res<NavigatorResource>,
+    mut navigator: ResMut<NavigatorResource>,
+    configuration: Res<ConfigurationResource>,
     mut inhabitant_found_job_writer: EventWriter<JobAssignedToInhabitantEvent>,
 ) {
-    let couples: Vec<AssignmentResult> = entity_storage.get_inhabitant_job_assignment();
+    // The waiting workers find the distances computed in the previous frames
+    let couples: Vec<AssignmentResult> = entity_storage
+        .get_inhabitant_job_assignment(&configuration.assignment, |from, to| {
+            navigator.get_distance(from, to)
+        });
 
     if couples.is_empty() {
         return;
@@ -176,12 +251,9 @@ fn find_job_for_inhabitants(
     info!("inhabitants-office assignments {}", couples.len());
 
     for couple in couples {
-        let _navigation_descriptor =
+        let navigation_descriptor =
             match navigator.get_navigation_descriptor(&couple.from_position, couple.to_position) {
-                // TODO consider to have a try not immediately
-                // "resign_assign_result" re-insert the couple inside an internal queue
-                // "resign_assign_result" can track this failure and avoid to propose again
-                // the same queue again and again
+                // Only the reachable offices are assigned, but better safe than sorry
                 None => {
                     entity_storage.resign_assign_result(couple);
                     continue;
@@ -189,6 +261,9 @@ fn find_job_for_inhabitants(
                 Some(nd) => nd,
             };
 
+        // The worker goes along this path every day
+        navigator.register_commute(couple.from, &navigation_descriptor);
+
         inhabitant_found_job_writer.send(JobAssignedToInhabitantEvent {
             workers_entity_ids: vec![couple.from],
             building_entity_id: couple.to,
@@ -198,6 +273,235 @@ fn find_job_for_inhabitants(
     }
 }
 
+fn find_school_for_inhabitants(
+    mut entity_storage: ResMut<EntityStorageResource>,
+    navigator: Res<NavigatorResource>,
+    mut school_assigned_writer: EventWriter<SchoolAssignedToInhabitantEvent>,
+) {
+    let couples: Vec<AssignmentResult> = entity_storage.get_inhabitant_school_assignment();
+
+    if couples.is_empty() {
+        return;
+    }
+
+    info!("inhabitants-school assignments {}", couples.len());
+
+    for couple in couples {
+        // TODO consider to have a try not immediately, as for the jobs
+        if navigator
+            .get_navigation_descriptor(&couple.from_position, couple.to_position)
+            .is_none()
+        {
+            entity_storage.resign_assign_result(couple);
+            continue;
+        }
+
+        school_assigned_writer.send(SchoolAssignedToInhabitantEvent {
+            students_entity_ids: vec![couple.from],
+            building_entity_id: couple.to,
+        });
+
+        entity_storage.found_school_for_inhabitant(&couple.from, couple.to, couple.to_position);
+    }
+}
+
+/// The students get closer to the next education level on every tick
+fn study(
+    mut game_tick: EventReader<GameTick>,
+    mut entity_storage: ResMut<EntityStorageResource>,
+    configuration: Res<ConfigurationResource>,
+    mut inhabitant_graduated_writer: EventWriter<InhabitantGraduatedEvent>,
+) {
+    let graduations = (0..GameTick::count(&mut game_tick))
+        .flat_map(|_| entity_storage.study(configuration.buildings.school.ticks_for_level))
+        .collect::<Vec<_>>();
+    for graduation in graduations {
+        info!(
+            "inhabitant {} reached {:?}",
+            graduation.inhabitant_entity_id, graduation.education_level
+        );
+
+        inhabitant_graduated_writer.send(InhabitantGraduatedEvent {
+            inhabitant_entity_id: graduation.inhabitant_entity_id,
+            school_entity_id: graduation.school_entity_id,
+            education_level: graduation.education_level,
+        });
+    }
+}
+
+/// Recompute the happiness of every inhabitant on every tick
+fn update_happiness(
+    mut game_tick: EventReader<GameTick>,
+    mut entity_storage: ResMut<EntityStorageResource>,
+    inhabitant_manager: Res<InhabitantManagerResource>,
+    mut random: ResMut<RandomResource>,
+    power_manager: Res<PowerManagerResource>,
+    mut palatability: ResMut<PalatabilityManagerResource>,
+) {
+    for _ in 0..GameTick::count(&mut game_tick) {
+        let city_happiness = entity_storage.update_happiness(
+            |inhabitant| {
+                let home = inhabitant.get_home().as_ref()?;
+                let house_position = home.get_house_position();
+                let work_place = inhabitant.get_work_place_id().as_ref();
+
+                Some(HappinessFactors {
+                    house_palatability: palatability
+                        .get_palatability_values(house_position)
+                        .house_value,
+                    employed: work_place.is_some(),
+                    studying: inhabitant.get_school().is_some(),
+                    working_age: *inhabitant.get_life_stage() == LifeStage::Adult,
+                    power_coverage: power_manager.get_power_coverage(home.get_house_id()),
+                    commute_distance: work_place.map(|work_place| {
+                        house_position.distance(work_place.get_work_place_position())
+                    }),
+                    taxes: palatability.get_taxes_per_resident(house_position),
+                })
+            },
+            |factors| inhabitant_manager.calculate_happiness(factors),
+        );
+        entity_storage.find_inhabitants_want_to_leave(|inhabitant| {
+            let house_position = inhabitant.get_home().as_ref()?.get_house_position();
+            let house_palatability = palatability
+                .get_palatability_values(house_position)
+                .house_value;
+            inhabitant_manager.emigration_reason(&mut random, inhabitant, house_palatability)
+        });
+
+        palatability.set_city_happiness(city_happiness);
+        debug!("city happiness: {:?}", palatability.city_happiness());
+    }
+}
+
+/// Every year the inhabitants grow old: the workers retire, the old inhabitants die
+/// and the children are born in the households with free spots at home
+#[allow(clippy::too_many_arguments)]
+fn age_population(
+    mut commands: Commands,
+    mut game_tick: EventReader<GameTick>,
+    mut entity_storage: ResMut<EntityStorageResource>,
+    mut inhabitant_manager: ResMut<InhabitantManagerResource>,
+    mut random: ResMut<RandomResource>,
+    configuration: Res<ConfigurationResource>,
+    mut inhabitant_born_writer: EventWriter<InhabitantBornEvent>,
+    mut inhabitant_retired_writer: EventWriter<InhabitantRetiredEvent>,
+) {
+    let ticks = GameTick::count(&mut game_tick);
+    let years = (0..ticks).filter(|_| inhabitant_manager.new_year()).count();
+    if years == 0 {
+        return;
+    }
+
+    for _ in 0..years {
+        let retirements = entity_storage.grow_old(&configuration.population, |inhabitant| {
+            inhabitant_manager.dies(&mut random, *inhabitant.get_age())
+        });
+        for retirement in retirements {
+            info!("inhabitant {} retired", retirement.inhabitant_entity_id);
+
+            inhabitant_retired_writer.send(InhabitantRetiredEvent {
+                inhabitant_entity_id: retirement.inhabitant_entity_id,
+                work_place_entity_id: retirement.work_place_entity_id,
+            });
+        }
+
+        for household_id in entity_storage.get_households_for_births() {
+            if !inhabitant_manager.gives_birth(&mut random) {
+                continue;
+            }
+
+            let entity = commands.spawn_empty().insert(InhabitantComponent).id();
+            let house_id =
+                entity_storage.give_birth(Inhabitant::newborn(entity.to_bits()), &household_id);
+
+            inhabitant_born_writer.send(InhabitantBornEvent {
+                inhabitant_entity_id: entity.to_bits(),
+                house_entity_id: house_id,
+            });
+        }
+    }
+
+    let population_statistics = entity_storage.population_statistics();
+    inhabitant_manager.set_population_statistics(population_statistics);
+    debug!(
+        "population: {:?}",
+        inhabitant_manager.population_statistics()
+    );
+}
+
+/// The inhabitants that want to leave move out of the city.
+/// It runs before the assignments, so their arrival is always handled before their departure
+fn emigrate(
+    mut commands: Commands,
+    mut entity_storage: ResMut<EntityStorageResource>,
+    mut inhabitant_left_writer: EventWriter<InhabitantLeftEvent>,
+) {
+    for (inhabitant, reason) in entity_storage.remove_emigrants() {
+        inhabitant_left_writer.send(leave_city(&mut commands, &inhabitant, reason));
+    }
+}
+
+/// The residents of the demolished buildings leave the city,
+/// the workers and the students look for another place
+fn evict_inhabitants(
+    mut building_demolished_reader: EventReader<BuildingDemolishedEvent>,
+    mut commands: Commands,
+    mut entity_storage: ResMut<EntityStorageResource>,
+    mut inhabitant_left_writer: EventWriter<InhabitantLeftEvent>,
+) {
+    for demolished in building_demolished_reader.iter() {
+        let building_entity_id = *demolished.building_snapshot.get_id();
+        commands
+            .entity(Entity::from_bits(building_entity_id))
+            .remove::<TargetComponent>()
+            .remove::<TargetTypeHouse>()
+            .remove::<TargetTypeOffice>()
+            .remove::<TargetTypeSchool>();
+
+        for inhabitant in entity_storage.demolish(&building_entity_id) {
+            inhabitant_left_writer.send(leave_city(
+                &mut commands,
+                &inhabitant,
+                LeaveReason::Evicted,
+            ));
+        }
+    }
+}
+
+/// Despawn the inhabitant that left the city, with or without a home
+fn leave_city(
+    commands: &mut Commands,
+    inhabitant: &Inhabitant,
+    reason: LeaveReason,
+) -> InhabitantLeftEvent {
+    info!(
+        "inhabitant {} left the city: {reason:?}",
+        inhabitant.get_id()
+    );
+
+    commands
+        .entity(Entity::from_bits(*inhabitant.get_id()))
+        .despawn();
+
+    InhabitantLeftEvent {
+        inhabitant_entity_id: *inhabitant.get_id(),
+        house_entity_id: inhabitant
+            .get_home()
+            .as_ref()
+            .map(|home| *home.get_house_id()),
+        work_place_entity_id: inhabitant
+            .get_work_place_id()
+            .as_ref()
+            .map(|work_place| *work_place.get_work_place_id()),
+        school_entity_id: inhabitant
+            .get_school()
+            .as_ref()
+            .map(|school| *school.get_school_id()),
+        reason,
+    }
+}
+
 mod resources {
     use std::ops::{Deref, DerefMut};
 
@@ -242,7 +546,10 @@ mod resources {
 
 pub mod events {
 
-    use crate::common::{position::Position, EntityId};
+    use crate::{
+        common::{enums::EducationLevel, position::Position, EntityId},
+        inhabitant::manager::LeaveReason,
+    };
 
     pub struct HomeAssignedToInhabitantEvent {
         pub inhabitants_entity_ids: Vec<EntityId>,
@@ -254,6 +561,37 @@ pub mod events {
         pub workers_entity_ids: Vec<EntityId>,
         pub building_entity_id: EntityId,
     }
+
+    pub struct InhabitantLeftEvent {
+        pub inhabitant_entity_id: EntityId,
+        /// `None` if still looking for a home
+        pub house_entity_id: Option<EntityId>,
+        pub work_place_entity_id: Option<EntityId>,
+        pub school_entity_id: Option<EntityId>,
+        pub reason: LeaveReason,
+    }
+
+    /// The child lives in the house of the parents
+    pub struct InhabitantBornEvent {
+        pub inhabitant_entity_id: EntityId,
+        pub house_entity_id: EntityId,
+    }
+
+    pub struct InhabitantRetiredEvent {
+        pub inhabitant_entity_id: EntityId,
+        pub work_place_entity_id: EntityId,
+    }
+
+    pub struct SchoolAssignedToInhabitantEvent {
+        pub students_entity_ids: Vec<EntityId>,
+        pub building_entity_id: EntityId,
+    }
+
+    pub struct InhabitantGraduatedEvent {
+        pub inhabitant_entity_id: EntityId,
+        pub school_entity_id: EntityId,
+        pub education_level: EducationLevel,
+    }
 }
 
 mod components {
@@ -268,6 +606,7 @@ mod components {
     pub enum TargetType {
         Office,
         House,
+        School,
     }
 
     #[derive(Component, Copy, Clone, Debug)]
@@ -276,6 +615,9 @@ mod components {
     #[derive(Component, Copy, Clone, Debug)]
     pub struct TargetTypeHouse;
 
+    #[derive(Component, Copy, Clone, Debug)]
+    pub struct TargetTypeSchool;
+
     #[derive(Component, Debug)]
     pub struct TargetComponent {
         // pub needed_count: usize,
@@ -1,9 +1,13 @@
 #![feature(const_fn_floating_point_arithmetic)]
+#![cfg_attr(test, feature(test))]
 
 mod building;
+mod clock;
+mod command;
 mod common;
 mod inhabitant;
 mod navigation;
+mod overlay;
 mod palatability;
 mod power;
 
@@ -12,23 +16,62 @@ mod e2e_test;
 
 use std::{collections::HashSet, sync::Arc};
 
-use bevy::{input::keyboard::KeyboardInput, prelude::*, render::camera::ScalingMode, time::Time};
+use bevy::{
+    app::ScheduleRunnerPlugin,
+    input::keyboard::KeyboardInput,
+    prelude::*,
+    render::{camera::ScalingMode, settings::WgpuSettings},
+    time::Time,
+    winit::WinitPlugin,
+};
 use bevy_mod_picking::*;
 
 use building::BuildingPlugin;
-use common::configuration::CONFIGURATION;
+use clock::ClockPlugin;
+use command::{events::GameCommandEvent, CommandMode, CommandPlugin, GameCommand};
+use common::{configuration::CONFIGURATION, executor::OrderedExecutor, random::Random};
 use inhabitant::InhabitantPlugin;
 use navigation::NavigatorPlugin;
+use overlay::OverlayPlugin;
 use palatability::PalatabilityPlugin;
 use power::PowerPlugin;
-use resources::ConfigurationResource;
+use resources::{ConfigurationResource, RandomResource};
 use tracing::debug;
 
 #[derive(Component, Deref, DerefMut)]
 struct GameTimer(Timer);
+/// Sent when some ticks passed: more than one if the simulation is fast
 #[derive(Component)]
 struct GameTick(u32);
 
+impl GameTick {
+    /// The ticks passed since the last read
+    fn count(game_tick: &mut EventReader<GameTick>) -> u32 {
+        game_tick.iter().map(|tick| tick.0).sum()
+    }
+}
+
+/// How fast the game time flows
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum SimulationSpeed {
+    Paused,
+    #[default]
+    Normal,
+    Fast,
+    Ultra,
+}
+
+impl SimulationSpeed {
+    fn multiplier(&self) -> u32 {
+        match self {
+            SimulationSpeed::Paused => 0,
+            SimulationSpeed::Normal => 1,
+            SimulationSpeed::Fast => 2,
+            SimulationSpeed::Ultra => 4,
+        }
+    }
+}
+
 #[derive(Resource)]
 struct PbrBundles {
     house: PbrBundle,
@@ -36,6 +79,8 @@ struct PbrBundles {
     garden: PbrBundle,
     office: PbrBundle,
     biomass_power_plant: PbrBundle,
+    battery: PbrBundle,
+    school: PbrBundle,
     in_progress: PbrBundle,
 }
 impl PbrBundles {
@@ -54,6 +99,12 @@ impl PbrBundles {
     pub fn biomass_power_plant(&self) -> PbrBundle {
         self.biomass_power_plant.clone()
     }
+    pub fn battery(&self) -> PbrBundle {
+        self.battery.clone()
+    }
+    pub fn school(&self) -> PbrBundle {
+        self.school.clone()
+    }
     pub fn in_progress(&self) -> PbrBundle {
         self.in_progress.clone()
     }
@@ -100,6 +151,8 @@ impl FromWorld for PbrBundles {
         let in_progress = get_colored_plane!(plane world, configuration, 33, 33, 33);
         let office = get_colored_plane!(plane world, configuration, 31, 125, 219);
         let biomass_power_plant = get_colored_plane!(plane world, configuration, 197, 34, 34);
+        let battery = get_colored_plane!(plane world, configuration, 230, 190, 40);
+        let school = get_colored_plane!(plane world, configuration, 140, 80, 200);
 
         PbrBundles {
             house,
@@ -108,15 +161,49 @@ impl FromWorld for PbrBundles {
             in_progress,
             office,
             biomass_power_plant,
+            battery,
+            school,
         }
     }
 }
 
+#[derive(Resource)]
+struct UiFont(Handle<Font>);
+
+impl FromWorld for UiFont {
+    fn from_world(world: &mut World) -> Self {
+        let asset_server = world.resource::<AssetServer>();
+        UiFont(asset_server.load("fonts/DejaVuSansMono.ttf"))
+    }
+}
+
 fn main() {
-    App::new()
-        .add_plugins(DefaultPlugins)
-        .add_plugin(DebugCursorPickingPlugin) // <- Adds the green debug cursor.
-        .insert_resource(ConfigurationResource(Arc::new(CONFIGURATION)))
+    let command_mode = CommandMode::from_args(std::env::args().skip(1));
+
+    let mut app = App::new();
+    if command_mode.is_headless() {
+        // No window and no GPU: the meshes and the materials stay as assets
+        app.insert_resource(WgpuSettings {
+            backends: None,
+            ..default()
+        })
+        .add_plugins(
+            DefaultPlugins
+                .set(WindowPlugin {
+                    add_primary_window: false,
+                    // Without windows it would close at once: it ends with the replay
+                    exit_on_all_closed: false,
+                    ..default()
+                })
+                .disable::<WinitPlugin>(),
+        )
+        .add_plugin(ScheduleRunnerPlugin);
+    } else {
+        app.add_plugins(DefaultPlugins)
+            .add_plugin(DebugCursorPickingPlugin); // <- Adds the green debug cursor.
+    }
+    app.insert_resource(ConfigurationResource(Arc::new(CONFIGURATION)))
+        .insert_resource(command_mode)
         .add_plugin(MainPlugin)
         .run();
 }
@@ -125,27 +212,83 @@ pub struct MainPlugin;
 
 impl Plugin for MainPlugin {
     fn build(&self, app: &mut App) {
-        app.add_startup_system(setup)
+        let configuration: &ConfigurationResource = app.world.resource();
+        let random = RandomResource(Random::new(configuration.game.seed));
+
+        // Given the seed and the commands, every run gives the same city
+        for stage in [
+            CoreStage::First,
+            CoreStage::PreUpdate,
+            CoreStage::Update,
+            CoreStage::PostUpdate,
+            CoreStage::Last,
+        ] {
+            app.stage(stage, |stage: &mut SystemStage| {
+                stage.set_executor(Box::<OrderedExecutor>::default());
+                stage
+            });
+        }
+
+        app.insert_resource(random)
+            .add_startup_system(setup)
             .add_event::<GameTick>()
+            .init_resource::<SimulationSpeed>()
+            .add_system_to_stage(CoreStage::PreUpdate, change_simulation_speed)
             .add_system_to_stage(CoreStage::Update, tick)
             .add_system_to_stage(CoreStage::PostUpdate, move_camera_on_keyboard_input)
             .init_resource::<PbrBundles>()
+            .init_resource::<UiFont>()
+            .add_plugin(CommandPlugin)
+            .add_plugin(ClockPlugin)
             .add_plugin(BuildingPlugin)
             .add_plugin(NavigatorPlugin)
             .add_plugin(InhabitantPlugin)
             .add_plugin(PalatabilityPlugin)
-            .add_plugin(PowerPlugin);
+            .add_plugin(PowerPlugin)
+            .add_plugin(OverlayPlugin);
     }
 }
 
-/// Send game tick: realtime is just an interpolation of discrete time
+/// Pause, speed up or step the simulation on command: the step works only while paused
+fn change_simulation_speed(
+    mut commands: EventReader<GameCommandEvent>,
+    mut simulation_speed: ResMut<SimulationSpeed>,
+    mut my_events: EventWriter<GameTick>,
+) {
+    for GameCommandEvent { command } in commands.iter() {
+        let speed = match command {
+            GameCommand::SetSimulationSpeed(speed) => *speed,
+            GameCommand::Step if *simulation_speed == SimulationSpeed::Paused => {
+                debug!("step!");
+                my_events.send(GameTick(1));
+                continue;
+            }
+            _ => continue,
+        };
+
+        info!("simulation speed: {speed:?}");
+        *simulation_speed = speed;
+    }
+}
+
+/// Send game tick: realtime is just an interpolation of discrete time.
+/// A headless replay sends its own ticks
 fn tick(
     time: Res<Time>,
+    simulation_speed: Res<SimulationSpeed>,
+    command_mode: Option<Res<CommandMode>>,
     mut game_timers: Query<&mut GameTimer>,
     mut my_events: EventWriter<GameTick>,
 ) {
+    if *simulation_speed == SimulationSpeed::Paused
+        || command_mode.is_some_and(|mode| mode.is_headless())
+    {
+        return;
+    }
+
     let mut game_timers = game_timers.single_mut();
-    if !game_timers.tick(time.delta()).finished() {
+    let delta = time.delta() * simulation_speed.multiplier();
+    if !game_timers.tick(delta).finished() {
         return;
     }
 
@@ -242,11 +385,14 @@ fn setup(mut commands: Commands) {
 }
 
 pub mod resources {
-    use std::{ops::Deref, sync::Arc};
+    use std::{
+        ops::{Deref, DerefMut},
+        sync::Arc,
+    };
 
     use bevy::prelude::Resource;
 
-    use crate::common::configuration::Configuration;
+    use crate::common::{configuration::Configuration, random::Random};
 
     #[derive(Resource)]
     pub struct ConfigurationResource(pub Arc<Configuration>);
@@ -258,4 +404,22 @@ pub mod resources {
             &self.0
         }
     }
+
+    /// The only source of randomness of the simulation
+    #[derive(Resource)]
+    pub struct RandomResource(pub Random);
+
+    impl Deref for RandomResource {
+        type Target = Random;
+
+        fn deref(&self) -> &Self::Target {
+            &self.0
+        }
+    }
+
+    impl DerefMut for RandomResource {
+        fn deref_mut(&mut self) -> &mut Self::Target {
+            &mut self.0
+        }
+    }
 }
@@ -1,22 +1,37 @@
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, BTreeSet},
     fmt::Display,
 };
 
 use tracing::info;
 
-use crate::common::position::Position;
+use crate::common::{position::Position, EntityId};
+
+/// Beyond it the cached distances are computed again from scratch
+const MAX_CACHED_DISTANCES: usize = 16_384;
 
 pub struct Navigator {
-    positions_to_add: HashSet<Position>,
-    nodes: HashMap<Position, HashSet<Position>>,
+    positions_to_add: BTreeSet<Position>,
+    nodes: BTreeMap<Position, BTreeSet<Position>>,
+    // Number of commuters passing on every street
+    traffic: BTreeMap<Position, u32>,
+    /// The streets every worker goes along, to take them back when the commute ends
+    commutes: BTreeMap<EntityId, Commute>,
+    /// The tiles where the streets give access to the buildings
+    entrances: BTreeMap<Position, BTreeSet<Position>>,
+    /// The path lengths already computed, until the streets or their ends change
+    distances: BTreeMap<(Position, Position), Option<u32>>,
 }
 impl Navigator {
     pub(super) fn new() -> Self {
-        let nodes: HashMap<Position, HashSet<Position>> = Default::default();
+        let nodes: BTreeMap<Position, BTreeSet<Position>> = Default::default();
         Self {
             positions_to_add: Default::default(),
             nodes,
+            traffic: Default::default(),
+            commutes: Default::default(),
+            entrances: Default::default(),
+            distances: Default::default(),
         }
     }
 
@@ -24,6 +39,63 @@ impl Navigator {
         self.positions_to_add.insert(position);
     }
 
+    /// The street is gone: nobody passes through it anymore
+    pub(super) fn remove_node(&mut self, position: &Position) {
+        self.positions_to_add.remove(position);
+        self.traffic.remove(position);
+        self.distances.clear();
+        for commute in self.commutes.values_mut() {
+            commute.streets.retain(|street| street != position);
+        }
+
+        let linked_nodes = self.nodes.remove(position).unwrap_or_default();
+        for node in linked_nodes {
+            if let Some(neighbors) = self.nodes.get_mut(&node) {
+                neighbors.remove(position);
+            }
+        }
+    }
+
+    pub(super) fn add_building(&mut self, position: Position, entrances: BTreeSet<Position>) {
+        if self.entrances.insert(position, entrances.clone()) != Some(entrances) {
+            self.forget_distances_of(&position);
+        }
+    }
+
+    /// The workers of the building don't go there anymore
+    pub(super) fn remove_building(&mut self, position: &Position) {
+        if self.entrances.remove(position).is_some() {
+            self.forget_distances_of(position);
+        }
+
+        let ended: Vec<EntityId> = self
+            .commutes
+            .iter()
+            .filter(|(_, commute)| commute.destination == *position)
+            .map(|(inhabitant_id, _)| *inhabitant_id)
+            .collect();
+        for inhabitant_id in ended {
+            self.unregister_commute(&inhabitant_id);
+        }
+    }
+
+    /// The paths cross only the streets: a building changes only the paths from and to it
+    fn forget_distances_of(&mut self, position: &Position) {
+        self.distances
+            .retain(|(start, end), _| start != position && end != position);
+    }
+
+    /// The entrances of the building at the position,
+    /// all the neighbors for an unknown position
+    fn access_tiles(&self, position: &Position) -> BTreeSet<Position> {
+        match self.entrances.get(position) {
+            Some(entrances) => entrances.clone(),
+            None => position.neighbors().collect(),
+        }
+    }
+
+    /// The path along the streets, entering them at the entrance of the start
+    /// and leaving them at the entrance of the end
     pub fn get_navigation_descriptor(
         &self,
         start_point: &Position,
@@ -31,16 +103,25 @@ impl Navigator {
     ) -> Option<NavigationDescriptor> {
         use pathfinding::prelude::astar;
 
-        let neighbors: HashSet<_> = end.neighbors().collect();
+        let neighbors = self.access_tiles(&end);
 
         let result = astar(
             start_point,
             |p| {
-                let neighbors = match self.nodes.get(p) {
+                let neighbors: Vec<_> = match self.nodes.get(p) {
+                    Some(n) => n.iter().copied().collect(),
+                    // The start is a building: the streets start from its entrances
+                    None if p == start_point => self
+                        .access_tiles(p)
+                        .into_iter()
+                        .filter(|n| self.nodes.contains_key(n))
+                        .collect(),
                     None => return vec![],
-                    Some(n) => n,
                 };
-                neighbors.iter().map(|p| (*p, 1_i64)).collect::<Vec<_>>()
+                neighbors
+                    .into_iter()
+                    .map(|p| (p, 1_i64))
+                    .collect::<Vec<_>>()
             },
             |p| {
                 let delta_x = if p.x > end.x {
@@ -75,6 +156,99 @@ impl Navigator {
         Some(descriptor)
     }
 
+    /// The length of the path from the start to the end, computed once
+    /// while the streets and the buildings stay the same
+    pub fn get_distance(&mut self, start_point: &Position, end: &Position) -> Option<u32> {
+        if let Some(distance) = self.distances.get(&(*start_point, *end)) {
+            return *distance;
+        }
+        let distance = self
+            .get_navigation_descriptor(start_point, *end)
+            .map(|descriptor| descriptor.length());
+        if self.distances.len() >= MAX_CACHED_DISTANCES {
+            self.distances.clear();
+        }
+        self.distances.insert((*start_point, *end), distance);
+        distance
+    }
+
+    /// Count a commuter on every street of the path.
+    /// A new job replaces the previous commute of the inhabitant
+    pub fn register_commute(&mut self, inhabitant_id: EntityId, descriptor: &NavigationDescriptor) {
+        self.unregister_commute(&inhabitant_id);
+
+        let streets: Vec<Position> = descriptor
+            .path
+            .iter()
+            .filter(|position| self.nodes.contains_key(position))
+            .copied()
+            .collect();
+        for position in &streets {
+            *self.traffic.entry(*position).or_default() += 1;
+        }
+
+        // The path is reversed: the destination comes first
+        let destination = descriptor.path[0];
+        self.commutes.insert(
+            inhabitant_id,
+            Commute {
+                destination,
+                streets,
+            },
+        );
+    }
+
+    /// The inhabitant doesn't go to work anymore
+    pub fn unregister_commute(&mut self, inhabitant_id: &EntityId) {
+        let commute = match self.commutes.remove(inhabitant_id) {
+            None => return,
+            Some(commute) => commute,
+        };
+        for position in commute.streets {
+            if let Some(commuters) = self.traffic.get_mut(&position) {
+                *commuters -= 1;
+                if *commuters == 0 {
+                    self.traffic.remove(&position);
+                }
+            }
+        }
+    }
+
+    pub fn get_traffic(&self) -> &BTreeMap<Position, u32> {
+        &self.traffic
+    }
+
+    /// The Manhattan distance to the nearest street, if not farther than `max_distance`
+    pub fn get_distance_to_nearest_street(
+        &self,
+        position: &Position,
+        max_distance: u32,
+    ) -> Option<u32> {
+        let is_street =
+            |p: &Position| self.nodes.contains_key(p) || self.positions_to_add.contains(p);
+
+        (0..=max_distance).find(|distance| {
+            let distance = i64::from(*distance);
+            (-distance..=distance).any(|dx| {
+                let dy = distance - dx.abs();
+                is_street(&Position {
+                    x: position.x + dx,
+                    y: position.y + dy,
+                }) || is_street(&Position {
+                    x: position.x + dx,
+                    y: position.y - dy,
+                })
+            })
+        })
+    }
+
+    /// A street is on one of the entrances
+    pub fn reaches_street(&self, entrances: &BTreeSet<Position>) -> bool {
+        entrances
+            .iter()
+            .any(|p| self.nodes.contains_key(p) || self.positions_to_add.contains(p))
+    }
+
     pub(super) fn rebuild(&mut self) -> usize {
         let positions_to_add = std::mem::take(&mut self.positions_to_add);
         let tot = positions_to_add.len();
@@ -100,31 +274,31 @@ impl Navigator {
             }
         }
 
-        tot - self.positions_to_add.len()
+        let added = tot - self.positions_to_add.len();
+        if added > 0 {
+            self.distances.clear();
+        }
+        added
     }
 }
 
-/*
-pub trait Reachable {
-    // TODO: this probably is wrong
-    // In the future we might have building that has more than one position
-    // (ie occupant more that 1 square)
-    // For the time being KISS
-    fn to_position(&self) -> Position;
+struct Commute {
+    destination: Position,
+    streets: Vec<Position>,
 }
 
-impl Reachable for HouseSnapshot {
-    fn to_position(&self) -> Position {
-        self.position
-    }
-}
-*/
-
 #[derive(Debug, PartialEq, Eq)]
 pub struct NavigationDescriptor {
     path: Vec<Position>,
 }
 
+impl NavigationDescriptor {
+    /// The number of tiles of the path
+    pub fn length(&self) -> u32 {
+        self.path.len() as u32
+    }
+}
+
 impl Display for NavigationDescriptor {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "path length {}", self.path.len())
@@ -134,6 +308,8 @@ impl Display for NavigationDescriptor {
 #[cfg(test)]
 mod tests {
 
+    use crate::common::position::{Footprint, Orientation};
+
     use super::*;
 
     #[test]
@@ -202,4 +378,219 @@ mod tests {
 
         assert_eq!(resolved, 3);
     }
+
+    #[test]
+    fn test_remove_node() {
+        let mut navigator = Navigator::new();
+        navigator.add_node(Position { x: 0, y: 0 });
+        navigator.add_node(Position { x: 1, y: 0 });
+        navigator.add_node(Position { x: 2, y: 0 });
+        navigator.rebuild();
+
+        let start = Position { x: 0, y: 0 };
+        let end = Position { x: 2, y: 1 };
+        assert!(navigator.get_navigation_descriptor(&start, end).is_some());
+
+        navigator.remove_node(&Position { x: 1, y: 0 });
+        assert_eq!(navigator.get_navigation_descriptor(&start, end), None);
+        assert_eq!(
+            navigator.get_distance_to_nearest_street(&Position { x: 1, y: 0 }, 0),
+            None
+        );
+    }
+
+    #[test]
+    fn test_navigate_to_footprint() {
+        let mut navigator = Navigator::new();
+        navigator.add_node(Position { x: 3, y: 0 });
+        navigator.add_node(Position { x: 3, y: 1 });
+        navigator.add_node(Position { x: 3, y: 2 });
+        navigator.add_node(Position { x: 3, y: 3 });
+        navigator.rebuild();
+
+        let start = Position { x: 3, y: 0 };
+        let building = Position { x: 1, y: 1 };
+        let footprint = Footprint { width: 2, depth: 2 };
+        assert_eq!(navigator.get_navigation_descriptor(&start, building), None);
+        assert!(navigator.reaches_street(&footprint.neighbors(&building)));
+        assert!(!navigator.reaches_street(&Footprint::SINGLE.neighbors(&building)));
+
+        navigator.add_building(building, footprint.neighbors(&building));
+        assert!(navigator
+            .get_navigation_descriptor(&start, building)
+            .is_some());
+        // The building is also left from the streets next to it
+        assert!(navigator
+            .get_navigation_descriptor(&building, Position { x: 4, y: 3 })
+            .is_some());
+
+        navigator.remove_building(&building);
+        assert_eq!(navigator.get_navigation_descriptor(&start, building), None);
+    }
+
+    #[test]
+    fn test_navigate_through_entrance() {
+        let mut navigator = Navigator::new();
+        navigator.add_node(Position { x: 0, y: 0 });
+        navigator.add_node(Position { x: 1, y: 0 });
+        navigator.add_node(Position { x: 2, y: 0 });
+        navigator.rebuild();
+
+        let start = Position { x: 0, y: 0 };
+        let building = Position { x: 1, y: 1 };
+        let entrances = |orientation| Footprint::SINGLE.front(&building, orientation);
+
+        // Facing away from the street
+        navigator.add_building(building, entrances(Orientation::South));
+        assert!(!navigator.reaches_street(&entrances(Orientation::South)));
+        assert_eq!(navigator.get_navigation_descriptor(&start, building), None);
+
+        navigator.add_building(building, entrances(Orientation::North));
+        assert!(navigator.reaches_street(&entrances(Orientation::North)));
+        assert!(navigator
+            .get_navigation_descriptor(&start, building)
+            .is_some());
+    }
+
+    #[test]
+    fn test_register_commute() {
+        let mut navigator = Navigator::new();
+        navigator.add_node(Position { x: 0, y: 0 });
+        navigator.add_node(Position { x: 1, y: 0 });
+        navigator.add_node(Position { x: 2, y: 0 });
+        navigator.rebuild();
+
+        let desc = navigator
+            .get_navigation_descriptor(&Position { x: 0, y: 0 }, Position { x: 2, y: 1 })
+            .unwrap();
+        navigator.register_commute(1, &desc);
+        navigator.register_commute(2, &desc);
+
+        // The destination is not a street
+        assert_eq!(navigator.get_traffic().get(&Position { x: 2, y: 1 }), None);
+        assert_eq!(
+            navigator.get_traffic().get(&Position { x: 2, y: 0 }),
+            Some(&2)
+        );
+        assert_eq!(
+            navigator.get_traffic().get(&Position { x: 0, y: 0 }),
+            Some(&2)
+        );
+    }
+
+    #[test]
+    fn test_unregister_commute() {
+        let mut navigator = Navigator::new();
+        navigator.add_node(Position { x: 0, y: 0 });
+        navigator.add_node(Position { x: 1, y: 0 });
+        navigator.add_node(Position { x: 2, y: 0 });
+        navigator.rebuild();
+
+        let office = Position { x: 2, y: 1 };
+        let other_office = Position { x: 0, y: 1 };
+        let to_office = navigator
+            .get_navigation_descriptor(&Position { x: 0, y: 0 }, office)
+            .unwrap();
+        let to_other_office = navigator
+            .get_navigation_descriptor(&Position { x: 0, y: 0 }, other_office)
+            .unwrap();
+        let traffic =
+            |navigator: &Navigator, x| navigator.get_traffic().get(&Position { x, y: 0 }).copied();
+
+        navigator.register_commute(1, &to_office);
+        navigator.register_commute(2, &to_office);
+        navigator.register_commute(3, &to_office);
+        assert_eq!(traffic(&navigator, 2), Some(3));
+
+        // The job changes
+        navigator.register_commute(1, &to_other_office);
+        assert_eq!(traffic(&navigator, 2), Some(2));
+        assert_eq!(traffic(&navigator, 0), Some(3));
+
+        // The worker leaves or retires
+        navigator.unregister_commute(&2);
+        navigator.unregister_commute(&2);
+        assert_eq!(traffic(&navigator, 2), Some(1));
+
+        // The commuters of a demolished street are not taken back from the new one
+        navigator.remove_node(&Position { x: 1, y: 0 });
+        navigator.add_node(Position { x: 1, y: 0 });
+        navigator.rebuild();
+        navigator.register_commute(4, &to_office);
+        navigator.unregister_commute(&3);
+        assert_eq!(traffic(&navigator, 1), Some(1));
+        navigator.unregister_commute(&1);
+
+        // The office is demolished
+        navigator.remove_building(&office);
+        assert!(navigator.get_traffic().is_empty());
+    }
+
+    #[test]
+    fn test_distance_follows_the_streets() {
+        let mut navigator = Navigator::new();
+        navigator.add_node(Position { x: 0, y: 0 });
+        navigator.add_node(Position { x: 1, y: 0 });
+        navigator.rebuild();
+
+        let start = Position { x: 0, y: 0 };
+        let end = Position { x: 3, y: 1 };
+        assert_eq!(navigator.get_distance(&start, &end), None);
+
+        navigator.add_node(Position { x: 2, y: 0 });
+        navigator.add_node(Position { x: 3, y: 0 });
+        navigator.rebuild();
+        let distance = navigator.get_distance(&start, &end);
+        assert_eq!(
+            distance,
+            navigator
+                .get_navigation_descriptor(&start, end)
+                .map(|descriptor| descriptor.length())
+        );
+        assert!(distance.is_some());
+
+        navigator.remove_node(&Position { x: 2, y: 0 });
+        assert_eq!(navigator.get_distance(&start, &end), None);
+    }
+
+    #[test]
+    fn test_distance_survives_unrelated_buildings() {
+        let mut navigator = Navigator::new();
+        navigator.add_node(Position { x: 0, y: 0 });
+        navigator.add_node(Position { x: 1, y: 0 });
+        navigator.add_node(Position { x: 2, y: 0 });
+        navigator.rebuild();
+
+        let start = Position { x: 0, y: 1 };
+        let end = Position { x: 2, y: 1 };
+        navigator.add_building(start, Footprint::SINGLE.neighbors(&start));
+        navigator.add_building(end, Footprint::SINGLE.neighbors(&end));
+        assert!(navigator.get_distance(&start, &end).is_some());
+
+        let elsewhere = Position { x: 1, y: 1 };
+        navigator.add_building(elsewhere, Footprint::SINGLE.neighbors(&elsewhere));
+        navigator.remove_building(&elsewhere);
+        // The same entrances again
+        navigator.add_building(end, Footprint::SINGLE.neighbors(&end));
+        assert!(navigator.distances.contains_key(&(start, end)));
+
+        // Facing away from the streets
+        navigator.add_building(end, Footprint::SINGLE.front(&end, Orientation::South));
+        assert!(!navigator.distances.contains_key(&(start, end)));
+        assert_eq!(navigator.get_distance(&start, &end), None);
+    }
+
+    #[test]
+    fn test_distance_to_nearest_street() {
+        let mut navigator = Navigator::new();
+        navigator.add_node(Position { x: 0, y: 0 });
+        navigator.add_node(Position { x: 1, y: 0 });
+        navigator.rebuild();
+
+        let distance = |x, y| navigator.get_distance_to_nearest_street(&Position { x, y }, 3);
+        assert_eq!(distance(0, 0), Some(0));
+        assert_eq!(distance(1, 1), Some(1));
+        assert_eq!(distance(3, -1), Some(3));
+        assert_eq!(distance(-2, 2), None);
+    }
 }
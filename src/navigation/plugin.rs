@@ -3,7 +3,8 @@ use bevy::prelude::*;
 use crate::building::BuildingSnapshot;
 use crate::common::position::Position;
 
-use crate::building::events::BuildingCreatedEvent;
+use crate::building::events::{BuildingCreatedEvent, BuildingDemolishedEvent};
+use crate::inhabitant::events::{InhabitantLeftEvent, InhabitantRetiredEvent};
 
 use super::navigator::Navigator;
 
@@ -20,7 +21,9 @@ impl Plugin for NavigatorPlugin {
 
         app.insert_resource(navigator)
             // .add_system(new_building_created)
-            .add_system(expand_navigator_graph);
+            .add_system(expand_navigator_graph)
+            .add_system(shrink_navigator_graph.after(expand_navigator_graph))
+            .add_system(end_commutes);
         // .add_system(tag_inhabitants_for_waiting_for_work)
         // .add_system(assign_waiting_for)
         // .add_system_to_stage(CoreStage::Last, add_node)
@@ -44,10 +47,14 @@ fn expand_navigator_graph(
 
                 need_to_rebuild = true;
             }
-            BuildingSnapshot::House(_) => {}
-            BuildingSnapshot::Office(_) => {}
-            BuildingSnapshot::Garden(_) => {}
-            BuildingSnapshot::BiomassPowerPlant(_) => {}
+            BuildingSnapshot::House(_)
+            | BuildingSnapshot::Office(_)
+            | BuildingSnapshot::Garden(_)
+            | BuildingSnapshot::BiomassPowerPlant(_)
+            | BuildingSnapshot::Battery(_)
+            | BuildingSnapshot::School(_) => {
+                navigator.add_building(*building_position, created_building.entrances.clone());
+            }
         }
     }
 
@@ -59,6 +66,36 @@ fn expand_navigator_graph(
     }
 }
 
+/// The demolished streets leave the graph, the other buildings are not reached anymore
+fn shrink_navigator_graph(
+    mut building_demolished_reader: EventReader<BuildingDemolishedEvent>,
+    mut navigator: ResMut<NavigatorResource>,
+) {
+    for demolished_building in building_demolished_reader.iter() {
+        match &demolished_building.building_snapshot {
+            BuildingSnapshot::Street(street) => {
+                info!("removing node at {:?}", street.position);
+                navigator.remove_node(&street.position);
+            }
+            building_snapshot => navigator.remove_building(building_snapshot.get_position()),
+        }
+    }
+}
+
+/// The workers that left the city or retired don't pass on the streets anymore
+fn end_commutes(
+    mut inhabitant_left_reader: EventReader<InhabitantLeftEvent>,
+    mut inhabitant_retired_reader: EventReader<InhabitantRetiredEvent>,
+    mut navigator: ResMut<NavigatorResource>,
+) {
+    for left in inhabitant_left_reader.iter() {
+        navigator.unregister_commute(&left.inhabitant_entity_id);
+    }
+    for retired in inhabitant_retired_reader.iter() {
+        navigator.unregister_commute(&retired.inhabitant_entity_id);
+    }
+}
+
 mod resources {
     use std::ops::{Deref, DerefMut};
 
@@ -0,0 +1,7 @@
+mod palatability;
+mod placement;
+mod plugin;
+mod power;
+
+pub use palatability::HoveredTile;
+pub use plugin::OverlayPlugin;
@@ -0,0 +1,386 @@
+use std::fmt::Write;
+
+use bevy::prelude::*;
+use bevy_mod_picking::{HoverEvent, PickingEvent};
+
+use crate::{
+    building::PlaneComponent,
+    common::position::Position,
+    palatability::{PalatabilityManagerResource, PalatabilityValues},
+    GameTick, UiFont,
+};
+
+use super::plugin::OverlayMode;
+
+pub use self::components::*;
+pub use self::resources::*;
+
+/// Number of colours for each side of the scale
+const SCALE_STEPS: i32 = 5;
+const SWATCH_SIZE: f32 = 16.;
+
+/// Tint the tiles by their palatability
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn draw_palatability_overlay(
+    mut commands: Commands,
+    mut game_tick: EventReader<GameTick>,
+    overlay_mode: Res<OverlayMode>,
+    palatability_manager: Res<PalatabilityManagerResource>,
+    materials: Res<PalatabilityOverlayMaterials>,
+    planes: Query<(&PlaneComponent, &Transform)>,
+    overlay_items: Query<Entity, With<PalatabilityOverlayComponent>>,
+    mut legend_bounds: Query<&mut Text, With<PalatabilityLegendBoundsComponent>>,
+) {
+    // The palatability changes only on ticks
+    let ticked = game_tick.iter().count() > 0;
+    if !ticked && !overlay_mode.is_changed() {
+        return;
+    }
+
+    for overlay_item in overlay_items.iter() {
+        commands.entity(overlay_item).despawn();
+    }
+
+    let value_of = match palatability_value_of(&overlay_mode) {
+        None => return,
+        Some(value_of) => value_of,
+    };
+
+    let values: Vec<_> = planes
+        .iter()
+        .map(|(plane, transform)| {
+            let values = palatability_manager.get_palatability_values(&plane.0);
+            (value_of(&values), transform)
+        })
+        .collect();
+
+    // The scale is symmetric, so 0 is always in the middle
+    let max = values
+        .iter()
+        .map(|(value, _)| value.abs())
+        .max()
+        .unwrap_or(0)
+        .max(1);
+
+    for (value, transform) in values {
+        let material = materials.scale[scale_step(value, max)].clone();
+
+        commands
+            .spawn(PbrBundle {
+                mesh: materials.tile_mesh.clone(),
+                material,
+                transform: Transform::from_translation(
+                    transform.translation + materials.tile_offset,
+                ),
+                ..default()
+            })
+            .insert(PalatabilityOverlayComponent);
+    }
+
+    for mut text in legend_bounds.iter_mut() {
+        text.sections[0].value = format!("-{max} .. 0 .. +{max}");
+    }
+}
+
+pub fn setup_palatability_legend(
+    mut commands: Commands,
+    font: Res<UiFont>,
+    materials: Res<PalatabilityOverlayMaterials>,
+) {
+    let text_style = TextStyle {
+        font: font.0.clone(),
+        font_size: 16.,
+        color: Color::WHITE,
+    };
+
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    bottom: Val::Px(10.),
+                    left: Val::Px(10.),
+                    ..default()
+                },
+                flex_direction: FlexDirection::Column,
+                ..default()
+            },
+            visibility: Visibility::INVISIBLE,
+            ..default()
+        })
+        .insert(PalatabilityLegendComponent)
+        .with_children(|parent| {
+            parent
+                .spawn(TextBundle::from_section("", text_style.clone()))
+                .insert(PalatabilityLegendTitleComponent);
+
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Row,
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|parent| {
+                    for color in &materials.scale_colors {
+                        parent.spawn(NodeBundle {
+                            style: Style {
+                                size: Size::new(Val::Px(SWATCH_SIZE), Val::Px(SWATCH_SIZE)),
+                                ..default()
+                            },
+                            background_color: (*color).into(),
+                            ..default()
+                        });
+                    }
+                });
+
+            parent
+                .spawn(TextBundle::from_section("", text_style.clone()))
+                .insert(PalatabilityLegendBoundsComponent);
+        });
+
+    commands
+        .spawn(TextBundle::from_section("", text_style).with_style(Style {
+            position_type: PositionType::Absolute,
+            ..default()
+        }))
+        .insert(PalatabilityTooltipComponent);
+}
+
+/// Show the legend only with a palatability overlay
+pub fn update_palatability_legend(
+    overlay_mode: Res<OverlayMode>,
+    mut legends: Query<&mut Visibility, With<PalatabilityLegendComponent>>,
+    mut titles: Query<&mut Text, With<PalatabilityLegendTitleComponent>>,
+) {
+    if !overlay_mode.is_changed() {
+        return;
+    }
+
+    let title = match *overlay_mode {
+        OverlayMode::HousePalatability => Some("House palatability"),
+        OverlayMode::OfficePalatability => Some("Office palatability"),
+        OverlayMode::None | OverlayMode::Power => None,
+    };
+
+    for mut visibility in legends.iter_mut() {
+        visibility.is_visible = title.is_some();
+    }
+    for mut text in titles.iter_mut() {
+        text.sections[0].value = title.unwrap_or_default().to_string();
+    }
+}
+
+/// Keep track of the tile under the cursor
+pub fn track_hovered_tile(
+    mut events: EventReader<PickingEvent>,
+    planes: Query<&PlaneComponent>,
+    mut hovered_tile: ResMut<HoveredTile>,
+) {
+    for event in events.iter() {
+        match event {
+            PickingEvent::Hover(HoverEvent::JustEntered(e)) => {
+                if let Ok(plane) = planes.get(*e) {
+                    hovered_tile.position = Some(plane.0);
+                }
+            }
+            PickingEvent::Hover(HoverEvent::JustLeft(e))
+                if planes.get(*e).map(|p| Some(p.0)) == Ok(hovered_tile.position) =>
+            {
+                hovered_tile.position = None;
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Show the value of the hovered tile and the sources contributing to it next to the cursor
+pub fn update_palatability_tooltip(
+    windows: Res<Windows>,
+    overlay_mode: Res<OverlayMode>,
+    hovered_tile: Res<HoveredTile>,
+    palatability_manager: Res<PalatabilityManagerResource>,
+    mut tooltips: Query<
+        (&mut Text, &mut Style, &mut Visibility),
+        With<PalatabilityTooltipComponent>,
+    >,
+) {
+    let cursor_position = windows.get_primary().and_then(|w| w.cursor_position());
+
+    let description = match (
+        palatability_value_of(&overlay_mode),
+        hovered_tile.position,
+        cursor_position,
+    ) {
+        (Some(value_of), Some(position), Some(_)) => {
+            Some(describe_tile(&palatability_manager, &position, value_of))
+        }
+        _ => None,
+    };
+
+    for (mut text, mut style, mut visibility) in tooltips.iter_mut() {
+        visibility.is_visible = description.is_some();
+
+        if let (Some(description), Some(cursor_position)) = (&description, cursor_position) {
+            if text.sections[0].value != *description {
+                text.sections[0].value = description.clone();
+            }
+            // The cursor position starts from the bottom left corner of the window
+            style.position = UiRect {
+                left: Val::Px(cursor_position.x + 16.),
+                bottom: Val::Px(cursor_position.y + 16.),
+                ..default()
+            };
+        }
+    }
+}
+
+fn describe_tile(
+    palatability_manager: &PalatabilityManagerResource,
+    position: &Position,
+    value_of: fn(&PalatabilityValues) -> i32,
+) -> String {
+    let value = value_of(&palatability_manager.get_palatability_values(position));
+
+    let mut description = format!("({}, {}): {}\n", position.x, position.y, value);
+    for contribution in palatability_manager.get_palatability_contributions(position) {
+        let value = value_of(&contribution.values);
+        if value == 0 {
+            continue;
+        }
+        writeln!(
+            description,
+            "- {} ({}, {}): {:+}",
+            contribution.building_name, contribution.origin.x, contribution.origin.y, value
+        )
+        .expect("write into a String never fails");
+    }
+
+    // Pollution and noise reduce the house palatability
+    let environment = palatability_manager.get_environment(position);
+    if environment.pollution > 0. || environment.noise > 0. {
+        writeln!(
+            description,
+            "pollution: {:.2}\nnoise: {:.2}",
+            environment.pollution, environment.noise
+        )
+        .expect("write into a String never fails");
+    }
+
+    description
+}
+
+fn palatability_value_of(overlay_mode: &OverlayMode) -> Option<fn(&PalatabilityValues) -> i32> {
+    match overlay_mode {
+        OverlayMode::HousePalatability => Some(|v| v.house_value),
+        OverlayMode::OfficePalatability => Some(|v| v.office_value),
+        OverlayMode::None | OverlayMode::Power => None,
+    }
+}
+
+/// The index of the colour in the scale: `SCALE_STEPS` is 0
+fn scale_step(value: i32, max: i32) -> usize {
+    let step = (value as f32 / max as f32 * SCALE_STEPS as f32).round() as i32;
+    (step.clamp(-SCALE_STEPS, SCALE_STEPS) + SCALE_STEPS) as usize
+}
+
+mod resources {
+    use bevy::prelude::*;
+
+    use crate::{common::position::Position, resources::ConfigurationResource};
+
+    use super::SCALE_STEPS;
+
+    /// The tile under the cursor
+    #[derive(Resource, Default)]
+    pub struct HoveredTile {
+        pub position: Option<Position>,
+    }
+
+    #[derive(Resource)]
+    pub struct PalatabilityOverlayMaterials {
+        pub tile_mesh: Handle<Mesh>,
+        pub tile_offset: Vec3,
+        /// From the most negative to the most positive value
+        pub scale: Vec<Handle<StandardMaterial>>,
+        pub scale_colors: Vec<Color>,
+    }
+
+    impl FromWorld for PalatabilityOverlayMaterials {
+        fn from_world(world: &mut World) -> Self {
+            let cube_size = world.resource::<ConfigurationResource>().cube_size;
+
+            let tile_mesh = world
+                .resource_mut::<Assets<Mesh>>()
+                .add(Mesh::from(shape::Plane { size: cube_size }));
+
+            // Diverging scale: red for negative values, white for 0, green for positive ones
+            let scale_colors: Vec<_> = (-SCALE_STEPS..=SCALE_STEPS)
+                .map(|step| {
+                    let t = step.abs() as f32 / SCALE_STEPS as f32;
+                    let fade = 1. - t * 0.8;
+                    if step < 0 {
+                        Color::rgba(0.9, fade, fade, 0.6)
+                    } else {
+                        Color::rgba(fade, 0.9, fade, 0.6)
+                    }
+                })
+                .collect();
+
+            let mut materials = world.resource_mut::<Assets<StandardMaterial>>();
+            let scale = scale_colors
+                .iter()
+                .map(|color| {
+                    materials.add(StandardMaterial {
+                        base_color: *color,
+                        alpha_mode: AlphaMode::Blend,
+                        unlit: true,
+                        ..default()
+                    })
+                })
+                .collect();
+
+            PalatabilityOverlayMaterials {
+                tile_mesh,
+                // Over the planes, under the buildings
+                tile_offset: Vec3::new(0., 0.001, 0.),
+                scale,
+                scale_colors,
+            }
+        }
+    }
+}
+
+mod components {
+    use bevy::prelude::Component;
+
+    #[derive(Component)]
+    pub struct PalatabilityOverlayComponent;
+
+    #[derive(Component)]
+    pub struct PalatabilityLegendComponent;
+
+    #[derive(Component)]
+    pub struct PalatabilityLegendTitleComponent;
+
+    #[derive(Component)]
+    pub struct PalatabilityLegendBoundsComponent;
+
+    #[derive(Component)]
+    pub struct PalatabilityTooltipComponent;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scale_step() {
+        assert_eq!(scale_step(0, 10), SCALE_STEPS as usize);
+        assert_eq!(scale_step(10, 10), 2 * SCALE_STEPS as usize);
+        assert_eq!(scale_step(-10, 10), 0);
+        assert_eq!(scale_step(4, 10), SCALE_STEPS as usize + 2);
+        assert_eq!(scale_step(-4, 10), SCALE_STEPS as usize - 2);
+    }
+}
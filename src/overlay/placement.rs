@@ -0,0 +1,199 @@
+use bevy::prelude::*;
+
+use crate::{
+    building::{
+        check_placement, dragged_placement, placement_transform, BuildingManagerResource, EditMode,
+        PlacementOrientationResource,
+    },
+    command::PlacementDrag,
+    common::position_utils::convert_position_into_bevy_coords,
+    navigation::NavigatorResource,
+    palatability::PalatabilityManagerResource,
+    resources::ConfigurationResource,
+    UiFont,
+};
+
+use super::palatability::HoveredTile;
+
+pub use self::components::*;
+pub use self::resources::*;
+
+pub fn setup_placement_preview(
+    mut commands: Commands,
+    font: Res<UiFont>,
+    materials: Res<PlacementPreviewMaterials>,
+) {
+    commands
+        .spawn(PbrBundle {
+            mesh: materials.plane.clone(),
+            material: materials.valid.clone(),
+            visibility: Visibility { is_visible: false },
+            ..default()
+        })
+        .insert(PlacementPreviewComponent);
+
+    let text_style = TextStyle {
+        font: font.0.clone(),
+        font_size: 16.,
+        color: Color::WHITE,
+    };
+    commands
+        .spawn(TextBundle::from_section("", text_style).with_style(Style {
+            position_type: PositionType::Absolute,
+            ..default()
+        }))
+        .insert(PlacementDragLabelComponent);
+}
+
+/// Show what the edit mode places under the cursor, covering its footprint and turned:
+/// green if it can be built and can work there, red otherwise
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn draw_placement_preview(
+    edit_mode: Res<EditMode>,
+    orientation: Res<PlacementOrientationResource>,
+    hovered_tile: Res<HoveredTile>,
+    building_manager: Res<BuildingManagerResource>,
+    palatability: Res<PalatabilityManagerResource>,
+    navigator: Res<NavigatorResource>,
+    configuration: Res<ConfigurationResource>,
+    materials: Res<PlacementPreviewMaterials>,
+    mut previews: Query<
+        (
+            &mut Transform,
+            &mut Handle<Mesh>,
+            &mut Handle<StandardMaterial>,
+            &mut Visibility,
+        ),
+        With<PlacementPreviewComponent>,
+    >,
+) {
+    let placement = hovered_tile.position.and_then(|position| {
+        let problems = check_placement(
+            &edit_mode,
+            **orientation,
+            &building_manager,
+            &palatability,
+            &navigator,
+            position,
+        )?;
+        let mesh_transform =
+            placement_transform(&edit_mode, **orientation, &building_manager, &configuration)?;
+        Some((position, mesh_transform, problems))
+    });
+
+    for (mut transform, mut mesh, mut material, mut visibility) in previews.iter_mut() {
+        let (position, mesh_transform, problems) = match &placement {
+            None => {
+                visibility.is_visible = false;
+                continue;
+            }
+            Some(placement) => placement,
+        };
+
+        visibility.is_visible = true;
+        transform.translation = convert_position_into_bevy_coords(&configuration, position)
+            + materials.offset
+            + mesh_transform.translation;
+        transform.rotation = mesh_transform.rotation;
+        transform.scale = mesh_transform.scale;
+        *mesh = match *edit_mode {
+            EditMode::House => materials.cube.clone(),
+            _ => materials.plane.clone(),
+        };
+        *material = if problems.is_empty() {
+            materials.valid.clone()
+        } else {
+            materials.invalid.clone()
+        };
+    }
+}
+
+/// While dragging, show next to the cursor how many buildings will be placed and their cost
+pub fn update_placement_drag_label(
+    windows: Res<Windows>,
+    drag: Res<PlacementDrag>,
+    edit_mode: Res<EditMode>,
+    hovered_tile: Res<HoveredTile>,
+    building_manager: Res<BuildingManagerResource>,
+    mut labels: Query<(&mut Text, &mut Style, &mut Visibility), With<PlacementDragLabelComponent>>,
+) {
+    let cursor_position = windows.get_primary().and_then(|w| w.cursor_position());
+
+    let description = match (drag.start, hovered_tile.position) {
+        (Some(from), Some(to)) => dragged_placement(&edit_mode, &building_manager, from, to)
+            .map(|(count, cost)| format!("{count} tiles, cost {cost}")),
+        _ => None,
+    };
+
+    for (mut text, mut style, mut visibility) in labels.iter_mut() {
+        visibility.is_visible = description.is_some() && cursor_position.is_some();
+
+        if let (Some(description), Some(cursor_position)) = (&description, cursor_position) {
+            if text.sections[0].value != *description {
+                text.sections[0].value = description.clone();
+            }
+            // The cursor position starts from the bottom left corner of the window
+            style.position = UiRect {
+                left: Val::Px(cursor_position.x + 16.),
+                bottom: Val::Px(cursor_position.y + 16.),
+                ..default()
+            };
+        }
+    }
+}
+
+mod resources {
+    use bevy::prelude::*;
+
+    use crate::resources::ConfigurationResource;
+
+    #[derive(Resource)]
+    pub struct PlacementPreviewMaterials {
+        pub cube: Handle<Mesh>,
+        pub plane: Handle<Mesh>,
+        pub offset: Vec3,
+        pub valid: Handle<StandardMaterial>,
+        pub invalid: Handle<StandardMaterial>,
+    }
+
+    impl FromWorld for PlacementPreviewMaterials {
+        fn from_world(world: &mut World) -> Self {
+            let cube_size = world.resource::<ConfigurationResource>().cube_size;
+
+            let mut meshes = world.resource_mut::<Assets<Mesh>>();
+            let cube = meshes.add(Mesh::from(shape::Cube { size: cube_size }));
+            let plane = meshes.add(Mesh::from(shape::Plane { size: cube_size }));
+
+            let mut materials = world.resource_mut::<Assets<StandardMaterial>>();
+            let mut translucent = |color| {
+                materials.add(StandardMaterial {
+                    base_color: color,
+                    alpha_mode: AlphaMode::Blend,
+                    unlit: true,
+                    ..default()
+                })
+            };
+            let valid = translucent(Color::rgba(0.2, 0.9, 0.2, 0.5));
+            let invalid = translucent(Color::rgba(0.9, 0.2, 0.2, 0.5));
+
+            PlacementPreviewMaterials {
+                cube,
+                plane,
+                // Over the planes and over the palatability overlay
+                offset: Vec3::new(0., 0.002, 0.),
+                valid,
+                invalid,
+            }
+        }
+    }
+}
+
+mod components {
+    use bevy::prelude::Component;
+
+    #[derive(Component)]
+    pub struct PlacementPreviewComponent;
+
+    #[derive(Component)]
+    pub struct PlacementDragLabelComponent;
+}
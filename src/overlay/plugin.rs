@@ -0,0 +1,84 @@
+use bevy::{
+    input::{keyboard::KeyboardInput, ButtonState},
+    prelude::*,
+};
+
+use super::palatability::{
+    draw_palatability_overlay, setup_palatability_legend, track_hovered_tile,
+    update_palatability_legend, update_palatability_tooltip, HoveredTile,
+    PalatabilityOverlayMaterials,
+};
+use super::placement::{
+    draw_placement_preview, setup_placement_preview, update_placement_drag_label,
+    PlacementPreviewMaterials,
+};
+use super::power::{
+    draw_power_overlay, inspect_power_plant, setup_power_inspector, update_power_inspector,
+    PowerInspector, PowerOverlayMaterials,
+};
+
+pub use resources::*;
+
+pub struct OverlayPlugin;
+
+impl Plugin for OverlayPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(OverlayMode::None)
+            .init_resource::<PowerOverlayMaterials>()
+            .init_resource::<PowerInspector>()
+            .init_resource::<PalatabilityOverlayMaterials>()
+            .init_resource::<HoveredTile>()
+            .init_resource::<PlacementPreviewMaterials>()
+            .add_startup_system(setup_power_inspector)
+            .add_startup_system(setup_palatability_legend)
+            .add_startup_system(setup_placement_preview)
+            .add_system(switch_overlay_mode)
+            .add_system(inspect_power_plant)
+            .add_system(draw_power_overlay)
+            .add_system(update_power_inspector)
+            .add_system(draw_palatability_overlay)
+            .add_system(update_palatability_legend)
+            .add_system(track_hovered_tile)
+            .add_system(draw_placement_preview.after(track_hovered_tile))
+            .add_system(update_placement_drag_label.after(track_hovered_tile))
+            .add_system(update_palatability_tooltip);
+    }
+}
+
+/// Allow the user to switch overlay mode base on the keyboard key.
+/// Selecting the current overlay again disables it
+fn switch_overlay_mode(
+    mut keyboard_input_events: EventReader<KeyboardInput>,
+    mut overlay_mode: ResMut<OverlayMode>,
+) {
+    if let Some(o) = keyboard_input_events
+        .iter()
+        .filter_map(|e| match (e.state, e.key_code) {
+            (ButtonState::Released, Some(KeyCode::F1)) => Some(OverlayMode::Power),
+            (ButtonState::Released, Some(KeyCode::F2)) => Some(OverlayMode::HousePalatability),
+            (ButtonState::Released, Some(KeyCode::F3)) => Some(OverlayMode::OfficePalatability),
+            _ => None,
+        })
+        .next()
+    {
+        let o = if *overlay_mode == o {
+            OverlayMode::None
+        } else {
+            o
+        };
+        info!("Switch OverlayMode to {:?}", o);
+        *overlay_mode = o;
+    }
+}
+
+mod resources {
+    use bevy::prelude::Resource;
+
+    #[derive(Debug, Hash, PartialEq, Eq, Resource)]
+    pub enum OverlayMode {
+        None,
+        Power,
+        HousePalatability,
+        OfficePalatability,
+    }
+}
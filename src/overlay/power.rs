@@ -0,0 +1,288 @@
+use std::fmt::Write;
+
+use bevy::prelude::*;
+use bevy_mod_picking::PickingEvent;
+
+use crate::{
+    building::{
+        BiomassPowerPlantComponent, EditMode, HouseComponent, OfficeComponent, PlaneComponent,
+    },
+    common::EntityId,
+    power::{PowerCoverage, PowerManagerResource},
+    GameTick, UiFont,
+};
+
+use super::plugin::OverlayMode;
+
+pub use self::components::*;
+pub use self::resources::*;
+
+const BAR_MAX_HEIGHT: f32 = 1.;
+const BAR_MIN_HEIGHT: f32 = 0.01;
+
+/// Select the power plant to inspect clicking on it
+pub fn inspect_power_plant(
+    mut events: EventReader<PickingEvent>,
+    overlay_mode: Res<OverlayMode>,
+    edit_mode: Res<EditMode>,
+    producers: Query<&BiomassPowerPlantComponent>,
+    mut power_inspector: ResMut<PowerInspector>,
+) {
+    // When an edit mode is set, the click is used for building
+    if *overlay_mode != OverlayMode::Power || *edit_mode != EditMode::None {
+        return;
+    }
+
+    let entity = events
+        .iter()
+        .filter_map(|e| match e {
+            PickingEvent::Clicked(e) => Some(e),
+            _ => None,
+        })
+        .next();
+
+    let entity = match entity {
+        None => return,
+        Some(entity) => entity,
+    };
+
+    let producer = producers.get(*entity).ok().map(|p| p.0);
+    if power_inspector.producer != producer {
+        info!("Inspect power plant {:?}", producer);
+        power_inspector.producer = producer;
+    }
+}
+
+/// Tint the consumers by their coverage and draw the utilisation of the producers
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn draw_power_overlay(
+    mut commands: Commands,
+    mut game_tick: EventReader<GameTick>,
+    overlay_mode: Res<OverlayMode>,
+    power_inspector: Res<PowerInspector>,
+    power_manager: Res<PowerManagerResource>,
+    materials: Res<PowerOverlayMaterials>,
+    consumers: Query<(Entity, &Transform), Or<(With<HouseComponent>, With<OfficeComponent>)>>,
+    producers: Query<(Entity, &Transform), With<BiomassPowerPlantComponent>>,
+    overlay_items: Query<Entity, With<PowerOverlayComponent>>,
+) {
+    // The power assignments change only on ticks
+    let ticked = game_tick.iter().count() > 0;
+    if !ticked && !overlay_mode.is_changed() && !power_inspector.is_changed() {
+        return;
+    }
+
+    for overlay_item in overlay_items.iter() {
+        commands.entity(overlay_item).despawn();
+    }
+
+    if *overlay_mode != OverlayMode::Power {
+        return;
+    }
+
+    let inspected_consumers = power_inspector
+        .producer
+        .map(|producer| power_manager.get_consumers_assigned_to(&producer))
+        .unwrap_or_default();
+
+    for (entity, transform) in consumers.iter() {
+        let id = entity.to_bits();
+
+        let material = if inspected_consumers.contains(&id) {
+            materials.inspected.clone()
+        } else {
+            match power_manager.get_power_coverage(&id) {
+                Some(PowerCoverage::Covered) => materials.covered.clone(),
+                Some(PowerCoverage::PartiallyCovered) => materials.partially_covered.clone(),
+                Some(PowerCoverage::Uncovered) | None => materials.uncovered.clone(),
+            }
+        };
+
+        commands
+            .spawn(PbrBundle {
+                mesh: materials.tile_mesh.clone(),
+                material,
+                transform: Transform::from_translation(
+                    transform.translation + materials.tile_offset,
+                ),
+                ..default()
+            })
+            .insert(PowerOverlayComponent);
+    }
+
+    for (entity, transform) in producers.iter() {
+        let utilisation = power_manager
+            .get_producer_utilisation(&entity.to_bits())
+            .unwrap_or(0.);
+        let height = (utilisation * BAR_MAX_HEIGHT).max(BAR_MIN_HEIGHT);
+
+        commands
+            .spawn(PbrBundle {
+                mesh: materials.bar_mesh.clone(),
+                material: materials.bar.clone(),
+                transform: Transform::from_translation(
+                    transform.translation + Vec3::new(0., height / 2., 0.),
+                )
+                .with_scale(Vec3::new(1., height, 1.)),
+                ..default()
+            })
+            .insert(PowerOverlayComponent);
+    }
+}
+
+pub fn setup_power_inspector(mut commands: Commands, font: Res<UiFont>) {
+    commands
+        .spawn(
+            TextBundle::from_section(
+                "",
+                TextStyle {
+                    font: font.0.clone(),
+                    font_size: 16.,
+                    color: Color::WHITE,
+                },
+            )
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    top: Val::Px(10.),
+                    left: Val::Px(10.),
+                    ..default()
+                },
+                ..default()
+            }),
+        )
+        .insert(PowerInspectorTextComponent);
+}
+
+/// List the consumers assigned to the inspected power plant
+pub fn update_power_inspector(
+    mut game_tick: EventReader<GameTick>,
+    overlay_mode: Res<OverlayMode>,
+    power_inspector: Res<PowerInspector>,
+    power_manager: Res<PowerManagerResource>,
+    planes: Query<&PlaneComponent>,
+    mut texts: Query<&mut Text, With<PowerInspectorTextComponent>>,
+) {
+    let ticked = game_tick.iter().count() > 0;
+    if !ticked && !overlay_mode.is_changed() && !power_inspector.is_changed() {
+        return;
+    }
+
+    let producer = match (&*overlay_mode, power_inspector.producer) {
+        (OverlayMode::Power, Some(producer)) => Some(producer),
+        _ => None,
+    };
+
+    let value = producer
+        .map(|producer| describe_producer(&power_manager, &planes, producer))
+        .unwrap_or_default();
+
+    for mut text in texts.iter_mut() {
+        text.sections[0].value = value.clone();
+    }
+}
+
+fn describe_producer(
+    power_manager: &PowerManagerResource,
+    planes: &Query<&PlaneComponent>,
+    producer: EntityId,
+) -> String {
+    let position_of = |id: EntityId| {
+        planes
+            .get(Entity::from_bits(id))
+            .map(|p| format!("({}, {})", p.0.x, p.0.y))
+            .unwrap_or_default()
+    };
+
+    let utilisation = power_manager
+        .get_producer_utilisation(&producer)
+        .unwrap_or(0.);
+    let consumers = power_manager.get_consumers_assigned_to(&producer);
+
+    let mut description = format!(
+        "Power plant {}\nutilisation: {:.0}%\nconsumers: {}\n",
+        position_of(producer),
+        utilisation * 100.,
+        consumers.len()
+    );
+    for consumer in consumers {
+        let coverage = power_manager.get_power_coverage(&consumer);
+        writeln!(description, "- {} {:?}", position_of(consumer), coverage)
+            .expect("write into a String never fails");
+    }
+
+    description
+}
+
+mod resources {
+    use bevy::prelude::*;
+
+    use crate::{common::EntityId, resources::ConfigurationResource};
+
+    use super::BAR_MAX_HEIGHT;
+
+    /// The power plant selected by the user
+    #[derive(Resource, Default)]
+    pub struct PowerInspector {
+        pub producer: Option<EntityId>,
+    }
+
+    #[derive(Resource)]
+    pub struct PowerOverlayMaterials {
+        pub tile_mesh: Handle<Mesh>,
+        pub tile_offset: Vec3,
+        pub bar_mesh: Handle<Mesh>,
+        pub covered: Handle<StandardMaterial>,
+        pub partially_covered: Handle<StandardMaterial>,
+        pub uncovered: Handle<StandardMaterial>,
+        pub inspected: Handle<StandardMaterial>,
+        pub bar: Handle<StandardMaterial>,
+    }
+
+    impl FromWorld for PowerOverlayMaterials {
+        fn from_world(world: &mut World) -> Self {
+            let cube_size = world.resource::<ConfigurationResource>().cube_size;
+
+            let mut meshes = world.resource_mut::<Assets<Mesh>>();
+            let tile_mesh = meshes.add(Mesh::from(shape::Plane { size: cube_size }));
+            // The bar is scaled on the utilisation
+            let bar_mesh = meshes.add(Mesh::from(shape::Box::new(
+                cube_size / 3.,
+                BAR_MAX_HEIGHT,
+                cube_size / 3.,
+            )));
+
+            let mut materials = world.resource_mut::<Assets<StandardMaterial>>();
+            let mut overlay_material = |color: Color| {
+                materials.add(StandardMaterial {
+                    base_color: color,
+                    alpha_mode: AlphaMode::Blend,
+                    unlit: true,
+                    ..default()
+                })
+            };
+
+            PowerOverlayMaterials {
+                tile_mesh,
+                // Over the houses
+                tile_offset: Vec3::new(0., cube_size / 2. + 0.001, 0.),
+                bar_mesh,
+                covered: overlay_material(Color::rgba(0.1, 0.8, 0.1, 0.6)),
+                partially_covered: overlay_material(Color::rgba(0.9, 0.7, 0.1, 0.6)),
+                uncovered: overlay_material(Color::rgba(0.9, 0.1, 0.1, 0.6)),
+                inspected: overlay_material(Color::rgba(0.1, 0.5, 0.9, 0.8)),
+                bar: overlay_material(Color::rgba(1., 1., 1., 0.9)),
+            }
+        }
+    }
+}
+
+mod components {
+    use bevy::prelude::Component;
+
+    #[derive(Component)]
+    pub struct PowerOverlayComponent;
+
+    #[derive(Component)]
+    pub struct PowerInspectorTextComponent;
+}
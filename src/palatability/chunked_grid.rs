@@ -0,0 +1,153 @@
+use std::collections::BTreeMap;
+
+use crate::common::position::Position;
+
+/// Side of the square chunks
+pub const CHUNK_SIZE: usize = 32;
+const CHUNK_SIDE: i64 = CHUNK_SIZE as i64;
+
+type Chunk<T> = Box<[T; CHUNK_SIZE * CHUNK_SIZE]>;
+
+/// A dense grid split in square chunks, allocated on the first write.
+/// Every chunk is stored by rows, so a row inside a chunk is contiguous in memory
+pub struct ChunkedGrid<T> {
+    chunks: BTreeMap<(i64, i64), Chunk<T>>,
+}
+
+impl<T: Copy + Default> Default for ChunkedGrid<T> {
+    fn default() -> Self {
+        Self {
+            chunks: Default::default(),
+        }
+    }
+}
+
+impl<T: Copy + Default> ChunkedGrid<T> {
+    pub fn get(&self, position: &Position) -> T {
+        let (chunk, index) = locate(position);
+        self.chunks
+            .get(&chunk)
+            .map_or_else(T::default, |chunk| chunk[index])
+    }
+
+    pub fn get_mut(&mut self, position: &Position) -> &mut T {
+        let (chunk, index) = locate(position);
+        &mut self.chunk_mut(chunk)[index]
+    }
+
+    /// Call `f` on every row slice of the rectangle between `min` and `max`, included.
+    /// The position is the first one of the slice
+    pub fn for_each_row_mut(
+        &mut self,
+        min: &Position,
+        max: &Position,
+        mut f: impl FnMut(Position, &mut [T]),
+    ) {
+        for chunk_y in min.y.div_euclid(CHUNK_SIDE)..=max.y.div_euclid(CHUNK_SIDE) {
+            for chunk_x in min.x.div_euclid(CHUNK_SIDE)..=max.x.div_euclid(CHUNK_SIDE) {
+                let chunk_origin = Position {
+                    x: chunk_x * CHUNK_SIDE,
+                    y: chunk_y * CHUNK_SIDE,
+                };
+                let from_x = (min.x.max(chunk_origin.x) - chunk_origin.x) as usize;
+                let to_x = (max.x.min(chunk_origin.x + CHUNK_SIDE - 1) - chunk_origin.x) as usize;
+                let from_y = (min.y.max(chunk_origin.y) - chunk_origin.y) as usize;
+                let to_y = (max.y.min(chunk_origin.y + CHUNK_SIDE - 1) - chunk_origin.y) as usize;
+
+                let chunk = self.chunk_mut((chunk_x, chunk_y));
+                for y in from_y..=to_y {
+                    let row = &mut chunk[y * CHUNK_SIZE + from_x..=y * CHUNK_SIZE + to_x];
+                    let position = Position {
+                        x: chunk_origin.x + from_x as i64,
+                        y: chunk_origin.y + y as i64,
+                    };
+                    f(position, row);
+                }
+            }
+        }
+    }
+
+    /// Iterate all the cells of the allocated chunks
+    pub fn iter(&self) -> impl Iterator<Item = (Position, T)> + '_ {
+        self.chunks.iter().flat_map(|((chunk_x, chunk_y), chunk)| {
+            chunk.iter().enumerate().map(move |(index, value)| {
+                let position = Position {
+                    x: chunk_x * CHUNK_SIDE + (index % CHUNK_SIZE) as i64,
+                    y: chunk_y * CHUNK_SIDE + (index / CHUNK_SIZE) as i64,
+                };
+                (position, *value)
+            })
+        })
+    }
+
+    /// Set every cell to the default value, keeping the chunks allocated
+    pub fn reset(&mut self) {
+        for chunk in self.chunks.values_mut() {
+            chunk.fill(T::default());
+        }
+    }
+
+    fn chunk_mut(&mut self, chunk: (i64, i64)) -> &mut Chunk<T> {
+        self.chunks
+            .entry(chunk)
+            .or_insert_with(|| Box::new([T::default(); CHUNK_SIZE * CHUNK_SIZE]))
+    }
+}
+
+fn locate(position: &Position) -> ((i64, i64), usize) {
+    let chunk = (
+        position.x.div_euclid(CHUNK_SIDE),
+        position.y.div_euclid(CHUNK_SIDE),
+    );
+    let index = position.y.rem_euclid(CHUNK_SIDE) as usize * CHUNK_SIZE
+        + position.x.rem_euclid(CHUNK_SIDE) as usize;
+    (chunk, index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_and_set() {
+        let mut grid: ChunkedGrid<i32> = ChunkedGrid::default();
+
+        for (x, y) in [(0, 0), (-1, -1), (31, 32), (-33, 64), (1000, -1000)] {
+            let position = Position { x, y };
+            assert_eq!(grid.get(&position), 0);
+            *grid.get_mut(&position) = (x + y) as i32;
+        }
+        for (x, y) in [(0, 0), (-1, -1), (31, 32), (-33, 64), (1000, -1000)] {
+            assert_eq!(grid.get(&Position { x, y }), (x + y) as i32);
+        }
+        assert_eq!(grid.get(&Position { x: 1, y: 0 }), 0);
+    }
+
+    #[test]
+    fn test_for_each_row_mut_covers_the_rectangle() {
+        let mut grid: ChunkedGrid<i32> = ChunkedGrid::default();
+        let min = Position { x: -40, y: -3 };
+        let max = Position { x: 40, y: 35 };
+
+        grid.for_each_row_mut(&min, &max, |start, row| {
+            for (dx, cell) in row.iter_mut().enumerate() {
+                *cell += (start.x + dx as i64 + start.y * 1000) as i32;
+            }
+        });
+
+        for (position, value) in grid.iter() {
+            let inside =
+                (min.x..=max.x).contains(&position.x) && (min.y..=max.y).contains(&position.y);
+            let expected = if inside {
+                (position.x + position.y * 1000) as i32
+            } else {
+                0
+            };
+            assert_eq!(value, expected, "{position:?}");
+        }
+        assert_eq!(
+            grid.iter().filter(|(_, v)| *v != 0).count(),
+            81 * 39 - 1 // (0, 0) is 0
+        );
+    }
+}
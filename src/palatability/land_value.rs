@@ -0,0 +1,93 @@
+use crate::{common::configuration::LandValueConfiguration, power::PowerCoverage};
+
+/// What the land value of a tile depends on
+#[derive(Debug)]
+pub struct LandValueFactors {
+    pub house_palatability: i32,
+    pub street_distance: Option<u32>,
+    pub power_coverage: Option<PowerCoverage>,
+    pub services: u32,
+}
+
+pub fn calculate_land_value(
+    configuration: &LandValueConfiguration,
+    factors: &LandValueFactors,
+) -> i32 {
+    let palatability = configuration.palatability_weight * factors.house_palatability;
+
+    let street_access = match factors.street_distance {
+        Some(distance) if distance <= configuration.max_street_distance => {
+            // The nearest tiles take the whole value
+            let steps = (configuration.max_street_distance + 1) as i32;
+            configuration.street_access_value * (steps - distance as i32) / steps
+        }
+        _ => 0,
+    };
+
+    let power = match factors.power_coverage {
+        Some(PowerCoverage::Covered) => configuration.power_value,
+        Some(PowerCoverage::PartiallyCovered) => configuration.power_value / 2,
+        Some(PowerCoverage::Uncovered) | None => 0,
+    };
+
+    let services =
+        configuration.service_value * factors.services.min(configuration.max_services) as i32;
+
+    (configuration.base_value + palatability + street_access + power + services).max(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::configuration::CONFIGURATION;
+
+    use super::*;
+
+    #[test]
+    fn test_calculate_land_value() {
+        let configuration = &CONFIGURATION.land_value;
+        let factors = |street_distance, power_coverage, services| LandValueFactors {
+            house_palatability: 0,
+            street_distance,
+            power_coverage,
+            services,
+        };
+
+        let base = configuration.base_value;
+        assert_eq!(
+            calculate_land_value(configuration, &factors(None, None, 0)),
+            base
+        );
+
+        let next_to_street = calculate_land_value(configuration, &factors(Some(0), None, 0));
+        let far_from_street = calculate_land_value(configuration, &factors(Some(2), None, 0));
+        let out_of_street = calculate_land_value(
+            configuration,
+            &factors(Some(configuration.max_street_distance + 1), None, 0),
+        );
+        assert_eq!(next_to_street, base + configuration.street_access_value);
+        assert!(next_to_street > far_from_street);
+        assert!(far_from_street > out_of_street);
+        assert_eq!(out_of_street, base);
+
+        assert_eq!(
+            calculate_land_value(
+                configuration,
+                &factors(None, Some(PowerCoverage::Covered), 0)
+            ),
+            base + configuration.power_value
+        );
+
+        // The services are capped
+        assert_eq!(
+            calculate_land_value(configuration, &factors(None, None, 100)),
+            base + configuration.service_value * configuration.max_services as i32
+        );
+
+        // Never negative
+        let polluted = LandValueFactors {
+            house_palatability: -1000,
+            ..factors(None, None, 0)
+        };
+        assert_eq!(calculate_land_value(configuration, &polluted), 0);
+    }
+}
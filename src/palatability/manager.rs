@@ -1,33 +1,56 @@
-use std::sync::Arc;
-
-use bevy::utils::HashMap;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::Arc,
+};
 
 use crate::{
     building::BuildingSnapshot,
     common::{
-        configuration::{Configuration, SourcePalatabilityConfiguration},
+        configuration::{
+            Configuration, DistanceKind, EmissionConfiguration, EnvironmentFieldConfiguration,
+            PalatabilityFalloff, SourcePalatabilityConfiguration,
+        },
         enums::EducationLevel,
         position::Position,
         EntityId,
     },
-    palatability::manager::macros::apply_source,
+    power::PowerCoverage,
+};
+
+use super::{
+    chunked_grid::ChunkedGrid,
+    land_value::{calculate_land_value, LandValueFactors},
 };
 
 use self::macros::palatability_range;
 
+/// Under this value, pollution and noise are dropped
+const MIN_ENVIRONMENT_VALUE: f32 = 0.01;
+
 pub struct PalatabilityManager {
     configuration: Arc<Configuration>,
     total_populations: u64,
     unemployed_inhabitants: Vec<EntityId>,
     vacant_inhabitants: u64,
     vacant_work: u64,
-    // This is the "concrete view" of all palatability sources
-    // This mean we can have some problem on:
-    // - building deletion
-    // - capability of building temporary unavailability
-    // - change value on upgrading / downgrading building values
-    // - other (?)
-    palatability_descriptors: HashMap<Position, PalatabilityDescriptor>,
+    // One chunked grid per field: only the chunks reached by a source are allocated.
+    // Every house and office value is the sum of the contributions of `palatability_sources`.
+    // Pollution and noise are stored here too, but they evolve every tick
+    palatability_fields: PalatabilityFields,
+    // The active sources, kept to subtract their contribution
+    // when they are removed or changed
+    palatability_sources: BTreeMap<EntityId, PalatabilitiesRange>,
+    // Pollution and noise emitted by the buildings and by the busy streets
+    building_emissions: BTreeMap<Position, EmissionConfiguration>,
+    traffic_emissions: BTreeMap<Position, EmissionConfiguration>,
+    // Updated around the created buildings and on the power consumers
+    land_values: BTreeMap<Position, i32>,
+    services: BTreeSet<Position>,
+    power_coverages: BTreeMap<Position, PowerCoverage>,
+    treasury: u64,
+    // The average happiness of the inhabitants with a home
+    city_happiness: Option<u8>,
+    schools: u32,
 }
 impl PalatabilityManager {
     pub fn new(configuration: Arc<Configuration>) -> Self {
@@ -37,43 +60,386 @@ impl PalatabilityManager {
             unemployed_inhabitants: vec![],
             vacant_inhabitants: 0,
             vacant_work: 0,
-            palatability_descriptors: Default::default(),
+            palatability_fields: Default::default(),
+            palatability_sources: Default::default(),
+            building_emissions: Default::default(),
+            traffic_emissions: Default::default(),
+            land_values: Default::default(),
+            services: Default::default(),
+            power_coverages: Default::default(),
+            treasury: 0,
+            city_happiness: None,
+            schools: 0,
         }
     }
 
+    /// Add the contribution of the source.
+    /// If the source is already present, its previous contribution is replaced
     pub(super) fn add_palatability_source(&mut self, source: &BuildingSnapshot) {
+        let id = *source.get_id();
+        self.remove_palatability_source(&id);
+
         let palatabilities_range = get_palatabilities_range(&self.configuration, source);
+        self.apply_palatabilities_range(&palatabilities_range, 1);
+        if let Some(emission) = &palatabilities_range.emission {
+            self.building_emissions
+                .insert(palatabilities_range.origin, emission.clone());
+        }
+
+        self.palatability_sources.insert(id, palatabilities_range);
+    }
+
+    /// Remove the contribution of the source, if present.
+    /// The pollution and the noise already emitted decay over the ticks
+    pub(super) fn remove_palatability_source(&mut self, id: &EntityId) {
+        if let Some(palatabilities_range) = self.palatability_sources.remove(id) {
+            self.apply_palatabilities_range(&palatabilities_range, -1);
+            self.building_emissions.remove(&palatabilities_range.origin);
+        }
+    }
+
+    /// Replace the emissions of the streets with the current commuters
+    pub(super) fn set_street_traffic<'a>(
+        &mut self,
+        traffic: impl IntoIterator<Item = (&'a Position, &'a u32)>,
+    ) {
+        let traffic_emission = &self.configuration.buildings.street.traffic_emission;
+
+        self.traffic_emissions = traffic
+            .into_iter()
+            .filter(|(_, commuters)| **commuters >= traffic_emission.busy_commuters)
+            .map(|(position, commuters)| {
+                let commuters = *commuters as f32;
+                let emission = EmissionConfiguration {
+                    pollution: traffic_emission.per_commuter.pollution * commuters,
+                    noise: traffic_emission.per_commuter.noise * commuters,
+                };
+                (*position, emission)
+            })
+            .collect();
+    }
 
-        if let Some(house_source) = palatabilities_range.house {
-            apply_source!(self, house_source, house_value);
+    /// Emit, diffuse and decay pollution and noise
+    pub(super) fn step_environment(&mut self) {
+        let emissions: Vec<_> = self
+            .building_emissions
+            .iter()
+            .chain(self.traffic_emissions.iter())
+            .collect();
+
+        let fields = &mut self.palatability_fields;
+        let mut pollution: BTreeMap<Position, f32> =
+            fields.pollution.iter().filter(|(_, v)| *v > 0.).collect();
+        let mut noise: BTreeMap<Position, f32> =
+            fields.noise.iter().filter(|(_, v)| *v > 0.).collect();
+        fields.pollution.reset();
+        fields.noise.reset();
+
+        for (position, emission) in emissions {
+            *pollution.entry(*position).or_default() += emission.pollution;
+            *noise.entry(*position).or_default() += emission.noise;
+        }
+
+        let environment = &self.configuration.environment;
+        let pollution = diffuse(pollution, &environment.pollution);
+        let noise = diffuse(noise, &environment.noise);
+
+        for (position, value) in pollution {
+            *fields.pollution.get_mut(&position) = value;
+        }
+        for (position, value) in noise {
+            *fields.noise.get_mut(&position) = value;
+        }
+    }
+
+    pub fn get_environment(&self, position: &Position) -> EnvironmentValues {
+        EnvironmentValues {
+            pollution: self.palatability_fields.pollution.get(position),
+            noise: self.palatability_fields.noise.get(position),
+        }
+    }
+
+    fn apply_palatabilities_range(
+        &mut self,
+        palatabilities_range: &PalatabilitiesRange,
+        sign: i32,
+    ) {
+        if let Some(house_source) = &palatabilities_range.house {
+            apply_range(
+                &mut self.palatability_fields.house_values,
+                house_source,
+                sign,
+            );
         }
 
-        if let Some(office_source) = palatabilities_range.office {
-            apply_source!(self, office_source, office_value);
+        if let Some(office_source) = &palatabilities_range.office {
+            apply_range(
+                &mut self.palatability_fields.office_values,
+                office_source,
+                sign,
+            );
         }
     }
 
     pub fn get_palatability(&self, building: &BuildingSnapshot) -> BuildingPalatability {
         let position = building.get_position();
 
-        let value = self
-            .palatability_descriptors
-            .get(position)
-            .map_or(0, |p| match building {
-                BuildingSnapshot::House(_) => p.house_value,
-                BuildingSnapshot::Office(_) => p.office_value,
-                BuildingSnapshot::Street(_) => 0,
-                BuildingSnapshot::Garden(_) => 0,
-                BuildingSnapshot::BiomassPowerPlant(_) => 0,
-            });
+        let value = match building {
+            BuildingSnapshot::House(_) => self.house_value(position),
+            BuildingSnapshot::Office(_) => self.palatability_fields.office_values.get(position),
+            BuildingSnapshot::Street(_) => 0,
+            BuildingSnapshot::Garden(_) => 0,
+            BuildingSnapshot::BiomassPowerPlant(_) => 0,
+            BuildingSnapshot::Battery(_) => 0,
+            BuildingSnapshot::School(_) => 0,
+        };
 
         BuildingPalatability { value }
     }
 
+    /// The palatability values of the position, for houses and for offices
+    pub fn get_palatability_values(&self, position: &Position) -> PalatabilityValues {
+        PalatabilityValues {
+            house_value: self.house_value(position),
+            office_value: self.palatability_fields.office_values.get(position),
+        }
+    }
+
+    /// Pollution and noise make the houses less palatable
+    fn house_value(&self, position: &Position) -> i32 {
+        let fields = &self.palatability_fields;
+        let environment = &self.configuration.environment;
+        let penalty = fields.pollution.get(position)
+            * environment.pollution.house_palatability_weight
+            + fields.noise.get(position) * environment.noise.house_palatability_weight;
+
+        // Truncate toward 0: a small penalty is ignored
+        fields.house_values.get(position) - penalty as i32
+    }
+
+    /// The sources contributing to the palatability of the position, sorted by id
+    pub fn get_palatability_contributions(
+        &self,
+        position: &Position,
+    ) -> Vec<PalatabilityContribution> {
+        let value = |range: &Option<PalatabilityRange>| {
+            range
+                .as_ref()
+                .map_or(0, |r| calculate_palatability_value(r, position))
+        };
+
+        let mut contributions: Vec<_> = self
+            .palatability_sources
+            .iter()
+            .map(|(id, range)| PalatabilityContribution {
+                source_id: *id,
+                building_name: range.building_name,
+                origin: range.origin,
+                values: PalatabilityValues {
+                    house_value: value(&range.house),
+                    office_value: value(&range.office),
+                },
+            })
+            .filter(|c| c.values != PalatabilityValues::default())
+            .collect();
+        contributions.sort_by_key(|c| c.source_id);
+
+        contributions
+    }
+
+    /// The services increase the land value of the tiles around
+    pub(super) fn register_service(&mut self, position: Position) {
+        self.services.insert(position);
+    }
+
+    /// The schools attract educated inhabitants
+    pub(super) fn register_school(&mut self) {
+        self.schools += 1;
+    }
+
+    /// Recompute the land value of the tiles the building can affect
+    pub(super) fn update_land_values_around(
+        &mut self,
+        building: &BuildingSnapshot,
+        street_distance: impl Fn(&Position, u32) -> Option<u32>,
+    ) {
+        let radius = self.land_value_radius(building);
+        self.update_land_values_in(building.get_position(), radius, &street_distance);
+    }
+
+    /// The demolished building stops contributing, as a source and as a service.
+    /// The land value around is recomputed
+    pub(super) fn remove_building(
+        &mut self,
+        building: &BuildingSnapshot,
+        street_distance: impl Fn(&Position, u32) -> Option<u32>,
+    ) {
+        // Before the source is removed
+        let radius = self.land_value_radius(building);
+        self.remove_palatability_source(building.get_id());
+
+        match building {
+            BuildingSnapshot::Garden(_) => {
+                self.services.remove(building.get_position());
+            }
+            BuildingSnapshot::School(_) => {
+                self.services.remove(building.get_position());
+                self.schools = self.schools.saturating_sub(1);
+            }
+            BuildingSnapshot::House(_)
+            | BuildingSnapshot::Office(_)
+            | BuildingSnapshot::Street(_)
+            | BuildingSnapshot::BiomassPowerPlant(_)
+            | BuildingSnapshot::Battery(_) => {}
+        }
+
+        self.update_land_values_in(building.get_position(), radius, &street_distance);
+    }
+
+    /// How far the building can change the land value
+    fn land_value_radius(&self, building: &BuildingSnapshot) -> i64 {
+        let land_value_configuration = &self.configuration.land_value;
+        let source_distance = self
+            .palatability_sources
+            .get(building.get_id())
+            .map_or(0, |range| range.max_distance());
+        source_distance
+            .max(land_value_configuration.service_radius)
+            .max(land_value_configuration.max_street_distance)
+            .into()
+    }
+
+    fn update_land_values_in(
+        &mut self,
+        origin: &Position,
+        radius: i64,
+        street_distance: &impl Fn(&Position, u32) -> Option<u32>,
+    ) {
+        for x in (origin.x - radius)..=(origin.x + radius) {
+            for y in (origin.y - radius)..=(origin.y + radius) {
+                self.update_land_value(Position { x, y }, street_distance);
+            }
+        }
+    }
+
+    /// Replace the power coverage of the consumers, refreshing their land value.
+    /// It runs every tick, so the land value of the consumers follows pollution and noise too
+    pub(super) fn update_power_coverages(
+        &mut self,
+        power_coverages: Vec<(Position, PowerCoverage)>,
+        street_distance: impl Fn(&Position, u32) -> Option<u32>,
+    ) {
+        self.power_coverages = power_coverages.into_iter().collect();
+
+        let positions: Vec<_> = self.power_coverages.keys().copied().collect();
+        for position in positions {
+            self.update_land_value(position, &street_distance);
+        }
+    }
+
+    fn update_land_value(
+        &mut self,
+        position: Position,
+        street_distance: &impl Fn(&Position, u32) -> Option<u32>,
+    ) {
+        let configuration = &self.configuration.land_value;
+        let service_radius = configuration.service_radius;
+
+        let factors = LandValueFactors {
+            house_palatability: self.get_palatability_values(&position).house_value,
+            street_distance: street_distance(&position, configuration.max_street_distance),
+            power_coverage: self.power_coverages.get(&position).copied(),
+            services: self
+                .services
+                .iter()
+                .filter(|s| s.distance(&position) <= service_radius)
+                .count() as u32,
+        };
+
+        let land_value = calculate_land_value(configuration, &factors);
+        self.land_values.insert(position, land_value);
+    }
+
+    pub fn get_land_value(&self, position: &Position) -> i32 {
+        self.land_values
+            .get(position)
+            .copied()
+            .unwrap_or(self.configuration.land_value.base_value)
+    }
+
+    /// The residents an house can host on its land, if more than the default ones
+    pub fn get_house_density(&self, position: &Position) -> Option<u32> {
+        let land_value = self.get_land_value(position);
+
+        self.configuration
+            .land_value
+            .house_densities
+            .iter()
+            .take_while(|density| density.min_land_value <= land_value)
+            .last()
+            .map(|density| density.max_residents)
+    }
+
+    /// Collect the taxes of the residents, based on the land value of their houses
+    pub(super) fn collect_taxes(&mut self, houses: impl Iterator<Item = (Position, u32)>) -> u64 {
+        let taxes: u64 = houses
+            .map(|(position, residents)| {
+                (residents as f32 * self.get_taxes_per_resident(&position)) as u64
+            })
+            .sum();
+        self.treasury += taxes;
+
+        taxes
+    }
+
+    /// The taxes paid every tick by a resident of the position
+    pub fn get_taxes_per_resident(&self, position: &Position) -> f32 {
+        self.get_land_value(position) as f32 * self.configuration.land_value.tax_rate
+    }
+
+    pub fn treasury(&self) -> u64 {
+        self.treasury
+    }
+
+    /// Pay from the treasury: false, and nothing is paid, if it isn't enough
+    pub fn spend(&mut self, amount: u64) -> bool {
+        match self.treasury.checked_sub(amount) {
+            None => false,
+            Some(treasury) => {
+                self.treasury = treasury;
+                true
+            }
+        }
+    }
+
+    /// Give back what was spent
+    pub fn refund(&mut self, amount: u64) {
+        self.treasury += amount;
+    }
+
+    pub fn set_city_happiness(&mut self, city_happiness: Option<u8>) {
+        self.city_happiness = city_happiness;
+    }
+
+    pub fn city_happiness(&self) -> Option<u8> {
+        self.city_happiness
+    }
+
     pub(super) fn add_unemployed_inhabitants(&mut self, inhabitants: Vec<EntityId>) {
         self.unemployed_inhabitants.extend(inhabitants);
     }
 
+    /// The newborn takes a spot that the immigrants can't have
+    pub(super) fn add_newborn(&mut self) {
+        self.total_populations += 1;
+        self.increment_vacant_inhabitants(-1);
+    }
+
+    pub(super) fn remove_inhabitant(&mut self, inhabitant: &EntityId) {
+        self.total_populations = self.total_populations.saturating_sub(1);
+        self.unemployed_inhabitants.retain(|id| id != inhabitant);
+    }
+
     pub(super) fn increment_vacant_work(&mut self, delta: i32) {
         self.vacant_work = (self.vacant_work as i128 + delta as i128).max(0) as u64;
     }
@@ -130,33 +496,112 @@ impl PalatabilityManager {
         self.vacant_inhabitants
     }
 
+    /// The education of the new inhabitants: the families with some education
+    /// move only to the cities with schools
     fn current_eduction_level(&self) -> EducationLevel {
-        EducationLevel::None
+        if self.schools > 0 {
+            EducationLevel::Low
+        } else {
+            EducationLevel::None
+        }
     }
 }
 
-#[derive(Debug)]
-struct PalatabilityDescriptor {
-    house_value: i32,
-    office_value: i32,
+/// Every field has its own grid, so a source updates contiguous rows of values
+#[derive(Default)]
+struct PalatabilityFields {
+    house_values: ChunkedGrid<i32>,
+    office_values: ChunkedGrid<i32>,
+    pollution: ChunkedGrid<f32>,
+    noise: ChunkedGrid<f32>,
+}
+
+/// Add the values of the range, or subtract them with a negative `sign`
+fn apply_range(grid: &mut ChunkedGrid<i32>, range: &PalatabilityRange, sign: i32) {
+    let max = i64::from(range.max_distance());
+    let min = Position {
+        x: range.origin.x - max,
+        y: range.origin.y - max,
+    };
+    let max = Position {
+        x: range.origin.x + max,
+        y: range.origin.y + max,
+    };
+
+    // The values of the square centered on the origin, computed once by row
+    let side = (max.x - min.x + 1) as usize;
+    let kernel: Vec<i32> = (min.y..=max.y)
+        .flat_map(|y| {
+            (min.x..=max.x)
+                .map(move |x| sign * calculate_palatability_value(range, &Position { x, y }))
+        })
+        .collect();
+
+    grid.for_each_row_mut(&min, &max, |start, row| {
+        let offset = (start.y - min.y) as usize * side + (start.x - min.x) as usize;
+        let kernel_row = &kernel[offset..offset + row.len()];
+        for (cell, value) in row.iter_mut().zip(kernel_row) {
+            *cell += value;
+        }
+    });
+}
+
+/// Move part of every value to the neighbors, then decay them
+fn diffuse(
+    field: BTreeMap<Position, f32>,
+    configuration: &EnvironmentFieldConfiguration,
+) -> BTreeMap<Position, f32> {
+    let kept = (1. - configuration.diffusion_rate) * configuration.decay_rate;
+    let spread = configuration.diffusion_rate / 4. * configuration.decay_rate;
+
+    let mut next: BTreeMap<Position, f32> = BTreeMap::default();
+    for (position, value) in field {
+        *next.entry(position).or_default() += value * kept;
+        for neighbor in position.neighbors() {
+            *next.entry(neighbor).or_default() += value * spread;
+        }
+    }
+    next.retain(|_, value| *value >= MIN_ENVIRONMENT_VALUE);
+
+    next
 }
 
 fn calculate_palatability_value(range: &PalatabilityRange, position: &Position) -> i32 {
-    let distance = range.origin.distance(position);
+    let distance = match range.distance {
+        DistanceKind::Manhattan => range.origin.distance(position) as f32,
+        DistanceKind::Euclidean => range.origin.euclidean_distance(position),
+    };
 
-    if distance < range.max_horizontal_distribution_distance {
+    if distance < range.max_horizontal_distribution_distance as f32 {
         return range.origin_value;
     }
 
-    if distance < range.max_linear_distribution_distance {
-        let d = (distance - range.max_horizontal_distribution_distance) as i32;
+    if distance < range.max_falloff_distribution_distance as f32 {
+        let d = distance - range.max_horizontal_distribution_distance as f32;
+        let value = range.origin_value as f32;
 
-        let value = range.origin_value - range.linear_factor * d;
-        if range.origin_value > 0 {
-            return value.max(0);
-        } else {
-            return value.min(0);
-        }
+        let value = match &range.falloff {
+            PalatabilityFalloff::Flat => value,
+            PalatabilityFalloff::Linear { factor } => {
+                let v = value - *factor as f32 * d;
+                // The decay never changes the sign of the value
+                if range.origin_value > 0 {
+                    v.max(0.)
+                } else {
+                    v.min(0.)
+                }
+            }
+            PalatabilityFalloff::Exponential { half_life } => {
+                value * 0.5_f32.powf(d / (*half_life).max(1) as f32)
+            }
+            PalatabilityFalloff::InverseSquare => value / (1. + d).powi(2),
+            PalatabilityFalloff::LookupTable(table) => table
+                .get(d as usize)
+                .map_or(0., |percentage| value * f32::from(*percentage) / 100.),
+        };
+
+        // Truncate toward 0: the decay never increases the value
+        return value as i32;
     }
 
     0
@@ -164,8 +609,22 @@ fn calculate_palatability_value(range: &PalatabilityRange, position: &Position)
 
 #[derive(Debug)]
 struct PalatabilitiesRange {
+    building_name: &'static str,
+    origin: Position,
     house: Option<PalatabilityRange>,
     office: Option<PalatabilityRange>,
+    emission: Option<EmissionConfiguration>,
+}
+impl PalatabilitiesRange {
+    /// Over this distance, the source gives nothing
+    fn max_distance(&self) -> u32 {
+        [&self.house, &self.office]
+            .into_iter()
+            .flatten()
+            .map(PalatabilityRange::max_distance)
+            .max()
+            .unwrap_or(0)
+    }
 }
 
 #[derive(Debug)]
@@ -173,8 +632,16 @@ struct PalatabilityRange {
     origin: Position,
     origin_value: i32,
     max_horizontal_distribution_distance: u32,
-    max_linear_distribution_distance: u32,
-    linear_factor: i32,
+    max_falloff_distribution_distance: u32,
+    falloff: PalatabilityFalloff,
+    distance: DistanceKind,
+}
+impl PalatabilityRange {
+    /// Over this distance, the range gives nothing
+    fn max_distance(&self) -> u32 {
+        self.max_falloff_distribution_distance
+            .max(self.max_horizontal_distribution_distance)
+    }
 }
 
 fn get_palatabilities_range(
@@ -189,6 +656,8 @@ fn get_palatabilities_range(
         BuildingSnapshot::BiomassPowerPlant(_) => {
             palatability_range!(configuration, biomass_power_plant, building)
         }
+        BuildingSnapshot::Battery(_) => palatability_range!(configuration, battery, building),
+        BuildingSnapshot::School(_) => palatability_range!(configuration, school, building),
     }
 }
 
@@ -199,8 +668,9 @@ fn get_palatability_wrapper(
         origin,
         origin_value: for_house.value,
         max_horizontal_distribution_distance: for_house.max_horizontal_distribution_distance,
-        max_linear_distribution_distance: for_house.max_linear_distribution_distance,
-        linear_factor: for_house.linear_factor,
+        max_falloff_distribution_distance: for_house.max_falloff_distribution_distance,
+        falloff: for_house.falloff.clone(),
+        distance: for_house.distance.clone(),
     }
 }
 
@@ -216,6 +686,26 @@ impl BuildingPalatability {
     }
 }
 
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct PalatabilityValues {
+    pub house_value: i32,
+    pub office_value: i32,
+}
+
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub struct EnvironmentValues {
+    pub pollution: f32,
+    pub noise: f32,
+}
+
+#[derive(Debug)]
+pub struct PalatabilityContribution {
+    pub source_id: EntityId,
+    pub building_name: &'static str,
+    pub origin: Position,
+    pub values: PalatabilityValues,
+}
+
 pub struct InhabitantToSpawn {
     pub education_level: EducationLevel,
 }
@@ -238,38 +728,566 @@ mod macros {
                 .as_ref();
 
             PalatabilitiesRange {
+                building_name: $configuration.buildings.$name.common.building_name,
+                origin: *$building.get_position(),
                 house: for_house.map(get_palatability_wrapper(*$building.get_position())),
                 office: for_office.map(get_palatability_wrapper(*$building.get_position())),
+                emission: $configuration
+                    .buildings
+                    .$name
+                    .palatability_configuration
+                    .emission
+                    .clone(),
             }
         }};
     }
 
-    macro_rules! apply_source {
-        ($self: ident, $palatability_range: ident, $name: ident) => {
-            let max: i64 = $palatability_range
-                .max_linear_distribution_distance
-                .max($palatability_range.max_horizontal_distribution_distance)
-                .into();
-            (($palatability_range.origin.x - max)..($palatability_range.origin.x + max))
-                .flat_map(|x| {
-                    (($palatability_range.origin.y - max)..($palatability_range.origin.y + max))
-                        .map(move |y| Position { x, y })
-                })
-                .for_each(|position| {
-                    let delta = calculate_palatability_value(&$palatability_range, &position);
+    pub(super) use palatability_range;
+}
 
-                    let entry = $self.palatability_descriptors.entry(position).or_insert(
-                        PalatabilityDescriptor {
-                            house_value: 0,
-                            office_value: 0,
-                        },
-                    );
+#[cfg(test)]
+mod tests {
+    use crate::{
+        building::{
+            BiomassPowerPlantSnapshot, GardenSnapshot, HouseSnapshot, OfficeSnapshot,
+            StreetSnapshot,
+        },
+        common::configuration::CONFIGURATION,
+    };
+
+    use super::*;
+
+    /// Deterministic xorshift, enough to generate the operations
+    struct Rng(u64);
+    impl Rng {
+        fn next(&mut self, max: u64) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0 % max
+        }
+    }
 
-                    entry.$name += delta;
-                });
+    fn random_source(rng: &mut Rng, id: EntityId) -> BuildingSnapshot {
+        let position = Position {
+            x: rng.next(20) as i64,
+            y: rng.next(20) as i64,
         };
+        match rng.next(5) {
+            0 => BuildingSnapshot::House(HouseSnapshot {
+                id,
+                position,
+                current_residents: 0,
+                max_residents: 0,
+            }),
+            1 => BuildingSnapshot::Office(OfficeSnapshot {
+                id,
+                position,
+                current_workers: 0,
+                max_workers: 0,
+            }),
+            2 => BuildingSnapshot::Street(StreetSnapshot { id, position }),
+            3 => BuildingSnapshot::BiomassPowerPlant(BiomassPowerPlantSnapshot { id, position }),
+            _ => BuildingSnapshot::Garden(GardenSnapshot { id, position }),
+        }
     }
 
-    pub(super) use apply_source;
-    pub(super) use palatability_range;
+    /// The field must always be equal to the sum of the active sources
+    fn assert_field_is_sum_of_sources(
+        manager: &PalatabilityManager,
+        active_sources: &BTreeMap<EntityId, BuildingSnapshot>,
+    ) {
+        let configuration = Arc::new(CONFIGURATION);
+
+        for x in -15..35 {
+            for y in -15..35 {
+                let position = Position { x, y };
+
+                let (expected_house, expected_office) = active_sources
+                    .values()
+                    .map(|source| get_palatabilities_range(&configuration, source))
+                    .map(|range| {
+                        let value = |r: &Option<PalatabilityRange>| {
+                            r.as_ref()
+                                .map_or(0, |r| calculate_palatability_value(r, &position))
+                        };
+                        (value(&range.house), value(&range.office))
+                    })
+                    .fold((0, 0), |(h, o), (dh, doff)| (h + dh, o + doff));
+
+                let fields = &manager.palatability_fields;
+                let (house, office) = (
+                    fields.house_values.get(&position),
+                    fields.office_values.get(&position),
+                );
+
+                assert_eq!(house, expected_house, "house value at {position:?}");
+                assert_eq!(office, expected_office, "office value at {position:?}");
+            }
+        }
+    }
+
+    fn range(falloff: PalatabilityFalloff, distance: DistanceKind) -> PalatabilityRange {
+        PalatabilityRange {
+            origin: Position { x: 0, y: 0 },
+            origin_value: 100,
+            max_horizontal_distribution_distance: 2,
+            max_falloff_distribution_distance: 6,
+            falloff,
+            distance,
+        }
+    }
+
+    #[test]
+    fn test_falloff_curves() {
+        let values = |falloff: PalatabilityFalloff| {
+            let range = range(falloff, DistanceKind::Manhattan);
+            (0..=6)
+                .map(|x| calculate_palatability_value(&range, &Position { x, y: 0 }))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            values(PalatabilityFalloff::Flat),
+            vec![100, 100, 100, 100, 100, 100, 0]
+        );
+        assert_eq!(
+            values(PalatabilityFalloff::Linear { factor: 30 }),
+            vec![100, 100, 100, 70, 40, 10, 0]
+        );
+        assert_eq!(
+            values(PalatabilityFalloff::Linear { factor: 60 }),
+            vec![100, 100, 100, 40, 0, 0, 0]
+        );
+        assert_eq!(
+            values(PalatabilityFalloff::Exponential { half_life: 1 }),
+            vec![100, 100, 100, 50, 25, 12, 0]
+        );
+        assert_eq!(
+            values(PalatabilityFalloff::InverseSquare),
+            vec![100, 100, 100, 25, 11, 6, 0]
+        );
+        assert_eq!(
+            values(PalatabilityFalloff::LookupTable(&[100, 50, 10])),
+            vec![100, 100, 100, 50, 10, 0, 0]
+        );
+    }
+
+    #[test]
+    fn test_falloff_distance_kind() {
+        let position = Position { x: 3, y: 4 };
+        let falloff = PalatabilityFalloff::Linear { factor: 10 };
+
+        let manhattan = range(falloff.clone(), DistanceKind::Manhattan);
+        let euclidean = range(falloff, DistanceKind::Euclidean);
+
+        // Out of the falloff distance
+        assert_eq!(calculate_palatability_value(&manhattan, &position), 0);
+        // d = 5 - 2
+        assert_eq!(calculate_palatability_value(&euclidean, &position), 70);
+    }
+
+    #[test]
+    fn test_source_is_symmetric() {
+        let mut manager = PalatabilityManager::new(Arc::new(CONFIGURATION));
+        manager.add_palatability_source(&BuildingSnapshot::Garden(GardenSnapshot {
+            id: 1,
+            position: Position { x: 10, y: 10 },
+        }));
+
+        let value = |x: i64, y: i64| {
+            manager.palatability_fields.house_values.get(&Position {
+                x: 10 + x,
+                y: 10 + y,
+            })
+        };
+
+        for x in -12..=12 {
+            for y in -12..=12 {
+                assert_eq!(value(x, y), value(-x, y), "({x}, {y})");
+                assert_eq!(value(x, y), value(x, -y), "({x}, {y})");
+                assert_eq!(value(x, y), value(y, x), "({x}, {y})");
+            }
+        }
+        assert_eq!(value(0, 7), 2);
+        assert_eq!(value(7, 0), 2);
+        assert_eq!(value(-7, 0), 2);
+        assert_eq!(value(0, -8), 0);
+    }
+
+    #[test]
+    fn test_remove_palatability_source() {
+        let mut manager = PalatabilityManager::new(Arc::new(CONFIGURATION));
+        let garden = BuildingSnapshot::Garden(GardenSnapshot {
+            id: 1,
+            position: Position { x: 5, y: 5 },
+        });
+        let house = BuildingSnapshot::House(HouseSnapshot {
+            id: 2,
+            position: Position { x: 6, y: 5 },
+            current_residents: 0,
+            max_residents: 0,
+        });
+
+        manager.add_palatability_source(&garden);
+        assert_eq!(manager.get_palatability(&house).value, 10);
+
+        manager.remove_palatability_source(garden.get_id());
+        assert_eq!(manager.get_palatability(&house).value, 0);
+
+        // Removing an unknown source does nothing
+        manager.remove_palatability_source(garden.get_id());
+        assert_eq!(manager.get_palatability(&house).value, 0);
+    }
+
+    #[test]
+    fn test_palatability_contributions() {
+        let mut manager = PalatabilityManager::new(Arc::new(CONFIGURATION));
+        manager.add_palatability_source(&BuildingSnapshot::Garden(GardenSnapshot {
+            id: 2,
+            position: Position { x: 5, y: 5 },
+        }));
+        manager.add_palatability_source(&BuildingSnapshot::House(HouseSnapshot {
+            id: 1,
+            position: Position { x: 6, y: 5 },
+            current_residents: 0,
+            max_residents: 0,
+        }));
+
+        let position = Position { x: 7, y: 5 };
+        let contributions = manager.get_palatability_contributions(&position);
+
+        assert_eq!(
+            contributions
+                .iter()
+                .map(|c| (c.source_id, c.building_name, c.values))
+                .collect::<Vec<_>>(),
+            vec![
+                (
+                    1,
+                    "house",
+                    PalatabilityValues {
+                        house_value: -1,
+                        office_value: 0
+                    }
+                ),
+                (
+                    2,
+                    "garden",
+                    PalatabilityValues {
+                        house_value: 10,
+                        office_value: 10
+                    }
+                ),
+            ]
+        );
+        assert_eq!(
+            manager.get_palatability_values(&position),
+            PalatabilityValues {
+                house_value: 9,
+                office_value: 10
+            }
+        );
+
+        // Too far from any source
+        let position = Position { x: 30, y: 30 };
+        assert!(manager.get_palatability_contributions(&position).is_empty());
+        assert_eq!(
+            manager.get_palatability_values(&position),
+            PalatabilityValues::default()
+        );
+    }
+
+    #[test]
+    fn test_environment_diffuses_and_decays() {
+        let mut manager = PalatabilityManager::new(Arc::new(CONFIGURATION));
+        let power_plant = BuildingSnapshot::BiomassPowerPlant(BiomassPowerPlantSnapshot {
+            id: 1,
+            position: Position { x: 0, y: 0 },
+        });
+        manager.add_palatability_source(&power_plant);
+
+        for _ in 0..50 {
+            manager.step_environment();
+        }
+
+        let pollution = |x| manager.get_environment(&Position { x, y: 0 }).pollution;
+        assert!(pollution(0) > pollution(1));
+        assert!(pollution(1) > pollution(3));
+        assert!(pollution(3) > 0.);
+        assert!((pollution(-3) - pollution(3)).abs() < 1e-4);
+        assert_eq!(pollution(100), 0.);
+
+        // Without the source, the pollution disappears
+        manager.remove_palatability_source(power_plant.get_id());
+        for _ in 0..200 {
+            manager.step_environment();
+        }
+        let fields = &manager.palatability_fields;
+        assert!(fields.pollution.iter().all(|(_, v)| v == 0.));
+        assert!(fields.noise.iter().all(|(_, v)| v == 0.));
+    }
+
+    #[test]
+    fn test_environment_reduces_house_palatability() {
+        let mut manager = PalatabilityManager::new(Arc::new(CONFIGURATION));
+        manager.add_palatability_source(&BuildingSnapshot::Garden(GardenSnapshot {
+            id: 1,
+            position: Position { x: 0, y: 0 },
+        }));
+        manager.add_palatability_source(&BuildingSnapshot::BiomassPowerPlant(
+            BiomassPowerPlantSnapshot {
+                id: 2,
+                position: Position { x: 2, y: 0 },
+            },
+        ));
+        let position = Position { x: 1, y: 0 };
+
+        let before = manager.get_palatability_values(&position);
+        for _ in 0..50 {
+            manager.step_environment();
+        }
+        let after = manager.get_palatability_values(&position);
+
+        assert!(after.house_value < before.house_value);
+        assert_eq!(after.office_value, before.office_value);
+    }
+
+    #[test]
+    fn test_busy_streets_are_noisy() {
+        let mut manager = PalatabilityManager::new(Arc::new(CONFIGURATION));
+        let busy_commuters = CONFIGURATION
+            .buildings
+            .street
+            .traffic_emission
+            .busy_commuters;
+        let quiet = Position { x: 0, y: 0 };
+        let busy = Position { x: 10, y: 0 };
+
+        let traffic: BTreeMap<_, _> = [(quiet, busy_commuters - 1), (busy, busy_commuters)]
+            .into_iter()
+            .collect();
+        manager.set_street_traffic(traffic.iter());
+        manager.step_environment();
+
+        assert_eq!(manager.get_environment(&quiet).noise, 0.);
+        assert!(manager.get_environment(&busy).noise > 0.);
+    }
+
+    #[test]
+    fn test_land_value() {
+        let mut manager = PalatabilityManager::new(Arc::new(CONFIGURATION));
+        let configuration = &CONFIGURATION.land_value;
+        let house_position = Position { x: 5, y: 5 };
+        let street_position = Position { x: 5, y: 4 };
+        let street_distance = |position: &Position, max_distance: u32| {
+            Some(street_position.distance(position)).filter(|d| *d <= max_distance)
+        };
+        let garden = BuildingSnapshot::Garden(GardenSnapshot {
+            id: 1,
+            position: Position { x: 7, y: 5 },
+        });
+
+        assert_eq!(
+            manager.get_land_value(&house_position),
+            configuration.base_value
+        );
+
+        manager.add_palatability_source(&garden);
+        manager.register_service(*garden.get_position());
+        manager.update_land_values_around(&garden, street_distance);
+        let with_garden = manager.get_land_value(&house_position);
+        assert!(with_garden > configuration.base_value);
+
+        manager.update_power_coverages(
+            vec![(house_position, PowerCoverage::Covered)],
+            street_distance,
+        );
+        assert_eq!(
+            manager.get_land_value(&house_position),
+            with_garden + configuration.power_value
+        );
+
+        // The demolished garden gives no value anymore
+        manager.remove_building(&garden, street_distance);
+        assert!(manager.get_land_value(&house_position) < with_garden + configuration.power_value);
+
+        // Far away tiles are not affected
+        assert_eq!(
+            manager.get_land_value(&Position { x: 30, y: 30 }),
+            configuration.base_value
+        );
+    }
+
+    #[test]
+    fn test_house_density_and_taxes() {
+        let mut manager = PalatabilityManager::new(Arc::new(CONFIGURATION));
+        let configuration = &CONFIGURATION.land_value;
+        let position = Position { x: 0, y: 0 };
+
+        assert_eq!(manager.get_house_density(&position), None);
+
+        let densities = configuration.house_densities;
+        manager
+            .land_values
+            .insert(position, densities[0].min_land_value);
+        assert_eq!(
+            manager.get_house_density(&position),
+            Some(densities[0].max_residents)
+        );
+        manager.land_values.insert(position, i32::MAX);
+        assert_eq!(
+            manager.get_house_density(&position),
+            densities.last().map(|d| d.max_residents)
+        );
+
+        manager.land_values.insert(position, 100);
+        let taxes =
+            manager.collect_taxes([(position, 10), (Position { x: 1, y: 1 }, 0)].into_iter());
+        assert_eq!(taxes, (10. * 100. * configuration.tax_rate) as u64);
+        manager.collect_taxes([(position, 10)].into_iter());
+        assert_eq!(manager.treasury(), 2 * taxes);
+
+        assert!(!manager.spend(2 * taxes + 1));
+        assert_eq!(manager.treasury(), 2 * taxes);
+        assert!(manager.spend(taxes));
+        assert_eq!(manager.treasury(), taxes);
+        manager.refund(taxes);
+        assert_eq!(manager.treasury(), 2 * taxes);
+    }
+
+    #[test]
+    fn test_field_is_sum_of_active_sources() {
+        for seed in 1..=20u64 {
+            let mut rng = Rng(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15));
+            let mut manager = PalatabilityManager::new(Arc::new(CONFIGURATION));
+            let mut active_sources = BTreeMap::new();
+
+            for _ in 0..30 {
+                // Few ids, so the same source is often changed or removed
+                let id = rng.next(8);
+                if rng.next(3) == 0 {
+                    manager.remove_palatability_source(&id);
+                    active_sources.remove(&id);
+                } else {
+                    let source = random_source(&mut rng, id);
+                    manager.add_palatability_source(&source);
+                    active_sources.insert(id, source);
+                }
+
+                assert_field_is_sum_of_sources(&manager, &active_sources);
+            }
+
+            // Removing everything leaves an empty field
+            for id in 0..8 {
+                manager.remove_palatability_source(&id);
+            }
+            active_sources.clear();
+            assert_field_is_sum_of_sources(&manager, &active_sources);
+        }
+    }
+
+    mod benches {
+        extern crate test;
+
+        use bevy::utils::HashMap;
+        use test::Bencher;
+
+        use super::*;
+
+        const TABLE_SIDE: u64 = 1024;
+
+        fn ranges() -> Vec<PalatabilityRange> {
+            let configuration = Arc::new(CONFIGURATION);
+            let mut rng = Rng(0x9E37_79B9_7F4A_7C15);
+
+            (0..500)
+                .filter_map(|id| {
+                    let garden = BuildingSnapshot::Garden(GardenSnapshot {
+                        id,
+                        position: Position {
+                            x: rng.next(TABLE_SIDE) as i64,
+                            y: rng.next(TABLE_SIDE) as i64,
+                        },
+                    });
+                    get_palatabilities_range(&configuration, &garden).house
+                })
+                .collect()
+        }
+
+        /// How the field was stored before the chunked grid
+        fn apply_range_on_hash_map(map: &mut HashMap<Position, i32>, range: &PalatabilityRange) {
+            let max = i64::from(range.max_distance());
+            for x in (range.origin.x - max)..=(range.origin.x + max) {
+                for y in (range.origin.y - max)..=(range.origin.y + max) {
+                    let position = Position { x, y };
+                    *map.entry(position).or_default() +=
+                        calculate_palatability_value(range, &position);
+                }
+            }
+        }
+
+        fn table() -> impl Iterator<Item = Position> {
+            (0..TABLE_SIDE as i64)
+                .flat_map(|y| (0..TABLE_SIDE as i64).map(move |x| Position { x, y }))
+        }
+
+        #[bench]
+        fn bench_apply_sources_on_hash_map(b: &mut Bencher) {
+            let ranges = ranges();
+            b.iter(|| {
+                let mut map = HashMap::default();
+                for range in &ranges {
+                    apply_range_on_hash_map(&mut map, range);
+                }
+                map
+            });
+        }
+
+        #[bench]
+        fn bench_apply_sources_on_chunked_grid(b: &mut Bencher) {
+            let ranges = ranges();
+            b.iter(|| {
+                let mut grid = ChunkedGrid::default();
+                for range in &ranges {
+                    apply_range(&mut grid, range, 1);
+                }
+                grid
+            });
+        }
+
+        #[bench]
+        fn bench_read_table_on_hash_map(b: &mut Bencher) {
+            let mut map = HashMap::default();
+            for range in &ranges() {
+                apply_range_on_hash_map(&mut map, range);
+            }
+            b.iter(|| {
+                table()
+                    .map(|p| map.get(&p).copied().unwrap_or(0))
+                    .sum::<i32>()
+            });
+        }
+
+        #[bench]
+        fn bench_read_table_on_chunked_grid(b: &mut Bencher) {
+            let mut grid = ChunkedGrid::default();
+            for range in &ranges() {
+                apply_range(&mut grid, range, 1);
+            }
+            b.iter(|| table().map(|p| grid.get(&p)).sum::<i32>());
+        }
+
+        #[test]
+        fn test_chunked_grid_is_equal_to_hash_map() {
+            let mut map = HashMap::default();
+            let mut grid = ChunkedGrid::default();
+            for range in &ranges() {
+                apply_range_on_hash_map(&mut map, range);
+                apply_range(&mut grid, range, 1);
+            }
+
+            assert!(table().all(|p| map.get(&p).copied().unwrap_or(0) == grid.get(&p)));
+        }
+    }
 }
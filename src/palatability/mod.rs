@@ -1,3 +1,5 @@
+mod chunked_grid;
+mod land_value;
 #[cfg(not(test))]
 mod manager;
 
@@ -5,6 +7,7 @@ mod manager;
 pub mod manager;
 
 mod plugin;
+pub use manager::PalatabilityValues;
 pub use plugin::{
     MoreInhabitantsNeeded, MoreWorkersNeeded, PalatabilityManagerResource, PalatabilityPlugin,
 };
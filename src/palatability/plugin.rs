@@ -1,11 +1,21 @@
+use std::collections::BTreeMap;
+
 use bevy::prelude::*;
 
-use crate::building::BuildingSnapshot;
+use crate::building::{BuildingManagerResource, BuildingSnapshot};
 
 use crate::GameTick;
 
-use crate::building::events::BuildingCreatedEvent;
-use crate::inhabitant::events::HomeAssignedToInhabitantEvent;
+use crate::building::events::{
+    BuildingCreatedEvent, BuildingDemolishedEvent, HouseDensityUpgradedEvent,
+};
+use crate::clock::events::{NewDayEvent, NewMonthEvent};
+use crate::clock::GameClockResource;
+use crate::inhabitant::events::{
+    HomeAssignedToInhabitantEvent, InhabitantBornEvent, InhabitantLeftEvent, InhabitantRetiredEvent,
+};
+use crate::navigation::NavigatorResource;
+use crate::power::PowerManagerResource;
 use crate::resources::ConfigurationResource;
 
 pub use self::events::*;
@@ -24,10 +34,28 @@ impl Plugin for PalatabilityPlugin {
             .add_event::<MoreInhabitantsNeeded>()
             .add_event::<MoreWorkersNeeded>()
             .add_system_to_stage(CoreStage::Last, increment_palatabilities)
+            .add_system_to_stage(
+                CoreStage::Last,
+                update_land_values.after(increment_palatabilities),
+            )
+            .add_system_to_stage(
+                CoreStage::Last,
+                forget_demolished_buildings.after(update_land_values),
+            )
             .add_system_to_stage(CoreStage::PostUpdate, habit_house)
+            .add_system_to_stage(CoreStage::PostUpdate, welcome_newborns)
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                leave_house.after(habit_house).after(welcome_newborns),
+            )
             .add_system(try_spawn_inhabitants)
             .add_system(try_spawn_workers)
-            .add_system(increment_vacant_spot);
+            .add_system(free_workplaces_of_retirees)
+            .add_system(increment_vacant_spot)
+            .add_system(step_environment)
+            .add_system(refresh_consumers_land_value)
+            .add_system(collect_taxes)
+            .add_system(report_city);
     }
 }
 
@@ -40,6 +68,147 @@ fn increment_palatabilities(
     }
 }
 
+/// The land value changes around the new buildings
+fn update_land_values(
+    mut building_created_reader: EventReader<BuildingCreatedEvent>,
+    navigator: Res<NavigatorResource>,
+    mut palatability: ResMut<PalatabilityManagerResource>,
+) {
+    for building_created in building_created_reader.iter() {
+        let building_snapshot = &building_created.building_snapshot;
+
+        match building_snapshot {
+            BuildingSnapshot::Garden(garden) => palatability.register_service(garden.position),
+            BuildingSnapshot::School(school) => {
+                palatability.register_service(school.position);
+                palatability.register_school();
+            }
+            BuildingSnapshot::House(_)
+            | BuildingSnapshot::Office(_)
+            | BuildingSnapshot::Street(_)
+            | BuildingSnapshot::BiomassPowerPlant(_)
+            | BuildingSnapshot::Battery(_) => {}
+        }
+
+        palatability.update_land_values_around(building_snapshot, |position, max_distance| {
+            navigator.get_distance_to_nearest_street(position, max_distance)
+        });
+    }
+}
+
+/// The demolished buildings stop contributing and their vacant spots disappear.
+/// The evicted inhabitants free their spots when they leave: the whole capacity goes
+fn forget_demolished_buildings(
+    mut building_demolished_reader: EventReader<BuildingDemolishedEvent>,
+    navigator: Res<NavigatorResource>,
+    mut palatability: ResMut<PalatabilityManagerResource>,
+) {
+    for building_demolished in building_demolished_reader.iter() {
+        let building_snapshot = &building_demolished.building_snapshot;
+
+        match building_snapshot {
+            BuildingSnapshot::House(house) => {
+                palatability.increment_vacant_inhabitants(-(house.max_residents as i32));
+            }
+            BuildingSnapshot::Office(office) => {
+                let delta = office.max_workers - office.current_workers;
+                palatability.increment_vacant_work(-(delta as i32));
+            }
+            BuildingSnapshot::Garden(_)
+            | BuildingSnapshot::Street(_)
+            | BuildingSnapshot::BiomassPowerPlant(_)
+            | BuildingSnapshot::Battery(_)
+            | BuildingSnapshot::School(_) => {}
+        }
+
+        palatability.remove_building(building_snapshot, |position, max_distance| {
+            navigator.get_distance_to_nearest_street(position, max_distance)
+        });
+    }
+}
+
+fn refresh_consumers_land_value(
+    mut game_tick: EventReader<GameTick>,
+    navigator: Res<NavigatorResource>,
+    power_manager: Res<PowerManagerResource>,
+    mut palatability: ResMut<PalatabilityManagerResource>,
+) {
+    if GameTick::count(&mut game_tick) == 0 {
+        return;
+    }
+
+    palatability.update_power_coverages(
+        power_manager.get_power_coverages(),
+        |position, max_distance| navigator.get_distance_to_nearest_street(position, max_distance),
+    );
+}
+
+fn collect_taxes(
+    mut game_tick: EventReader<GameTick>,
+    building_manager: Res<BuildingManagerResource>,
+    mut palatability: ResMut<PalatabilityManagerResource>,
+) {
+    for _ in 0..GameTick::count(&mut game_tick) {
+        let houses = building_manager
+            .get_houses()
+            .map(|house| (house.position, house.current_residents));
+        let taxes = palatability.collect_taxes(houses);
+        debug!(
+            "taxes collected: {taxes}, treasury: {}",
+            palatability.treasury()
+        );
+    }
+}
+
+/// Pollution and noise evolve on every tick
+fn step_environment(
+    mut game_tick: EventReader<GameTick>,
+    navigator: Res<NavigatorResource>,
+    clock: Res<GameClockResource>,
+    mut palatability: ResMut<PalatabilityManagerResource>,
+) {
+    let ticks = GameTick::count(&mut game_tick);
+    if ticks == 0 {
+        return;
+    }
+
+    // Fewer commuters out of the rush hours
+    let traffic_percentage = clock.traffic_percentage();
+    let traffic: BTreeMap<_, _> = navigator
+        .get_traffic()
+        .iter()
+        .map(|(position, commuters)| (*position, commuters * traffic_percentage / 100))
+        .collect();
+    palatability.set_street_traffic(&traffic);
+    for _ in 0..ticks {
+        palatability.step_environment();
+    }
+}
+
+/// Daily and monthly reports about the city
+fn report_city(
+    mut new_day_reader: EventReader<NewDayEvent>,
+    mut new_month_reader: EventReader<NewMonthEvent>,
+    palatability: Res<PalatabilityManagerResource>,
+) {
+    for new_day in new_day_reader.iter() {
+        debug!(
+            "{}: population {}, happiness {:?}",
+            new_day.date,
+            palatability.total_populations(),
+            palatability.city_happiness()
+        );
+    }
+    for new_month in new_month_reader.iter() {
+        info!(
+            "{}/{}: treasury {}",
+            new_month.month,
+            new_month.year,
+            palatability.treasury()
+        );
+    }
+}
+
 fn habit_house(
     mut inhabitant_arrived_writer: EventReader<HomeAssignedToInhabitantEvent>,
     mut palatability: ResMut<PalatabilityManagerResource>,
@@ -57,27 +226,69 @@ fn habit_house(
     palatability.add_unemployed_inhabitants(inhabitants);
 }
 
-fn try_spawn_inhabitants(
-    mut game_tick: EventReader<GameTick>,
+fn welcome_newborns(
+    mut inhabitant_born_reader: EventReader<InhabitantBornEvent>,
     mut palatability: ResMut<PalatabilityManagerResource>,
-    mut more_inhabitants_needed_writer: EventWriter<MoreInhabitantsNeeded>,
 ) {
-    if game_tick.iter().count() == 0 {
-        return;
+    for born in inhabitant_born_reader.iter() {
+        palatability.add_newborn();
+        debug!(
+            "inhabitant {} born in {}",
+            born.inhabitant_entity_id, born.house_entity_id
+        );
     }
+}
 
-    let inhabitants_to_spawn =
-        palatability.consume_inhabitants_to_spawn_and_increment_populations();
-    if inhabitants_to_spawn.is_empty() {
-        return;
+/// The spots left by the inhabitants can host new ones, the same for the workplaces
+fn leave_house(
+    mut inhabitant_left_reader: EventReader<InhabitantLeftEvent>,
+    mut palatability: ResMut<PalatabilityManagerResource>,
+) {
+    for left in inhabitant_left_reader.iter() {
+        palatability.remove_inhabitant(&left.inhabitant_entity_id);
+        palatability.increment_vacant_inhabitants(1);
+        if left.work_place_entity_id.is_some() {
+            palatability.increment_vacant_work(1);
+        }
+
+        let population = palatability.total_populations();
+        info!(
+            "population count: {population:?}, left for {:?}",
+            left.reason
+        );
     }
+}
+
+/// The retirees leave their workplace to the unemployed
+fn free_workplaces_of_retirees(
+    mut inhabitant_retired_reader: EventReader<InhabitantRetiredEvent>,
+    mut palatability: ResMut<PalatabilityManagerResource>,
+) {
+    let retirees = inhabitant_retired_reader.iter().count();
+    if retirees > 0 {
+        palatability.increment_vacant_work(retirees as i32);
+    }
+}
+
+fn try_spawn_inhabitants(
+    mut game_tick: EventReader<GameTick>,
+    mut palatability: ResMut<PalatabilityManagerResource>,
+    mut more_inhabitants_needed_writer: EventWriter<MoreInhabitantsNeeded>,
+) {
+    for _ in 0..GameTick::count(&mut game_tick) {
+        let inhabitants_to_spawn =
+            palatability.consume_inhabitants_to_spawn_and_increment_populations();
+        if inhabitants_to_spawn.is_empty() {
+            return;
+        }
 
-    more_inhabitants_needed_writer.send(MoreInhabitantsNeeded {
-        inhabitants_to_spawn,
-    });
+        more_inhabitants_needed_writer.send(MoreInhabitantsNeeded {
+            inhabitants_to_spawn,
+        });
 
-    let population = palatability.total_populations();
-    info!("population count: {population:?}");
+        let population = palatability.total_populations();
+        info!("population count: {population:?}");
+    }
 }
 
 fn try_spawn_workers(
@@ -85,11 +296,9 @@ fn try_spawn_workers(
     mut palatability: ResMut<PalatabilityManagerResource>,
     mut more_workers_needed_writer: EventWriter<MoreWorkersNeeded>,
 ) {
-    if game_tick.iter().count() == 0 {
-        return;
-    }
-
-    let workers = palatability.consume_workers_to_spawn();
+    let workers: Vec<_> = (0..GameTick::count(&mut game_tick))
+        .flat_map(|_| palatability.consume_workers_to_spawn())
+        .collect();
     if workers.is_empty() {
         return;
     }
@@ -102,8 +311,15 @@ fn try_spawn_workers(
 
 fn increment_vacant_spot(
     mut building_created_reader: EventReader<BuildingCreatedEvent>,
+    mut house_density_upgraded_reader: EventReader<HouseDensityUpgradedEvent>,
     mut palatability: ResMut<PalatabilityManagerResource>,
 ) {
+    for house_density_upgraded in house_density_upgraded_reader.iter() {
+        let delta = house_density_upgraded.added_residents;
+        info!("increment_vacant_inhabitants by {} after upgrade", delta);
+        palatability.increment_vacant_inhabitants(delta as i32);
+    }
+
     for building_created in building_created_reader.iter() {
         match &building_created.building_snapshot {
             BuildingSnapshot::House(house) => {
@@ -120,7 +336,9 @@ fn increment_vacant_spot(
             }
             BuildingSnapshot::Garden(_)
             | BuildingSnapshot::Street(_)
-            | BuildingSnapshot::BiomassPowerPlant(_) => {}
+            | BuildingSnapshot::BiomassPowerPlant(_)
+            | BuildingSnapshot::Battery(_)
+            | BuildingSnapshot::School(_) => {}
         }
     }
 }
@@ -1,28 +1,38 @@
-use std::{collections::HashMap, sync::Arc};
-
-use bevy::utils::HashSet;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::Arc,
+};
 
 use crate::{
     building::BuildingSnapshot,
     common::{configuration::Configuration, position::Position, EntityId},
 };
 
+use super::strategy::{create_strategy, PowerAssignmentStrategy, ProducerCandidate};
+
 pub struct PowerManager {
     configuration: Arc<Configuration>,
-    consumers: HashMap<EntityId, EnergyPowerConsumer>,
-    not_yet_covered_consumers: HashSet<EntityId>,
-    producers: HashMap<EntityId, EnergyPowerProducer>,
-    // customer -> producer(s)[]
-    assignments: HashMap<EntityId, Vec<EntityId>>,
+    consumers: BTreeMap<EntityId, EnergyPowerConsumer>,
+    not_yet_covered_consumers: BTreeSet<EntityId>,
+    producers: BTreeMap<EntityId, EnergyPowerProducer>,
+    // Storages behave as consumers when there's surplus and as producers when
+    // the demand exceeds the supply: they cover the consumers left by the producers
+    storages: BTreeMap<EntityId, EnergyPowerStorage>,
+    // customer -> producer(s) and storage(s)[]
+    assignments: BTreeMap<EntityId, Vec<EntityId>>,
+    strategy: Box<dyn PowerAssignmentStrategy>,
 }
 
 impl PowerManager {
     pub fn new(configuration: Arc<Configuration>) -> Self {
+        let strategy = create_strategy(&configuration.power.assignment_strategy);
         Self {
+            strategy,
             configuration,
             consumers: Default::default(),
             not_yet_covered_consumers: Default::default(),
             producers: Default::default(),
+            storages: Default::default(),
             assignments: Default::default(),
         }
     }
@@ -57,7 +67,10 @@ impl PowerManager {
             // The following ones are not considered as consumer of electric power
             BuildingSnapshot::Garden(_)
             | BuildingSnapshot::Street(_)
-            | BuildingSnapshot::BiomassPowerPlant(_) => return,
+            | BuildingSnapshot::BiomassPowerPlant(_)
+            | BuildingSnapshot::School(_) => return,
+            // Batteries are handled by `register_power_storage`
+            BuildingSnapshot::Battery(_) => return,
         };
 
         debug_assert!(
@@ -75,17 +88,20 @@ impl PowerManager {
         let energy_power_producer = match building {
             BuildingSnapshot::Office(_) | BuildingSnapshot::House(_) => return,
             BuildingSnapshot::Garden(_) | BuildingSnapshot::Street(_) => return,
+            BuildingSnapshot::School(_) => return,
+            // Batteries are handled by `register_power_storage`
+            BuildingSnapshot::Battery(_) => return,
             BuildingSnapshot::BiomassPowerPlant(_) => {
-                let total_capacity_wh = self
+                let power_source = &self
                     .configuration
                     .buildings
                     .biomass_power_plant
-                    .power_source
-                    .capacity_wh;
+                    .power_source;
                 EnergyPowerProducer {
                     position: *building.get_position(),
-                    total_capacity_wh,
-                    remain_capacity_wh: total_capacity_wh,
+                    total_capacity_wh: power_source.capacity_wh,
+                    remain_capacity_wh: power_source.capacity_wh,
+                    priority: power_source.priority,
                 }
             }
         };
@@ -100,9 +116,163 @@ impl PowerManager {
             .insert(*building.get_id(), energy_power_producer);
     }
 
+    pub fn register_power_storage(&mut self, building: &BuildingSnapshot) {
+        let energy_power_storage = match building {
+            BuildingSnapshot::Office(_) | BuildingSnapshot::House(_) => return,
+            BuildingSnapshot::Garden(_) | BuildingSnapshot::Street(_) => return,
+            BuildingSnapshot::BiomassPowerPlant(_) | BuildingSnapshot::School(_) => return,
+            BuildingSnapshot::Battery(_) => {
+                let power_storage = &self.configuration.buildings.battery.power_storage;
+                EnergyPowerStorage {
+                    position: *building.get_position(),
+                    capacity_wh: power_storage.capacity_wh,
+                    max_charge_wh: power_storage.max_charge_wh,
+                    max_discharge_wh: power_storage.max_discharge_wh,
+                    state_of_charge_wh: 0,
+                    dedicated_wh: 0,
+                }
+            }
+        };
+
+        debug_assert!(
+            !self.storages.contains_key(building.get_id()),
+            "storage {} already registered",
+            building.get_id()
+        );
+
+        self.storages
+            .insert(*building.get_id(), energy_power_storage);
+    }
+
     pub fn register_new_inhabitants_at_home(&mut self, house_id: EntityId, inhabitant_delta: u32) {
-        let house = self.consumers.get_mut(&house_id).unwrap();
+        // The house could be demolished before the inhabitants arrived
+        let house = match self.consumers.get_mut(&house_id) {
+            None => return,
+            Some(house) => house,
+        };
         house.multiplier += inhabitant_delta;
+        // The new request has to be covered too
+        self.not_yet_covered_consumers.insert(house_id);
+    }
+
+    /// The consumer requests less power: the exceeding coverage goes back to its producers
+    pub fn register_inhabitants_left(&mut self, consumer_id: &EntityId, inhabitant_delta: u32) {
+        // The consumer could be demolished with its inhabitants
+        let consumer = match self.consumers.get_mut(consumer_id) {
+            None => return,
+            Some(consumer) => consumer,
+        };
+        consumer.multiplier = consumer.multiplier.saturating_sub(inhabitant_delta);
+
+        let exceeding = consumer.covered.saturating_sub(consumer.requested());
+        consumer.covered -= exceeding;
+        self.release_coverage(consumer_id, exceeding);
+    }
+
+    /// The building is demolished: a consumer gives back its coverage,
+    /// the consumers of a producer or of a storage have to be covered again
+    pub fn unregister(&mut self, building_id: &EntityId) {
+        if let Some(consumer) = self.consumers.remove(building_id) {
+            self.release_coverage(building_id, consumer.covered);
+            self.assignments.remove(building_id);
+            self.not_yet_covered_consumers.remove(building_id);
+        }
+
+        if self.producers.remove(building_id).is_some()
+            || self.storages.remove(building_id).is_some()
+        {
+            self.uncover_consumers_of(building_id);
+        }
+    }
+
+    /// The consumers lose the supplier and look for their whole request again
+    fn uncover_consumers_of(&mut self, supplier_id: &EntityId) {
+        for consumer_id in self.get_consumers_assigned_to(supplier_id) {
+            let assignments = self.assignments.get_mut(&consumer_id).unwrap();
+            assignments.retain(|id| id != supplier_id);
+
+            // How much the supplier gave is not tracked: start over
+            let consumer = self.consumers.get_mut(&consumer_id).unwrap();
+            let covered = std::mem::take(&mut consumer.covered);
+            self.release_coverage(&consumer_id, covered);
+            self.not_yet_covered_consumers.insert(consumer_id);
+        }
+    }
+
+    /// How much every supplier gives is not tracked: release from the last assigned one
+    fn release_coverage(&mut self, consumer_id: &EntityId, mut exceeding: u32) {
+        let supplier_ids: Vec<EntityId> = self
+            .assignments
+            .get(consumer_id)
+            .into_iter()
+            .flatten()
+            .rev()
+            .copied()
+            .collect();
+        for supplier_id in supplier_ids {
+            if exceeding == 0 {
+                break;
+            }
+            exceeding -= self.give_back(&supplier_id, exceeding);
+        }
+    }
+
+    /// The suppliers not yet assigned to the consumer that can cover its remain, by id
+    fn candidates(
+        &self,
+        suppliers: impl Iterator<Item = (EntityId, Position, u32, u8)>,
+        assignments: &[EntityId],
+        remain: u32,
+    ) -> Vec<ProducerCandidate> {
+        let mut candidates: Vec<ProducerCandidate> = suppliers
+            .filter(|(id, ..)| !assignments.contains(id) && self.remain_capacity(id) >= remain)
+            .map(
+                |(id, position, total_capacity_wh, priority)| ProducerCandidate {
+                    id,
+                    position,
+                    total_capacity_wh,
+                    remain_capacity_wh: self.remain_capacity(&id),
+                    priority,
+                },
+            )
+            .collect();
+        candidates.sort_by_key(|c| c.id);
+        candidates
+    }
+
+    /// What the producer or the storage can still dedicate
+    fn remain_capacity(&self, supplier_id: &EntityId) -> u32 {
+        match self.producers.get(supplier_id) {
+            Some(producer) => producer.remain_capacity_wh,
+            None => self.storages[supplier_id].remain_capacity_wh(),
+        }
+    }
+
+    /// Dedicate up to `wh` of the supplier, return how much
+    fn take(&mut self, supplier_id: &EntityId, wh: u32) -> u32 {
+        let taken = self.remain_capacity(supplier_id).min(wh);
+        match self.producers.get_mut(supplier_id) {
+            Some(producer) => producer.remain_capacity_wh -= taken,
+            None => self.storages.get_mut(supplier_id).unwrap().dedicated_wh += taken,
+        }
+        taken
+    }
+
+    /// Free up to `wh` of the supplier, return how much
+    fn give_back(&mut self, supplier_id: &EntityId, wh: u32) -> u32 {
+        match self.producers.get_mut(supplier_id) {
+            Some(producer) => {
+                let released = (producer.total_capacity_wh - producer.remain_capacity_wh).min(wh);
+                producer.remain_capacity_wh += released;
+                released
+            }
+            None => {
+                let storage = self.storages.get_mut(supplier_id).unwrap();
+                let released = storage.dedicated_wh.min(wh);
+                storage.dedicated_wh -= released;
+                released
+            }
+        }
     }
 
     #[allow(dead_code)]
@@ -120,77 +290,95 @@ impl PowerManager {
         // if it is not enough, try to a new producers
 
         // TODO: avoid to recalculate too many time the same uncovered consumers
-        // TODO: apply a strategy on the assignment: the the time being the "first" producer is used
 
-        let mut changed_consumers: HashMap<EntityId, u32> = HashMap::new();
-        let mut changed_producers: HashMap<EntityId, u32> = HashMap::new();
-
-        for not_yet_covered_consumer in self.not_yet_covered_consumers.iter() {
-            let consumer = self.consumers.get_mut(not_yet_covered_consumer).unwrap();
+        let mut changed_consumers: BTreeMap<EntityId, u32> = BTreeMap::new();
+        let mut changed_producers: BTreeMap<EntityId, u32> = BTreeMap::new();
 
+        let not_yet_covered_consumers: Vec<EntityId> =
+            self.not_yet_covered_consumers.iter().copied().collect();
+        for not_yet_covered_consumer in &not_yet_covered_consumers {
+            let consumer = &self.consumers[not_yet_covered_consumer];
+            let consumer_position = consumer.position;
             let mut remain = consumer.requested() - consumer.covered;
             if remain == 0 {
                 continue;
             }
 
-            // try to put the remains into already assigned producers
-            if let Some(assignments) = self.assignments.get(not_yet_covered_consumer) {
-                for assignment in assignments {
-                    let producer = self.producers.get_mut(assignment).unwrap();
-                    let energy_to_reduce = producer.remain_capacity_wh.min(remain);
-                    producer.remain_capacity_wh -= energy_to_reduce;
-                    remain -= energy_to_reduce;
-                    consumer.covered += energy_to_reduce;
-
-                    let c: &mut u32 = changed_consumers
-                        .entry(*not_yet_covered_consumer)
-                        .or_default();
-                    *c += energy_to_reduce;
-                    let c: &mut u32 = changed_producers.entry(*assignment).or_default();
-                    *c += energy_to_reduce;
-
-                    if remain == 0 {
-                        break;
-                    }
-                }
+            // try to put the remains into already assigned producers and storages
+            let assignments = self
+                .assignments
+                .get(not_yet_covered_consumer)
+                .cloned()
+                .unwrap_or_default();
+            for assignment in &assignments {
+                let energy_to_reduce = self.take(assignment, remain);
+                remain -= energy_to_reduce;
+                self.consumers
+                    .get_mut(not_yet_covered_consumer)
+                    .unwrap()
+                    .covered += energy_to_reduce;
+
+                let c: &mut u32 = changed_consumers
+                    .entry(*not_yet_covered_consumer)
+                    .or_default();
+                *c += energy_to_reduce;
+                let c: &mut u32 = changed_producers.entry(*assignment).or_default();
+                *c += energy_to_reduce;
 
                 if remain == 0 {
-                    continue;
+                    break;
                 }
             }
+            if remain == 0 {
+                continue;
+            }
 
             // If:
             // - the assignments are not sufficient to cover the remain
             // - no assignments
-            // try to find a new assignment
-            // TODO: instead to choose randomly, we probably would like to choose the "best" one
-            let available_producer = self.producers.iter_mut().find(|p| {
-                let already_assigned = self
-                    .assignments
-                    .get(not_yet_covered_consumer)
-                    .map(|assignments| assignments.contains(p.0))
-                    .unwrap_or(false);
-
-                !already_assigned && p.1.remain_capacity_wh >= remain
-            });
-            let available_producer = match available_producer {
-                // No available producers:
-                // - all producer are already assigned and they are not able to handle the load
-                // - not already assigned producers have insufficient capacity
+            // try to find a new producer using the configured strategy.
+            // The storages drain: they are used only when no producer is able to
+            let producers = self
+                .producers
+                .iter()
+                .map(|(id, p)| (*id, p.position, p.total_capacity_wh, p.priority));
+            let storages = self
+                .storages
+                .iter()
+                .map(|(id, s)| (*id, s.position, s.max_discharge_wh, 0));
+            let available_supplier_id = self
+                .strategy
+                .choose_producer(
+                    &consumer_position,
+                    &self.candidates(producers, &assignments, remain),
+                )
+                .or_else(|| {
+                    self.strategy.choose_producer(
+                        &consumer_position,
+                        &self.candidates(storages, &assignments, remain),
+                    )
+                });
+
+            let available_supplier_id = match available_supplier_id {
+                // No available producers nor storages:
+                // - all of them are already assigned and they are not able to handle the load
+                // - not already assigned ones have insufficient capacity
                 // - no producers at all
                 None => continue,
-                Some(available_producer) => available_producer,
+                Some(available_supplier_id) => available_supplier_id,
             };
 
-            let energy_to_reduce = available_producer.1.remain_capacity_wh.min(remain);
-            available_producer.1.remain_capacity_wh -= energy_to_reduce;
-            consumer.covered += energy_to_reduce;
+            let energy_to_reduce = self.take(&available_supplier_id, remain);
+            self.consumers
+                .get_mut(not_yet_covered_consumer)
+                .unwrap()
+                .covered += energy_to_reduce;
 
             let c: &mut u32 = changed_consumers
                 .entry(*not_yet_covered_consumer)
                 .or_default();
             *c += energy_to_reduce;
-            let c: &mut u32 = changed_producers.entry(*available_producer.0).or_default();
+            let c: &mut u32 = changed_producers.entry(available_supplier_id).or_default();
             *c += energy_to_reduce;
 
             let assignments = self
@@ -199,13 +387,13 @@ impl PowerManager {
                 .or_default();
 
             debug_assert!(
-                !assignments.contains(available_producer.0),
-                "producer {} already present for {}",
-                available_producer.0,
+                !assignments.contains(&available_supplier_id),
+                "supplier {} already present for {}",
+                available_supplier_id,
                 not_yet_covered_consumer
             );
 
-            assignments.push(*available_producer.0);
+            assignments.push(available_supplier_id);
         }
 
         // Remove all completely covered consumers
@@ -235,6 +423,81 @@ impl PowerManager {
         }
     }
 
+    /// Compute the energy balance of a single tick.
+    /// The surplus of the producers charges the storages; when the demand exceeds
+    /// the supply, the storages are discharged to cover the difference.
+    /// `demand_percentage` scales the nominal demand of the consumers (100 is nominal).
+    pub fn balance_energy(&mut self, demand_percentage: u32) -> EnergyBalance {
+        let supplied_wh: u64 = self
+            .producers
+            .values()
+            .map(|p| u64::from(p.total_capacity_wh))
+            .sum();
+        let nominal_demand_wh: u64 = self
+            .consumers
+            .values()
+            .map(|c| u64::from(c.requested()))
+            .sum();
+        let demand_wh = nominal_demand_wh * u64::from(demand_percentage) / 100;
+
+        let mut charged_wh = 0;
+        let mut discharged_wh = 0;
+        let mut unserved_wh = 0;
+
+        if supplied_wh >= demand_wh {
+            let mut surplus = supplied_wh - demand_wh;
+            for storage in self.storages.values_mut() {
+                if surplus == 0 {
+                    break;
+                }
+                let charge = storage.chargeable_wh().min(surplus);
+                storage.state_of_charge_wh += charge as u32;
+                surplus -= charge;
+                charged_wh += charge;
+            }
+        } else {
+            let mut deficit = demand_wh - supplied_wh;
+            for storage in self.storages.values_mut() {
+                if deficit == 0 {
+                    break;
+                }
+                let discharge = storage.dischargeable_wh().min(deficit);
+                storage.state_of_charge_wh -= discharge as u32;
+                deficit -= discharge;
+                discharged_wh += discharge;
+            }
+            unserved_wh = deficit;
+        }
+
+        // The drained storages cannot keep what they dedicated
+        let drained: Vec<EntityId> = self
+            .storages
+            .iter()
+            .filter(|(_, storage)| storage.dischargeable_wh() < u64::from(storage.dedicated_wh))
+            .map(|(id, _)| *id)
+            .collect();
+        for storage_id in drained {
+            self.uncover_consumers_of(&storage_id);
+            self.storages.get_mut(&storage_id).unwrap().dedicated_wh = 0;
+        }
+
+        EnergyBalance {
+            supplied_wh,
+            demand_wh,
+            charged_wh,
+            discharged_wh,
+            unserved_wh,
+            stored_wh: self.stored_energy(),
+        }
+    }
+
+    pub fn stored_energy(&self) -> u64 {
+        self.storages
+            .values()
+            .map(|s| u64::from(s.state_of_charge_wh))
+            .sum()
+    }
+
     #[allow(dead_code)]
     pub fn calculate_missing_power_energy(&self) -> u32 {
         self.not_yet_covered_consumers
@@ -243,7 +506,6 @@ impl PowerManager {
             .sum()
     }
 
-    #[allow(dead_code)]
     pub fn is_completely_covered(&self, building_id: &EntityId) -> (u32, bool) {
         if self.producers.contains_key(building_id) {
             return (0, true);
@@ -254,13 +516,62 @@ impl PowerManager {
             Some(c) => (c.requested() - c.covered, c.requested() <= c.covered),
         }
     }
+
+    /// Return `None` if the building is not a consumer
+    pub fn get_power_coverage(&self, building_id: &EntityId) -> Option<PowerCoverage> {
+        let consumer = self.consumers.get(building_id)?;
+
+        let coverage = match self.is_completely_covered(building_id) {
+            (_, true) => PowerCoverage::Covered,
+            (_, false) if consumer.covered > 0 => PowerCoverage::PartiallyCovered,
+            (_, false) => PowerCoverage::Uncovered,
+        };
+        Some(coverage)
+    }
+
+    /// The coverage of every consumer, by position
+    pub fn get_power_coverages(&self) -> Vec<(Position, PowerCoverage)> {
+        self.consumers
+            .iter()
+            .filter_map(|(id, consumer)| Some((consumer.position, self.get_power_coverage(id)?)))
+            .collect()
+    }
+
+    /// Return the percentage of the capacity dedicated to the consumers
+    pub fn get_producer_utilisation(&self, producer_id: &EntityId) -> Option<f32> {
+        let producer = self.producers.get(producer_id)?;
+        if producer.total_capacity_wh == 0 {
+            return Some(0.);
+        }
+
+        let used = producer.total_capacity_wh - producer.remain_capacity_wh;
+        Some(used as f32 / producer.total_capacity_wh as f32)
+    }
+
+    pub fn get_consumers_assigned_to(&self, producer_id: &EntityId) -> Vec<EntityId> {
+        let mut consumers: Vec<EntityId> = self
+            .assignments
+            .iter()
+            .filter(|(_, producers)| producers.contains(producer_id))
+            .map(|(consumer, _)| *consumer)
+            .collect();
+        consumers.sort();
+        consumers
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PowerCoverage {
+    Covered,
+    PartiallyCovered,
+    Uncovered,
 }
 
 #[allow(dead_code)]
 #[derive(Debug)]
 pub struct ChangePowerAssignment {
-    pub(super) consumers: HashMap<EntityId, (u32, u32)>,
-    pub(super) producers: HashMap<EntityId, u32>,
+    pub(super) consumers: BTreeMap<EntityId, (u32, u32)>,
+    pub(super) producers: BTreeMap<EntityId, u32>,
 }
 
 #[allow(dead_code)]
@@ -269,6 +580,7 @@ struct EnergyPowerProducer {
     position: Position,
     total_capacity_wh: u32,
     remain_capacity_wh: u32,
+    priority: u8,
 }
 
 #[allow(dead_code)]
@@ -286,6 +598,47 @@ impl EnergyPowerConsumer {
     }
 }
 
+#[allow(dead_code)]
+#[derive(Debug)]
+struct EnergyPowerStorage {
+    position: Position,
+    capacity_wh: u32,
+    max_charge_wh: u32,
+    max_discharge_wh: u32,
+    state_of_charge_wh: u32,
+    // Promised to the covered consumers
+    dedicated_wh: u32,
+}
+impl EnergyPowerStorage {
+    fn chargeable_wh(&self) -> u64 {
+        u64::from(
+            self.max_charge_wh
+                .min(self.capacity_wh - self.state_of_charge_wh),
+        )
+    }
+
+    fn dischargeable_wh(&self) -> u64 {
+        u64::from(self.max_discharge_wh.min(self.state_of_charge_wh))
+    }
+
+    fn remain_capacity_wh(&self) -> u32 {
+        self.max_discharge_wh
+            .min(self.state_of_charge_wh)
+            .saturating_sub(self.dedicated_wh)
+    }
+}
+
+/// The energy report of a single tick
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnergyBalance {
+    pub supplied_wh: u64,
+    pub demand_wh: u64,
+    pub charged_wh: u64,
+    pub discharged_wh: u64,
+    pub unserved_wh: u64,
+    pub stored_wh: u64,
+}
+
 #[derive(Debug, Hash, PartialEq, Eq)]
 struct ConsumerAssignmentPair {
     consumer: EntityId,
@@ -295,7 +648,12 @@ struct ConsumerAssignmentPair {
 #[cfg(test)]
 mod tests {
 
-    use crate::{building::*, common::configuration::CONFIGURATION};
+    use crate::{
+        building::*,
+        common::configuration::{
+            PowerAssignmentStrategyKind, PowerStorageConfiguration, CONFIGURATION,
+        },
+    };
 
     use super::*;
 
@@ -335,8 +693,8 @@ mod tests {
 
         let change_assignment = manager.dedicate_power_to_consumers();
 
-        assert_eq!(HashMap::new(), change_assignment.consumers);
-        assert_eq!(HashMap::new(), change_assignment.producers);
+        assert_eq!(BTreeMap::new(), change_assignment.consumers);
+        assert_eq!(BTreeMap::new(), change_assignment.producers);
 
         let biomass_power1 = 3_u64;
         let position = Position { x: 3, y: 0 };
@@ -349,7 +707,7 @@ mod tests {
         let change_assignment = manager.dedicate_power_to_consumers();
 
         assert_eq!(
-            HashMap::from([
+            BTreeMap::from([
                 (house1, (manager.consumers[&house1].covered, 0)),
                 (house2, (manager.consumers[&house2].covered, 0)),
                 (office1, (manager.consumers[&office1].covered, 0))
@@ -357,7 +715,7 @@ mod tests {
             change_assignment.consumers
         );
         assert_eq!(
-            HashMap::from([(
+            BTreeMap::from([(
                 biomass_power1,
                 manager.consumers[&house1].covered
                     + manager.consumers[&house2].covered
@@ -367,8 +725,8 @@ mod tests {
         );
 
         let change_assignment = manager.dedicate_power_to_consumers();
-        assert_eq!(HashMap::from([]), change_assignment.consumers);
-        assert_eq!(HashMap::from([]), change_assignment.producers);
+        assert_eq!(BTreeMap::from([]), change_assignment.consumers);
+        assert_eq!(BTreeMap::from([]), change_assignment.producers);
 
         assert_eq!((0, true), manager.is_completely_covered(&house1));
         assert_eq!((0, true), manager.is_completely_covered(&house2));
@@ -432,4 +790,490 @@ mod tests {
         let missing_power = manager.calculate_missing_power_energy();
         assert_eq!(missing_power, 2400 + manager.consumers[&house].requested());
     }
+
+    #[test]
+    fn test_battery_smooths_demand_peaks() {
+        let mut configuration = CONFIGURATION;
+        configuration
+            .buildings
+            .biomass_power_plant
+            .power_source
+            .capacity_wh = 10_000;
+        configuration.buildings.battery.power_storage = PowerStorageConfiguration {
+            capacity_wh: 12_000,
+            max_charge_wh: 5_000,
+            max_discharge_wh: 5_000,
+        };
+        let mut manager = PowerManager::new(Arc::new(configuration));
+
+        let building = &BuildingSnapshot::BiomassPowerPlant(BiomassPowerPlantSnapshot {
+            id: 0,
+            position: Position { x: 0, y: 0 },
+        });
+        manager.register_power_source(building);
+
+        // 5 workers * 2000wh: the nominal demand is exactly the plant capacity
+        let building = &BuildingSnapshot::Office(OfficeSnapshot {
+            id: 1,
+            position: Position { x: 1, y: 0 },
+            current_workers: 5,
+            max_workers: 6,
+        });
+        manager.register_power_consumer(building);
+
+        // Without storages, the evening peak is not served
+        let balance = manager.balance_energy(150);
+        assert_eq!(balance.demand_wh, 15_000);
+        assert_eq!(balance.unserved_wh, 5_000);
+
+        let battery = 2_u64;
+        let building = &BuildingSnapshot::Battery(BatterySnapshot {
+            id: battery,
+            position: Position { x: 2, y: 0 },
+        });
+        manager.register_power_storage(building);
+        // Not a consumer
+        assert_eq!((0, false), manager.is_completely_covered(&battery));
+        assert_eq!(manager.get_power_coverage(&battery), None);
+
+        // During the day, the surplus charges the battery
+        for _ in 0..3 {
+            manager.balance_energy(50);
+        }
+        assert_eq!(manager.stored_energy(), 12_000);
+
+        // During the evening, the battery covers the peak till it is empty
+        let balance = manager.balance_energy(150);
+        assert_eq!(
+            balance,
+            EnergyBalance {
+                supplied_wh: 10_000,
+                demand_wh: 15_000,
+                charged_wh: 0,
+                discharged_wh: 5_000,
+                unserved_wh: 0,
+                stored_wh: 7_000,
+            }
+        );
+        let balance = manager.balance_energy(150);
+        assert_eq!(balance.unserved_wh, 0);
+        assert_eq!(balance.stored_wh, 2_000);
+
+        let balance = manager.balance_energy(150);
+        assert_eq!(balance.discharged_wh, 2_000);
+        assert_eq!(balance.unserved_wh, 3_000);
+        assert_eq!(balance.stored_wh, 0);
+    }
+
+    #[test]
+    fn test_battery_covers_consumers_left_by_producers() {
+        let mut configuration = CONFIGURATION;
+        configuration
+            .buildings
+            .biomass_power_plant
+            .power_source
+            .capacity_wh = 10_000;
+        configuration.buildings.battery.power_storage = PowerStorageConfiguration {
+            capacity_wh: 12_000,
+            max_charge_wh: 6_000,
+            max_discharge_wh: 6_000,
+        };
+        let mut manager = PowerManager::new(Arc::new(configuration));
+
+        let plant = 0_u64;
+        manager.register_power_source(&BuildingSnapshot::BiomassPowerPlant(
+            BiomassPowerPlantSnapshot {
+                id: plant,
+                position: Position { x: 0, y: 0 },
+            },
+        ));
+        let battery = 1_u64;
+        manager.register_power_storage(&BuildingSnapshot::Battery(BatterySnapshot {
+            id: battery,
+            position: Position { x: 1, y: 0 },
+        }));
+        // 2000wh every worker
+        let office = |id, current_workers| {
+            BuildingSnapshot::Office(OfficeSnapshot {
+                id,
+                position: Position { x: 2, y: 0 },
+                current_workers,
+                max_workers: 6,
+            })
+        };
+        let (office1, office2) = (2_u64, 3_u64);
+        manager.register_power_consumer(&office(office1, 4));
+        manager.register_power_consumer(&office(office2, 3));
+
+        // The empty battery cannot cover the second office
+        manager.dedicate_power_to_consumers();
+        assert_eq!(manager.get_consumers_assigned_to(&plant), vec![office1]);
+        assert_eq!(
+            manager.get_power_coverage(&office2),
+            Some(PowerCoverage::Uncovered)
+        );
+
+        // Charged by the surplus, it does
+        manager.balance_energy(0);
+        manager.balance_energy(0);
+        manager.dedicate_power_to_consumers();
+        assert_eq!(manager.get_consumers_assigned_to(&battery), vec![office2]);
+        assert_eq!(
+            manager.get_power_coverage(&office2),
+            Some(PowerCoverage::Covered)
+        );
+
+        // Drained, it gives the office up
+        manager.balance_energy(200);
+        manager.balance_energy(200);
+        assert_eq!(manager.stored_energy(), 0);
+        assert!(manager.get_consumers_assigned_to(&battery).is_empty());
+        assert_eq!(
+            manager.get_power_coverage(&office2),
+            Some(PowerCoverage::Uncovered)
+        );
+
+        // Demolished, its consumers look for another supplier
+        manager.balance_energy(0);
+        manager.dedicate_power_to_consumers();
+        assert_eq!(manager.get_consumers_assigned_to(&battery), vec![office2]);
+        manager.unregister(&battery);
+        assert!(manager.get_consumers_assigned_to(&battery).is_empty());
+        assert_eq!(
+            manager.get_power_coverage(&office2),
+            Some(PowerCoverage::Uncovered)
+        );
+    }
+
+    #[test]
+    fn test_power_coverage_and_utilisation() {
+        let mut configuration = CONFIGURATION;
+        configuration
+            .buildings
+            .biomass_power_plant
+            .power_source
+            .capacity_wh = 10_000;
+        let mut manager = PowerManager::new(Arc::new(configuration));
+
+        let plant = 0_u64;
+        manager.register_power_source(&BuildingSnapshot::BiomassPowerPlant(
+            BiomassPowerPlantSnapshot {
+                id: plant,
+                position: Position { x: 0, y: 0 },
+            },
+        ));
+        let house = 1_u64;
+        manager.register_power_consumer(&BuildingSnapshot::House(HouseSnapshot {
+            id: house,
+            position: Position { x: 1, y: 0 },
+            current_residents: 2,
+            max_residents: 8,
+        }));
+        // 6 workers * 2000wh is more than the plant capacity
+        let office = 2_u64;
+        manager.register_power_consumer(&BuildingSnapshot::Office(OfficeSnapshot {
+            id: office,
+            position: Position { x: 2, y: 0 },
+            current_workers: 6,
+            max_workers: 6,
+        }));
+
+        manager.dedicate_power_to_consumers();
+
+        assert_eq!(
+            manager.get_power_coverage(&house),
+            Some(PowerCoverage::Covered)
+        );
+        assert_eq!(
+            manager.get_power_coverage(&office),
+            Some(PowerCoverage::Uncovered)
+        );
+        assert_eq!(manager.get_power_coverage(&plant), None);
+        assert_eq!(manager.get_producer_utilisation(&plant), Some(0.06));
+        assert_eq!(manager.get_producer_utilisation(&house), None);
+        assert_eq!(manager.get_consumers_assigned_to(&plant), vec![house]);
+
+        manager.register_new_inhabitants_at_home(house, 2);
+        assert_eq!(
+            manager.get_power_coverage(&house),
+            Some(PowerCoverage::PartiallyCovered)
+        );
+    }
+
+    #[test]
+    fn test_inhabitants_left_release_power() {
+        let mut configuration = CONFIGURATION;
+        configuration
+            .buildings
+            .biomass_power_plant
+            .power_source
+            .capacity_wh = 10_000;
+        let mut manager = PowerManager::new(Arc::new(configuration));
+
+        let plant = 0_u64;
+        manager.register_power_source(&BuildingSnapshot::BiomassPowerPlant(
+            BiomassPowerPlantSnapshot {
+                id: plant,
+                position: Position { x: 0, y: 0 },
+            },
+        ));
+        let house = 1_u64;
+        manager.register_power_consumer(&BuildingSnapshot::House(HouseSnapshot {
+            id: house,
+            position: Position { x: 1, y: 0 },
+            current_residents: 4,
+            max_residents: 8,
+        }));
+        manager.dedicate_power_to_consumers();
+        assert_eq!(manager.get_producer_utilisation(&plant), Some(0.12));
+
+        manager.register_inhabitants_left(&house, 2);
+        assert_eq!(
+            manager.get_power_coverage(&house),
+            Some(PowerCoverage::Covered)
+        );
+        assert_eq!(manager.get_producer_utilisation(&plant), Some(0.06));
+
+        // The new inhabitants are covered again
+        manager.register_new_inhabitants_at_home(house, 3);
+        manager.dedicate_power_to_consumers();
+        assert_eq!(
+            manager.get_power_coverage(&house),
+            Some(PowerCoverage::Covered)
+        );
+        assert_eq!(manager.get_producer_utilisation(&plant), Some(0.15));
+    }
+
+    #[test]
+    fn test_new_inhabitants_are_covered_again() {
+        let mut manager = PowerManager::new(Arc::new(CONFIGURATION));
+
+        manager.register_power_source(&BuildingSnapshot::BiomassPowerPlant(
+            BiomassPowerPlantSnapshot {
+                id: 0,
+                position: Position { x: 0, y: 0 },
+            },
+        ));
+        let house = 1_u64;
+        manager.register_power_consumer(&BuildingSnapshot::House(HouseSnapshot {
+            id: house,
+            position: Position { x: 1, y: 0 },
+            current_residents: 2,
+            max_residents: 8,
+        }));
+        manager.dedicate_power_to_consumers();
+        assert_eq!(manager.calculate_missing_power_energy(), 0);
+
+        // Only the new residents are missing
+        manager.register_new_inhabitants_at_home(house, 3);
+        let single_expenditure = CONFIGURATION
+            .buildings
+            .house
+            .power_consumer_configuration
+            .consume_wh;
+        assert_eq!(
+            manager.calculate_missing_power_energy(),
+            3 * single_expenditure
+        );
+
+        let changes = manager.dedicate_power_to_consumers();
+        assert_eq!(
+            changes.consumers,
+            BTreeMap::from([(house, (3 * single_expenditure, 0))])
+        );
+        assert_eq!(manager.calculate_missing_power_energy(), 0);
+
+        // Not a consumer: nothing to cover
+        manager.register_new_inhabitants_at_home(0, 3);
+        assert_eq!(manager.calculate_missing_power_energy(), 0);
+    }
+
+    #[test]
+    fn test_unregister_demolished_buildings() {
+        let mut configuration = CONFIGURATION;
+        configuration
+            .buildings
+            .biomass_power_plant
+            .power_source
+            .capacity_wh = 10_000;
+        let mut manager = PowerManager::new(Arc::new(configuration));
+
+        let plant = |id, x| {
+            BuildingSnapshot::BiomassPowerPlant(BiomassPowerPlantSnapshot {
+                id,
+                position: Position { x, y: 0 },
+            })
+        };
+        let house = |id, x| {
+            BuildingSnapshot::House(HouseSnapshot {
+                id,
+                position: Position { x, y: 1 },
+                current_residents: 4,
+                max_residents: 8,
+            })
+        };
+        let (plant1, plant2, house1, house2) = (0_u64, 1_u64, 2_u64, 3_u64);
+        manager.register_power_source(&plant(plant1, 0));
+        manager.register_power_consumer(&house(house1, 0));
+        manager.register_power_consumer(&house(house2, 1));
+        manager.dedicate_power_to_consumers();
+        assert_eq!(manager.get_producer_utilisation(&plant1), Some(0.24));
+
+        // The demolished house gives its power back
+        manager.unregister(&house1);
+        assert_eq!(manager.get_power_coverage(&house1), None);
+        assert_eq!(manager.get_producer_utilisation(&plant1), Some(0.12));
+        // The inhabitants evicted later are ignored
+        manager.register_inhabitants_left(&house1, 4);
+
+        // Without its plant, the house looks for another one
+        manager.register_power_source(&plant(plant2, 5));
+        manager.unregister(&plant1);
+        assert_eq!(
+            manager.get_power_coverage(&house2),
+            Some(PowerCoverage::Uncovered)
+        );
+        manager.dedicate_power_to_consumers();
+        assert_eq!(
+            manager.get_power_coverage(&house2),
+            Some(PowerCoverage::Covered)
+        );
+        assert_eq!(manager.get_consumers_assigned_to(&plant2), vec![house2]);
+    }
+
+    /// `plants` plants along the x axis, every one surrounded by 3 houses
+    fn create_city(assignment_strategy: PowerAssignmentStrategyKind, plants: u64) -> PowerManager {
+        let mut configuration = CONFIGURATION;
+        configuration.power.assignment_strategy = assignment_strategy;
+        // Every plant can cover 4 houses (8 residents * 300wh = 2400wh)
+        configuration
+            .buildings
+            .biomass_power_plant
+            .power_source
+            .capacity_wh = 10_000;
+        let mut manager = PowerManager::new(Arc::new(configuration));
+
+        for plant in 0..plants {
+            let x = plant as i64 * 20;
+            manager.register_power_source(&BuildingSnapshot::BiomassPowerPlant(
+                BiomassPowerPlantSnapshot {
+                    id: plant,
+                    position: Position { x, y: 0 },
+                },
+            ));
+
+            for delta in 1..=3 {
+                manager.register_power_consumer(&BuildingSnapshot::House(HouseSnapshot {
+                    id: plants + plant * 3 + delta,
+                    position: Position {
+                        x: x + delta as i64,
+                        y: 1,
+                    },
+                    current_residents: 8,
+                    max_residents: 8,
+                }));
+            }
+        }
+
+        manager
+    }
+
+    fn houses_per_producer(manager: &PowerManager) -> BTreeMap<EntityId, usize> {
+        let mut houses_per_producer = BTreeMap::new();
+        for producer in manager.assignments.values().flatten() {
+            *houses_per_producer.entry(*producer).or_default() += 1;
+        }
+        houses_per_producer
+    }
+
+    fn total_distance(manager: &PowerManager) -> u32 {
+        manager
+            .assignments
+            .iter()
+            .flat_map(|(consumer, producers)| {
+                producers.iter().map(|producer| {
+                    manager.consumers[consumer]
+                        .position
+                        .distance(&manager.producers[producer].position)
+                })
+            })
+            .sum()
+    }
+
+    #[test]
+    fn test_assignment_strategies_on_city() {
+        let mut first_fit = create_city(PowerAssignmentStrategyKind::FirstFit, 3);
+        first_fit.dedicate_power_to_consumers();
+        assert_eq!(first_fit.calculate_missing_power_energy(), 0);
+        assert_eq!(
+            houses_per_producer(&first_fit),
+            BTreeMap::from([(0, 4), (1, 4), (2, 1)])
+        );
+
+        let mut priority = create_city(PowerAssignmentStrategyKind::Priority, 3);
+        priority.dedicate_power_to_consumers();
+        // All the plants have the same priority
+        assert_eq!(
+            houses_per_producer(&priority),
+            BTreeMap::from([(0, 4), (1, 4), (2, 1)])
+        );
+
+        let mut nearest = create_city(PowerAssignmentStrategyKind::Nearest, 3);
+        nearest.dedicate_power_to_consumers();
+        assert_eq!(nearest.calculate_missing_power_energy(), 0);
+        assert_eq!(
+            houses_per_producer(&nearest),
+            BTreeMap::from([(0, 3), (1, 3), (2, 3)])
+        );
+        // Every house is covered by the plant next to it
+        assert_eq!(total_distance(&nearest), 3 * (2 + 3 + 4));
+        assert!(total_distance(&nearest) < total_distance(&first_fit));
+
+        let mut least_loaded = create_city(PowerAssignmentStrategyKind::LeastLoaded, 3);
+        least_loaded.dedicate_power_to_consumers();
+        assert_eq!(least_loaded.calculate_missing_power_energy(), 0);
+        assert_eq!(
+            houses_per_producer(&least_loaded),
+            BTreeMap::from([(0, 3), (1, 3), (2, 3)])
+        );
+    }
+
+    mod benches {
+        extern crate test;
+
+        use test::Bencher;
+
+        use super::*;
+
+        fn bench_strategy(b: &mut Bencher, assignment_strategy: PowerAssignmentStrategyKind) {
+            b.iter(|| {
+                let mut manager = create_city(assignment_strategy.clone(), 50);
+                manager.dedicate_power_to_consumers();
+                (
+                    total_distance(&manager),
+                    manager.calculate_missing_power_energy(),
+                )
+            });
+        }
+
+        #[bench]
+        fn bench_first_fit(b: &mut Bencher) {
+            bench_strategy(b, PowerAssignmentStrategyKind::FirstFit);
+        }
+
+        #[bench]
+        fn bench_nearest(b: &mut Bencher) {
+            bench_strategy(b, PowerAssignmentStrategyKind::Nearest);
+        }
+
+        #[bench]
+        fn bench_least_loaded(b: &mut Bencher) {
+            bench_strategy(b, PowerAssignmentStrategyKind::LeastLoaded);
+        }
+
+        #[bench]
+        fn bench_priority(b: &mut Bencher) {
+            bench_strategy(b, PowerAssignmentStrategyKind::Priority);
+        }
+    }
 }
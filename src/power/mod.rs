@@ -1,4 +1,6 @@
 mod manager;
 mod plugin;
+mod strategy;
 
-pub use plugin::PowerPlugin;
+pub use manager::PowerCoverage;
+pub use plugin::{PowerManagerResource, PowerPlugin};
@@ -1,12 +1,17 @@
 use bevy::prelude::*;
 
-use crate::building::events::BuildingCreatedEvent;
-use crate::inhabitant::events::{HomeAssignedToInhabitantEvent, JobAssignedToInhabitantEvent};
+use crate::building::events::{BuildingCreatedEvent, BuildingDemolishedEvent};
+use crate::clock::GameClockResource;
+use crate::inhabitant::events::{
+    HomeAssignedToInhabitantEvent, InhabitantBornEvent, InhabitantLeftEvent,
+    InhabitantRetiredEvent, JobAssignedToInhabitantEvent,
+};
 use crate::resources::ConfigurationResource;
+use crate::GameTick;
 
 use self::components::PowerCoveredComponent;
 pub use self::events::*;
-use self::resources::PowerManagerResource;
+pub use self::resources::*;
 
 use super::manager::PowerManager;
 
@@ -19,9 +24,13 @@ impl Plugin for PowerPlugin {
 
         app.insert_resource(PowerManagerResource(power_manager))
             // .add_event::<PowerCoveredBuildingEvent>()
+            .add_event::<EnergyBalanceReportedEvent>()
             .add_system(register_power_consumers)
+            .add_system(unregister_demolished_buildings.after(register_power_consumers))
             .add_system(dedicate_power_to_consumers)
-            .add_system(increment_power_consumption);
+            .add_system(increment_power_consumption)
+            .add_system(decrement_power_consumption.after(increment_power_consumption))
+            .add_system(balance_energy);
     }
 }
 
@@ -33,6 +42,22 @@ fn register_power_consumers(
         let building_snapshot = &building_created_event.building_snapshot;
         power_manager.register_power_consumer(building_snapshot);
         power_manager.register_power_source(building_snapshot);
+        power_manager.register_power_storage(building_snapshot);
+    }
+}
+
+/// The demolished buildings neither consume nor produce anymore
+fn unregister_demolished_buildings(
+    mut power_manager: ResMut<PowerManagerResource>,
+    mut building_demolished: EventReader<BuildingDemolishedEvent>,
+    mut commands: Commands,
+) {
+    for building_demolished_event in building_demolished.iter() {
+        let building_id = building_demolished_event.building_snapshot.get_id();
+        power_manager.unregister(building_id);
+        commands
+            .entity(Entity::from_bits(*building_id))
+            .remove::<PowerCoveredComponent>();
     }
 }
 
@@ -40,6 +65,7 @@ fn increment_power_consumption(
     mut power_manager: ResMut<PowerManagerResource>,
     mut home_assigned_to_inhabitant_event_reader: EventReader<HomeAssignedToInhabitantEvent>,
     mut job_assigned_to_inhabitant_event_reader: EventReader<JobAssignedToInhabitantEvent>,
+    mut inhabitant_born_reader: EventReader<InhabitantBornEvent>,
 ) {
     for event in home_assigned_to_inhabitant_event_reader.iter() {
         let house_id = event.building_entity_id;
@@ -52,6 +78,29 @@ fn increment_power_consumption(
         let delta_count = u32::try_from(event.workers_entity_ids.len()).unwrap();
         power_manager.register_new_inhabitants_at_home(house_id, delta_count);
     }
+
+    for event in inhabitant_born_reader.iter() {
+        power_manager.register_new_inhabitants_at_home(event.house_entity_id, 1);
+    }
+}
+
+fn decrement_power_consumption(
+    mut power_manager: ResMut<PowerManagerResource>,
+    mut inhabitant_left_reader: EventReader<InhabitantLeftEvent>,
+    mut inhabitant_retired_reader: EventReader<InhabitantRetiredEvent>,
+) {
+    for event in inhabitant_left_reader.iter() {
+        if let Some(house_entity_id) = &event.house_entity_id {
+            power_manager.register_inhabitants_left(house_entity_id, 1);
+        }
+        if let Some(work_place_entity_id) = &event.work_place_entity_id {
+            power_manager.register_inhabitants_left(work_place_entity_id, 1);
+        }
+    }
+
+    for event in inhabitant_retired_reader.iter() {
+        power_manager.register_inhabitants_left(&event.work_place_entity_id, 1);
+    }
 }
 
 fn dedicate_power_to_consumers(
@@ -73,6 +122,24 @@ fn dedicate_power_to_consumers(
     */
 }
 
+/// Charge or discharge the storages, reporting the energy balance of every tick
+fn balance_energy(
+    mut game_tick: EventReader<GameTick>,
+    clock: Res<GameClockResource>,
+    mut power_manager: ResMut<PowerManagerResource>,
+    mut energy_balance_writer: EventWriter<EnergyBalanceReportedEvent>,
+) {
+    // The demand follows the time of the day
+    let demand_percentage = clock.power_demand_percentage();
+
+    for _ in 0..GameTick::count(&mut game_tick) {
+        let energy_balance = power_manager.balance_energy(demand_percentage);
+        debug!("energy balance: {energy_balance:?}");
+
+        energy_balance_writer.send(EnergyBalanceReportedEvent { energy_balance });
+    }
+}
+
 mod resources {
     use std::ops::{Deref, DerefMut};
 
@@ -105,4 +172,11 @@ mod components {
     pub struct PowerCoveredComponent;
 }
 
-mod events {}
+mod events {
+    use crate::power::manager::EnergyBalance;
+
+    #[allow(dead_code)]
+    pub struct EnergyBalanceReportedEvent {
+        pub energy_balance: EnergyBalance,
+    }
+}
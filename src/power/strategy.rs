@@ -0,0 +1,151 @@
+use crate::common::{configuration::PowerAssignmentStrategyKind, position::Position, EntityId};
+
+/// A producer that is able to cover the remain of a consumer
+#[derive(Debug)]
+pub struct ProducerCandidate {
+    pub id: EntityId,
+    pub position: Position,
+    pub total_capacity_wh: u32,
+    pub remain_capacity_wh: u32,
+    pub priority: u8,
+}
+
+/// Choose which producer should cover a consumer.
+/// The candidates are sorted by id, so every strategy is deterministic
+pub trait PowerAssignmentStrategy: Send + Sync {
+    fn choose_producer(
+        &self,
+        consumer_position: &Position,
+        candidates: &[ProducerCandidate],
+    ) -> Option<EntityId>;
+}
+
+pub fn create_strategy(kind: &PowerAssignmentStrategyKind) -> Box<dyn PowerAssignmentStrategy> {
+    match kind {
+        PowerAssignmentStrategyKind::FirstFit => Box::new(FirstFit),
+        PowerAssignmentStrategyKind::Nearest => Box::new(Nearest),
+        PowerAssignmentStrategyKind::LeastLoaded => Box::new(LeastLoaded),
+        PowerAssignmentStrategyKind::Priority => Box::new(Priority),
+    }
+}
+
+/// The first available producer
+pub struct FirstFit;
+impl PowerAssignmentStrategy for FirstFit {
+    fn choose_producer(
+        &self,
+        _consumer_position: &Position,
+        candidates: &[ProducerCandidate],
+    ) -> Option<EntityId> {
+        candidates.first().map(|c| c.id)
+    }
+}
+
+/// The producer nearest to the consumer
+pub struct Nearest;
+impl PowerAssignmentStrategy for Nearest {
+    fn choose_producer(
+        &self,
+        consumer_position: &Position,
+        candidates: &[ProducerCandidate],
+    ) -> Option<EntityId> {
+        candidates
+            .iter()
+            .min_by_key(|c| c.position.distance(consumer_position))
+            .map(|c| c.id)
+    }
+}
+
+/// The producer with the lowest percentage of used capacity
+pub struct LeastLoaded;
+impl PowerAssignmentStrategy for LeastLoaded {
+    fn choose_producer(
+        &self,
+        _consumer_position: &Position,
+        candidates: &[ProducerCandidate],
+    ) -> Option<EntityId> {
+        candidates
+            .iter()
+            .min_by_key(|c| {
+                let used = u64::from(c.total_capacity_wh - c.remain_capacity_wh);
+                // Compare used / total without losing precision
+                (used * 1_000_000)
+                    .checked_div(u64::from(c.total_capacity_wh))
+                    .unwrap_or(u64::MAX)
+            })
+            .map(|c| c.id)
+    }
+}
+
+/// The producer with the highest priority
+pub struct Priority;
+impl PowerAssignmentStrategy for Priority {
+    fn choose_producer(
+        &self,
+        _consumer_position: &Position,
+        candidates: &[ProducerCandidate],
+    ) -> Option<EntityId> {
+        candidates
+            .iter()
+            // `max_by_key` returns the last max element: reverse to keep the first one
+            .rev()
+            .max_by_key(|c| c.priority)
+            .map(|c| c.id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidates() -> Vec<ProducerCandidate> {
+        vec![
+            ProducerCandidate {
+                id: 1,
+                position: Position { x: 10, y: 0 },
+                total_capacity_wh: 1_000,
+                remain_capacity_wh: 500,
+                priority: 0,
+            },
+            ProducerCandidate {
+                id: 2,
+                position: Position { x: 2, y: 0 },
+                total_capacity_wh: 1_000,
+                remain_capacity_wh: 100,
+                priority: 5,
+            },
+            ProducerCandidate {
+                id: 3,
+                position: Position { x: 5, y: 5 },
+                total_capacity_wh: 4_000,
+                remain_capacity_wh: 3_000,
+                priority: 5,
+            },
+        ]
+    }
+
+    #[test]
+    fn test_strategies() {
+        let origin = Position { x: 0, y: 0 };
+        let candidates = candidates();
+
+        assert_eq!(FirstFit.choose_producer(&origin, &candidates), Some(1));
+        assert_eq!(Nearest.choose_producer(&origin, &candidates), Some(2));
+        assert_eq!(LeastLoaded.choose_producer(&origin, &candidates), Some(3));
+        assert_eq!(Priority.choose_producer(&origin, &candidates), Some(2));
+    }
+
+    #[test]
+    fn test_strategies_without_candidates() {
+        let origin = Position { x: 0, y: 0 };
+
+        for kind in [
+            PowerAssignmentStrategyKind::FirstFit,
+            PowerAssignmentStrategyKind::Nearest,
+            PowerAssignmentStrategyKind::LeastLoaded,
+            PowerAssignmentStrategyKind::Priority,
+        ] {
+            assert_eq!(create_strategy(&kind).choose_producer(&origin, &[]), None);
+        }
+    }
+}